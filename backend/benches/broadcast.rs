@@ -0,0 +1,92 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    collections::HashMap,
+    hint::black_box,
+    sync::{atomic::{AtomicUsize, Ordering}, Arc},
+};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use hide_and_seek::{
+    message::{ChatChannel, ServerEvent},
+    protocol::{self, Encoding, Payload, SharedEvent, PROTOCOL_VERSION},
+};
+
+const PLAYERS: usize = 50;
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+fn events() -> Vec<(&'static str, ServerEvent)> {
+    let scores: HashMap<i64, f32> = (0..PLAYERS as i64).map(|id| (id * 7_919_123, id as f32 * 1.5)).collect();
+
+    vec![
+        ("score_update", ServerEvent::ScoreUpdate { scores, seconds_left: 600 }),
+        ("chat", ServerEvent::Chat {
+            sender: 1,
+            message: "anyone near the fountain?".to_string(),
+            image: None,
+            channel: ChatChannel::All,
+        }),
+    ]
+}
+
+// what every session used to do with its own copy of the event
+fn per_session(event: &ServerEvent) -> Vec<Option<Payload>> {
+    (0..PLAYERS).map(|_| {
+        let event = event.clone();
+        protocol::encode(&event, PROTOCOL_VERSION, Encoding::Json)
+    }).collect()
+}
+
+fn shared(event: &ServerEvent) -> Vec<Option<Payload>> {
+    let shared = Arc::new(SharedEvent::new(event.clone()));
+
+    (0..PLAYERS).map(|_| {
+        let event = shared.clone();
+        event.encode(PROTOCOL_VERSION, Encoding::Json)
+    }).collect()
+}
+
+fn allocations(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    f();
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+fn broadcast(c: &mut Criterion) {
+    let mut group = c.benchmark_group("broadcast");
+
+    for (name, event) in events() {
+        println!(
+            "{} to {} players: {} allocations per session, {} shared",
+            name,
+            PLAYERS,
+            allocations(|| { black_box(per_session(&event)); }),
+            allocations(|| { black_box(shared(&event)); }),
+        );
+
+        group.bench_function(format!("{}/per_session", name), |b| b.iter(|| per_session(black_box(&event))));
+        group.bench_function(format!("{}/shared", name), |b| b.iter(|| shared(black_box(&event))));
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, broadcast);
+criterion_main!(benches);
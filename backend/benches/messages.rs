@@ -0,0 +1,95 @@
+use actix::prelude::*;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use hide_and_seek::{
+    game::Game,
+    images::ImageStore,
+    message::*,
+    moderation::WordFilter,
+    outbox::{Outbox, Sink},
+    server::GameServer,
+};
+
+const GAME_COUNTS: [usize; 3] = [10, 1_000, 30_000];
+
+struct Setup {
+    server: Addr<GameServer>,
+    game: Addr<Game>,
+    host: String,
+    guest: i64,
+    guest_token: String,
+}
+
+async fn join(server: &Addr<GameServer>, player: i64, game: u16) -> Addr<Game> {
+    let (addr, info) = server.send(LookupGame { player, game }).await.unwrap().unwrap();
+    addr.send(Join { id: player, connection: info }).await.unwrap();
+    addr
+}
+
+// fills the server with two-player games and returns one of them
+async fn setup(games: usize) -> Setup {
+    let images = ImageStore::new(std::env::temp_dir().join("hide_and_seek_bench")).unwrap();
+    let server = GameServer::new(images, WordFilter::default()).start();
+    let outbox = Outbox::new(Sink.start().recipient());
+
+    let connect = |name: &str| server.send(Connect {
+        outbox: outbox.clone(),
+        name: name.to_string(),
+        score_deltas: false,
+        resume: None,
+    });
+
+    let mut last = None;
+
+    for _ in 0..games {
+        let (host, host_token, _) = connect("host").await.unwrap();
+        let (guest, guest_token, _) = connect("guest").await.unwrap();
+
+        let created = server.send(NewGame { player: host, x: 0.0, y: 0.0, minutes: 10, tag_mode: TagMode::Instant, power_ups: false, radius: None });
+        let id = match created.await.unwrap() {
+            Ok((_, ServerEvent::JoinedGame { id, .. })) => id,
+            _ => panic!("failed to create game"),
+        };
+
+        let game = join(&server, guest, id).await;
+        last = Some((game, host_token, guest, guest_token));
+    }
+
+    let (game, host, guest, guest_token) = last.unwrap();
+
+    // the index is updated asynchronously by the game
+    while server.send(FindGame { token: guest_token.clone() }).await.unwrap().is_none() {
+        actix::clock::sleep(std::time::Duration::from_millis(1)).await;
+    }
+
+    Setup { server, game, host, guest, guest_token }
+}
+
+fn messages(c: &mut Criterion) {
+    let system = System::new();
+    let mut group = c.benchmark_group("messages");
+
+    for games in GAME_COUNTS {
+        let Setup { server, game, host, guest, guest_token } = system.block_on(setup(games));
+        let id = system.block_on(server.send(FindGame { token: host.clone() })).unwrap().unwrap();
+
+        group.bench_with_input(BenchmarkId::new("find_game", games), &host, |b, token| {
+            b.iter(|| system.block_on(server.send(FindGame { token: token.clone() })).unwrap());
+        });
+
+        group.bench_with_input(BenchmarkId::new("leave_join", games), &guest, |b, &guest| {
+            b.iter(|| system.block_on(async {
+                game.send(ClientMessage { sender: guest, event: ClientEvent::LeaveGame }).await.unwrap();
+
+                // wait for the game to report the leave before joining again
+                while server.send(FindGame { token: guest_token.clone() }).await.unwrap().is_some() {}
+                join(&server, guest, id).await;
+            }));
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, messages);
+criterion_main!(benches);
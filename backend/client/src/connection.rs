@@ -0,0 +1,144 @@
+use std::{io, time::Duration};
+
+use actix_codec::Framed;
+use actix_http::ws;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{Map, Value};
+use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::TcpStream, time};
+
+use hide_and_seek_core::event::{ClientEvent, ServerEvent};
+use hide_and_seek_core::protocol::{Encoding, PROTOCOL_VERSION};
+
+use crate::{Config, Error};
+
+const MAX_RESPONSE_HEAD: usize = 8 * 1024;
+
+pub(crate) type Socket = Framed<TcpStream, ws::Codec>;
+
+// only plain ws:// urls, the server doesn't terminate TLS itself
+fn parse_url(url: &str) -> Result<(&str, &str), Error> {
+    let rest = url.strip_prefix("ws://").ok_or_else(|| Error::Protocol(format!("Unsupported url {}", url)))?;
+
+    match rest.find('/') {
+        Some(index) => Ok((&rest[..index], &rest[index..])),
+        None => Ok((rest, "/")),
+    }
+}
+
+async fn open(url: &str) -> Result<Socket, Error> {
+    let (host, path) = parse_url(url)?;
+    let mut stream = TcpStream::connect(host).await?;
+
+    let key = STANDARD.encode(rand::random::<[u8; 16]>());
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: {}\r\nSec-WebSocket-Version: 13\r\n\r\n",
+        path, host, key,
+    );
+    stream.write_all(request.as_bytes()).await?;
+
+    // the server doesn't speak before the client does, so nothing past the
+    // response head can have been sent yet
+    let mut head = Vec::new();
+
+    while !head.ends_with(b"\r\n\r\n") {
+        if head.len() >= MAX_RESPONSE_HEAD {
+            return Err(Error::Protocol("Response head too long".to_string()));
+        }
+
+        let byte = stream.read_u8().await?;
+        head.push(byte);
+    }
+
+    let head = String::from_utf8_lossy(&head);
+    let status = head.lines().next().unwrap_or_default();
+
+    if status.split(' ').nth(1) != Some("101") {
+        return Err(Error::Protocol(format!("Unexpected response: {}", status)));
+    }
+
+    let accept = ws::hash_key(key.as_bytes());
+    let accepted = head.lines().any(|line| match line.split_once(':') {
+        Some((name, value)) => name.eq_ignore_ascii_case("sec-websocket-accept") && value.trim().as_bytes() == accept,
+        None => false,
+    });
+
+    if !accepted {
+        return Err(Error::Protocol("Invalid Sec-WebSocket-Accept".to_string()));
+    }
+
+    Ok(Framed::new(stream, ws::Codec::new().client_mode()))
+}
+
+pub(crate) async fn send(socket: &mut Socket, event: &ClientEvent, request_id: Option<u32>) -> Result<(), Error> {
+    // unit variants serialize to a bare string, which can't carry an id
+    let mut value = match serde_json::to_value(event).unwrap() {
+        Value::String(variant) => Value::Object(Map::from_iter([(variant, Value::Null)])),
+        value => value,
+    };
+
+    if let (Some(request_id), Some(object)) = (request_id, value.as_object_mut()) {
+        object.insert("request_id".to_string(), request_id.into());
+    }
+
+    socket.send(ws::Message::Text(value.to_string().into())).await?;
+    Ok(())
+}
+
+// Opens a connection and completes the Connect handshake, returning the
+// player id and session token the server handed out. Passing the token of an
+// earlier session resumes it if the server still knows it.
+pub(crate) async fn connect(config: &Config, resume: Option<&str>) -> Result<(Socket, i64, String), Error> {
+    let handshake = async {
+        let mut socket = open(&config.url).await?;
+
+        let connect = ClientEvent::Connect {
+            name: config.name.clone(),
+            version: PROTOCOL_VERSION,
+            encoding: Encoding::Json,
+            score_deltas: config.score_deltas,
+            resume: resume.map(str::to_string),
+        };
+        send(&mut socket, &connect, None).await?;
+
+        loop {
+            let frame = match socket.next().await {
+                Some(frame) => frame?,
+                None => return Err(Error::Disconnected),
+            };
+
+            match frame {
+                ws::Frame::Text(text) => match serde_json::from_slice(&text) {
+                    Ok(ServerEvent::Connected { id, token, .. }) => return Ok((socket, id, token)),
+                    Ok(ServerEvent::IncompatibleProtocol { min_version, max_version }) => {
+                        return Err(Error::Incompatible { min_version, max_version });
+                    },
+                    Ok(ServerEvent::Error { code, message, details, .. }) => {
+                        return Err(Error::Server { code, message, details });
+                    },
+                    _ => (),
+                },
+                ws::Frame::Ping(bytes) => socket.send(ws::Message::Pong(bytes)).await?,
+                ws::Frame::Close(_) => return Err(Error::Disconnected),
+                _ => (),
+            }
+        }
+    };
+
+    match time::timeout(config.timeout, handshake).await {
+        Ok(result) => result,
+        Err(_) => Err(Error::Timeout),
+    }
+}
+
+pub(crate) fn backoff(config: &Config, attempt: u32) -> Duration {
+    config.reconnect_delay
+        .checked_mul(2u32.saturating_pow(attempt))
+        .map_or(config.max_reconnect_delay, |delay| delay.min(config.max_reconnect_delay))
+}
+
+impl From<ws::ProtocolError> for Error {
+    fn from(err: ws::ProtocolError) -> Self {
+        Error::Io(io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
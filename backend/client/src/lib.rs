@@ -0,0 +1,420 @@
+mod connection;
+
+use std::{
+    collections::HashMap,
+    fmt,
+    future::poll_fn,
+    io,
+    pin::Pin,
+    sync::{atomic::{AtomicI64, Ordering}, Arc},
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use actix_http::ws;
+use bytes::Bytes;
+use futures_core::Stream;
+use futures_util::{SinkExt, StreamExt};
+use tokio::{sync::{mpsc, oneshot}, time};
+
+pub use hide_and_seek_core::error::{ErrorCode, ErrorDetails};
+pub use hide_and_seek_core::event::*;
+
+use connection::Socket;
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub url: String,
+    pub name: String,
+    pub score_deltas: bool,
+    pub heartbeat_interval: Duration,
+    // how long the server may stay silent before the connection counts as lost
+    pub timeout: Duration,
+    // reconnecting is given up after this many failed attempts in a row
+    pub reconnect_attempts: u32,
+    pub reconnect_delay: Duration,
+    pub max_reconnect_delay: Duration,
+}
+
+impl Config {
+    pub fn new(url: impl Into<String>, name: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            name: name.into(),
+            score_deltas: false,
+            heartbeat_interval: Duration::from_secs(5),
+            timeout: Duration::from_secs(10),
+            reconnect_attempts: 10,
+            reconnect_delay: Duration::from_millis(500),
+            max_reconnect_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Protocol(String),
+    Timeout,
+    Incompatible { min_version: u32, max_version: u32 },
+    Server { code: ErrorCode, message: String, details: Option<ErrorDetails> },
+    // the connection was lost before the request was answered
+    Disconnected,
+    // the client gave up reconnecting
+    Closed,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "{}", err),
+            Error::Protocol(message) => write!(f, "{}", message),
+            Error::Timeout => write!(f, "Connection timed out"),
+            Error::Incompatible { min_version, max_version } => {
+                write!(f, "Server supports protocol versions {} to {} only", min_version, max_version)
+            },
+            Error::Server { code, message, .. } => write!(f, "{} ({:?})", message, code),
+            Error::Disconnected => write!(f, "Connection lost"),
+            Error::Closed => write!(f, "Client closed"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Event {
+    Server(ServerEvent),
+    Disconnected,
+    // a resumed session keeps its id and game, otherwise the id is a new one
+    Reconnected { id: i64, resumed: bool },
+}
+
+type Reply = oneshot::Sender<Result<Option<ServerEvent>, Error>>;
+
+struct Request {
+    event: ClientEvent,
+    reply: Reply,
+}
+
+// Cheap to clone, all clones share one connection. The connection is closed
+// once every clone is dropped.
+#[derive(Clone)]
+pub struct Client {
+    requests: mpsc::UnboundedSender<Request>,
+    id: Arc<AtomicI64>,
+}
+
+// Everything the server sends except acks, in order, plus connection changes.
+// Ends once the client is closed or gives up reconnecting.
+pub struct Events {
+    events: mpsc::UnboundedReceiver<Event>,
+}
+
+pub async fn connect(config: Config) -> Result<(Client, Events), Error> {
+    let (socket, id, token) = connection::connect(&config, None).await?;
+    let (requests, receiver) = mpsc::unbounded_channel();
+    let (sender, events) = mpsc::unbounded_channel();
+    let id = Arc::new(AtomicI64::new(id));
+
+    let driver = Driver {
+        config,
+        id: id.clone(),
+        token,
+        requests: receiver,
+        events: sender,
+        pending: HashMap::new(),
+        next_request: 0,
+        last_event: None,
+        game: None,
+    };
+
+    tokio::spawn(driver.run(socket));
+    Ok((Client { requests, id }, Events { events }))
+}
+
+impl Client {
+    pub fn id(&self) -> i64 {
+        self.id.load(Ordering::Relaxed)
+    }
+
+    // Sends any event and waits for the server to accept or reject it. The
+    // event the server replied with, if any, is returned as well as streamed.
+    pub async fn send(&self, event: ClientEvent) -> Result<Option<ServerEvent>, Error> {
+        let (reply, response) = oneshot::channel();
+        self.requests.send(Request { event, reply }).map_err(|_| Error::Closed)?;
+        response.await.unwrap_or(Err(Error::Closed))
+    }
+
+    async fn command(&self, event: ClientEvent) -> Result<(), Error> {
+        self.send(event).await.map(|_| ())
+    }
+
+    async fn join(&self, event: ClientEvent) -> Result<u16, Error> {
+        match self.send(event).await? {
+            Some(ServerEvent::JoinedGame { id, .. }) => Ok(id),
+            reply => Err(Error::Protocol(format!("Unexpected reply {:?}", reply))),
+        }
+    }
+
+    pub async fn create_game(&self, x: f64, y: f64, minutes: u64, tag_mode: TagMode, power_ups: bool, radius: Option<f64>) -> Result<u16, Error> {
+        self.join(ClientEvent::CreateGame { x, y, minutes, tag_mode, power_ups, radius }).await
+    }
+
+    pub async fn join_game(&self, game: u16) -> Result<u16, Error> {
+        self.join(ClientEvent::JoinGame { game }).await
+    }
+
+    pub async fn leave_game(&self) -> Result<(), Error> {
+        self.command(ClientEvent::LeaveGame).await
+    }
+
+    pub async fn start_game(&self) -> Result<(), Error> {
+        self.command(ClientEvent::StartGame).await
+    }
+
+    pub async fn chat(&self, message: impl Into<String>, image: Option<String>, channel: ChatChannel) -> Result<(), Error> {
+        self.command(ClientEvent::Chat { message: message.into(), image, channel }).await
+    }
+
+    pub async fn mute_player(&self, player: i64) -> Result<(), Error> {
+        self.command(ClientEvent::MutePlayer { player }).await
+    }
+
+    pub async fn unmute_player(&self, player: i64) -> Result<(), Error> {
+        self.command(ClientEvent::UnmutePlayer { player }).await
+    }
+
+    pub async fn add_bot(&self, difficulty: Difficulty, path: Vec<(f64, f64)>) -> Result<(), Error> {
+        self.command(ClientEvent::AddBot { difficulty, path }).await
+    }
+
+    pub async fn remove_bot(&self, player: i64) -> Result<(), Error> {
+        self.command(ClientEvent::RemoveBot { player }).await
+    }
+
+    pub async fn update_position(&self, x: f64, y: f64) -> Result<(), Error> {
+        self.command(ClientEvent::UpdatePosition { x, y }).await
+    }
+
+    pub async fn tag_player(&self, player: i64, image: Option<String>) -> Result<(), Error> {
+        self.command(ClientEvent::TagPlayer { player, image }).await
+    }
+
+    pub async fn tag_with_proof(&self, nonce: impl Into<String>) -> Result<(), Error> {
+        self.command(ClientEvent::TagWithProof { nonce: nonce.into() }).await
+    }
+
+    pub async fn confirm_tag(&self) -> Result<(), Error> {
+        self.command(ClientEvent::ConfirmTag).await
+    }
+
+    pub async fn dispute_tag(&self) -> Result<(), Error> {
+        self.command(ClientEvent::DisputeTag).await
+    }
+
+    // the snapshot arrives on the event stream
+    pub async fn resync_scores(&self) -> Result<(), Error> {
+        self.command(ClientEvent::ResyncScores).await
+    }
+}
+
+impl Events {
+    pub async fn recv(&mut self) -> Option<Event> {
+        self.events.recv().await
+    }
+}
+
+impl Stream for Events {
+    type Item = Event;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Event>> {
+        self.events.poll_recv(cx)
+    }
+}
+
+enum Next {
+    Request(Option<Request>),
+    Frame(Option<Result<ws::Frame, ws::ProtocolError>>),
+    Heartbeat,
+}
+
+// Owns the connection on a task of its own, so heartbeats and reconnection
+// keep running no matter how the client and event stream are used.
+struct Driver {
+    config: Config,
+    id: Arc<AtomicI64>,
+    token: String,
+    requests: mpsc::UnboundedReceiver<Request>,
+    events: mpsc::UnboundedSender<Event>,
+    pending: HashMap<u32, (ClientEvent, Option<Reply>)>,
+    next_request: u32,
+    // the server sends a request's reply right before its ack
+    last_event: Option<ServerEvent>,
+    game: Option<u16>,
+}
+
+impl Driver {
+    async fn run(mut self, mut socket: Socket) {
+        loop {
+            if !self.session(&mut socket).await {
+                let _ = socket.send(ws::Message::Close(None)).await;
+                return;
+            }
+
+            for reply in self.pending.drain().filter_map(|(_, (_, reply))| reply) {
+                let _ = reply.send(Err(Error::Disconnected));
+            }
+
+            self.last_event = None;
+            let _ = self.events.send(Event::Disconnected);
+
+            socket = match self.reconnect().await {
+                Some(socket) => socket,
+                None => return,
+            };
+        }
+    }
+
+    // returns false once every client is dropped, true when the connection is lost
+    async fn session(&mut self, socket: &mut Socket) -> bool {
+        let mut heartbeat = time::interval(self.config.heartbeat_interval);
+        let mut last_seen = Instant::now();
+
+        loop {
+            let next = poll_fn(|cx| {
+                if let Poll::Ready(frame) = socket.poll_next_unpin(cx) {
+                    return Poll::Ready(Next::Frame(frame));
+                }
+
+                if let Poll::Ready(request) = self.requests.poll_recv(cx) {
+                    return Poll::Ready(Next::Request(request));
+                }
+
+                if heartbeat.poll_tick(cx).is_ready() {
+                    return Poll::Ready(Next::Heartbeat);
+                }
+
+                Poll::Pending
+            }).await;
+
+            let result = match next {
+                Next::Request(None) => return false,
+                Next::Request(Some(request)) => self.request(socket, request.event, Some(request.reply)).await,
+                Next::Frame(Some(Ok(frame))) => {
+                    last_seen = Instant::now();
+                    self.frame(socket, frame).await
+                },
+                Next::Frame(_) => Err(Error::Disconnected),
+                Next::Heartbeat if last_seen.elapsed() > self.config.timeout => Err(Error::Timeout),
+                Next::Heartbeat => socket.send(ws::Message::Ping(Bytes::new())).await.map_err(Error::from),
+            };
+
+            if result.is_err() {
+                return true;
+            }
+        }
+    }
+
+    async fn request(&mut self, socket: &mut Socket, event: ClientEvent, reply: Option<Reply>) -> Result<(), Error> {
+        let request_id = self.next_request;
+        self.next_request = self.next_request.wrapping_add(1);
+        let sent = connection::send(socket, &event, Some(request_id)).await;
+        self.pending.insert(request_id, (event, reply));
+        sent
+    }
+
+    async fn frame(&mut self, socket: &mut Socket, frame: ws::Frame) -> Result<(), Error> {
+        match frame {
+            // events added by newer servers are skipped
+            ws::Frame::Text(text) => match serde_json::from_slice(&text) {
+                Ok(event) => self.receive(event),
+                Err(_) => log::debug!("Skipping unknown event: {}", String::from_utf8_lossy(&text)),
+            },
+            ws::Frame::Ping(bytes) => socket.send(ws::Message::Pong(bytes)).await?,
+            ws::Frame::Close(_) => return Err(Error::Disconnected),
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    fn receive(&mut self, event: ServerEvent) {
+        match &event {
+            ServerEvent::Ack { request_id } => {
+                let last_event = self.last_event.take();
+
+                // commands without a reply of their own are acked right
+                // away, so the event before is somebody else's broadcast
+                if let Some((request, Some(reply))) = self.pending.remove(request_id) {
+                    let _ = reply.send(Ok(last_event.filter(|event| is_reply(&request, event))));
+                }
+
+                return;
+            },
+            ServerEvent::Error { code, message, details, request_id: Some(request_id) } => {
+                if let Some((_, Some(reply))) = self.pending.remove(request_id) {
+                    let _ = reply.send(Err(Error::Server { code: *code, message: message.clone(), details: details.clone() }));
+                }
+            },
+            ServerEvent::JoinedGame { id, .. } => self.game = Some(*id),
+            ServerEvent::LeftGame => self.game = None,
+            _ => (),
+        }
+
+        self.last_event = Some(event.clone());
+        let _ = self.events.send(Event::Server(event));
+    }
+
+    // The server keeps a player's place in their game for a while after the
+    // connection goes. Once it has given up on the old session, a game can
+    // only be rejoined while it is still waiting for players.
+    async fn reconnect(&mut self) -> Option<Socket> {
+        for attempt in 0..self.config.reconnect_attempts {
+            time::sleep(connection::backoff(&self.config, attempt)).await;
+
+            let (mut socket, id, token) = match connection::connect(&self.config, Some(&self.token)).await {
+                Ok(value) => value,
+                Err(err) => {
+                    log::warn!("Reconnecting failed: {}", err);
+                    continue;
+                },
+            };
+
+            let resumed = self.id.swap(id, Ordering::Relaxed) == id;
+            self.token = token;
+            let _ = self.events.send(Event::Reconnected { id, resumed });
+
+            if resumed {
+                return Some(socket);
+            }
+
+            if let Some(game) = self.game.take() {
+                if self.request(&mut socket, ClientEvent::JoinGame { game }, None).await.is_err() {
+                    self.game = Some(game);
+                    continue;
+                }
+            }
+
+            return Some(socket);
+        }
+
+        None
+    }
+}
+
+// the only requests the server answers with an event of their own
+fn is_reply(request: &ClientEvent, event: &ServerEvent) -> bool {
+    matches!(
+        (request, event),
+        (ClientEvent::CreateGame { .. } | ClientEvent::JoinGame { .. }, ServerEvent::JoinedGame { .. })
+            | (ClientEvent::LeaveGame, ServerEvent::LeftGame)
+            | (ClientEvent::ResyncScores, ServerEvent::ScoreSnapshot { .. })
+    )
+}
@@ -0,0 +1,165 @@
+use std::{net::SocketAddr, sync::{Arc, Mutex}, time::Duration};
+
+use actix::prelude::*;
+use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+use actix_web_actors::ws;
+use tokio::{net::{TcpListener, TcpStream}, task::JoinHandle, time};
+
+use hide_and_seek::{client::Session, images::ImageStore, moderation::WordFilter, server::GameServer};
+use hide_and_seek_client::*;
+
+async fn entry_point(
+    req: HttpRequest,
+    stream: web::Payload,
+    server: web::Data<Addr<GameServer>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    ws::start(Session::new(server.get_ref().clone()), &req, stream)
+}
+
+async fn start_server(name: &str) -> SocketAddr {
+    let dir = std::env::temp_dir().join(format!("hide_and_seek_client_{}_{}", std::process::id(), name));
+    let server = GameServer::new(ImageStore::new(dir).unwrap(), WordFilter::default()).start();
+
+    let http = HttpServer::new(move || {
+        App::new()
+            .app_data(web::Data::new(server.clone()))
+            .route("/", web::get().to(entry_point))
+    })
+    .workers(1)
+    .bind(("127.0.0.1", 0))
+    .unwrap();
+
+    let addr = http.addrs()[0];
+    actix_web::rt::spawn(http.run());
+    addr
+}
+
+// forwards connections to the server until they are cut
+struct Proxy {
+    addr: SocketAddr,
+    connections: Arc<Mutex<Vec<JoinHandle<()>>>>,
+}
+
+impl Proxy {
+    async fn start(target: SocketAddr) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let connections = Arc::new(Mutex::new(Vec::new()));
+        let handles = connections.clone();
+
+        tokio::spawn(async move {
+            while let Ok((mut client, _)) = listener.accept().await {
+                let handle = tokio::spawn(async move {
+                    let mut server = TcpStream::connect(target).await.unwrap();
+                    let _ = tokio::io::copy_bidirectional(&mut client, &mut server).await;
+                });
+
+                handles.lock().unwrap().push(handle);
+            }
+        });
+
+        Self { addr, connections }
+    }
+
+    fn cut(&self) {
+        for connection in self.connections.lock().unwrap().drain(..) {
+            connection.abort();
+        }
+    }
+}
+
+fn config(addr: SocketAddr, name: &str) -> Config {
+    let mut config = Config::new(format!("ws://{}/", addr), name);
+    config.reconnect_delay = Duration::from_millis(50);
+    config
+}
+
+async fn wait_for<T>(events: &mut Events, find: impl Fn(Event) -> Option<T>) -> T {
+    let search = async {
+        loop {
+            if let Some(found) = find(events.recv().await.expect("event stream ended")) {
+                return found;
+            }
+        }
+    };
+
+    time::timeout(Duration::from_secs(5), search).await.expect("event never arrived")
+}
+
+#[actix_web::test]
+async fn requests_are_answered() {
+    let addr = start_server("requests").await;
+    let (host, mut host_events) = connect(config(addr, "host")).await.unwrap();
+    let (guest, mut guest_events) = connect(config(addr, "guest")).await.unwrap();
+
+    let game = host.create_game(0.0, 0.0, 10, TagMode::Instant, false, None).await.unwrap();
+    assert_eq!(guest.join_game(game).await.unwrap(), game);
+
+    let joined = wait_for(&mut host_events, |event| match event {
+        Event::Server(ServerEvent::PlayerJoined { id, name }) => Some((id, name)),
+        _ => None,
+    }).await;
+    assert_eq!(joined, (guest.id(), "guest".to_string()));
+
+    match guest.start_game().await {
+        Err(Error::Server { code: ErrorCode::NotHost, .. }) => (),
+        result => panic!("unexpected result {:?}", result),
+    }
+
+    // the broadcast right before the ack isn't a reply of its own
+    assert!(host.send(ClientEvent::StartGame).await.unwrap().is_none());
+    let seeker = wait_for(&mut guest_events, |event| match event {
+        Event::Server(ServerEvent::GameStarted { seeker }) => Some(seeker),
+        _ => None,
+    }).await;
+    assert!(seeker == host.id() || seeker == guest.id());
+
+    host.chat("hello", None, ChatChannel::All).await.unwrap();
+    let message = wait_for(&mut guest_events, |event| match event {
+        Event::Server(ServerEvent::Chat { sender, message, .. }) if sender == host.id() => Some(message),
+        _ => None,
+    }).await;
+    assert_eq!(message, "hello");
+}
+
+#[actix_web::test]
+async fn resumes_running_game_after_connection_loss() {
+    let addr = start_server("reconnect").await;
+    let proxy = Proxy::start(addr).await;
+
+    let (host, mut host_events) = connect(config(proxy.addr, "host")).await.unwrap();
+    let (guest, mut guest_events) = connect(config(addr, "guest")).await.unwrap();
+
+    let game = host.create_game(0.0, 0.0, 10, TagMode::Instant, false, None).await.unwrap();
+    guest.join_game(game).await.unwrap();
+    host.start_game().await.unwrap();
+
+    let old_id = host.id();
+    proxy.cut();
+
+    wait_for(&mut host_events, |event| matches!(event, Event::Disconnected).then_some(())).await;
+    let (new_id, resumed) = wait_for(&mut host_events, |event| match event {
+        Event::Reconnected { id, resumed } => Some((id, resumed)),
+        _ => None,
+    }).await;
+
+    assert!(resumed);
+    assert_eq!(new_id, old_id);
+    assert_eq!(host.id(), old_id);
+
+    // the host never left the game, so there is nothing to rejoin
+    host.chat("back", None, ChatChannel::All).await.unwrap();
+    let message = wait_for(&mut guest_events, |event| match event {
+        Event::Server(ServerEvent::PlayerLeft { .. }) => panic!("the host was dropped from the game"),
+        Event::Server(ServerEvent::Chat { sender, message, .. }) if sender == old_id => Some(message),
+        _ => None,
+    }).await;
+    assert_eq!(message, "back");
+
+    guest.chat("welcome back", None, ChatChannel::All).await.unwrap();
+    let message = wait_for(&mut host_events, |event| match event {
+        Event::Server(ServerEvent::Chat { sender, message, .. }) if sender == guest.id() => Some(message),
+        _ => None,
+    }).await;
+    assert_eq!(message, "welcome back");
+}
@@ -0,0 +1,101 @@
+use std::time::Instant;
+use geo::{GeodesicBearing, GeodesicDestination, Point};
+use rand::Rng;
+
+use crate::event::Difficulty;
+
+const WAYPOINTS: usize = 6;
+
+pub(crate) enum Goal {
+    Roam,
+    Chase(Point),
+    Flee(Point),
+}
+
+impl Difficulty {
+    // metres per second, from a stroll to a jog
+    fn speed(self, seeking: bool) -> f64 {
+        match (self, seeking) {
+            (Difficulty::Easy, false) => 1.0,
+            (Difficulty::Easy, true) => 1.2,
+            (Difficulty::Medium, false) => 1.4,
+            (Difficulty::Medium, true) => 2.0,
+            (Difficulty::Hard, false) => 1.8,
+            (Difficulty::Hard, true) => 3.0,
+        }
+    }
+
+    // generated paths stay within this many metres of the game's centre,
+    // where hiders score the most
+    fn roam_radius(self) -> f64 {
+        match self {
+            Difficulty::Easy => 150.0,
+            Difficulty::Medium => 90.0,
+            Difficulty::Hard => 40.0,
+        }
+    }
+
+    // hiders run once the seeker is this close
+    pub(crate) fn flee_distance(self) -> Option<f64> {
+        match self {
+            Difficulty::Easy => None,
+            Difficulty::Medium => Some(30.0),
+            Difficulty::Hard => Some(60.0),
+        }
+    }
+}
+
+// A simulated player walking a loop of waypoints, or towards and away from
+// other players once the game has started.
+pub(crate) struct Bot {
+    pub difficulty: Difficulty,
+    path: Vec<Point>,
+    waypoint: usize,
+    pos: Point,
+    stepped: Instant,
+}
+
+impl Bot {
+    pub fn new(difficulty: Difficulty, center: Point, path: Vec<Point>, rng: &mut impl Rng, now: Instant) -> Self {
+        let path = match path.is_empty() {
+            true => (0..WAYPOINTS)
+                .map(|_| center.geodesic_destination(rng.gen_range(0.0..360.0), rng.gen_range(0.0..difficulty.roam_radius())))
+                .collect(),
+            false => path,
+        };
+
+        Self { difficulty, pos: path[0], waypoint: 1 % path.len(), path, stepped: now }
+    }
+
+    pub fn pos(&self) -> Point {
+        self.pos
+    }
+
+    pub fn step(&mut self, now: Instant, goal: Goal, seeking: bool) -> Point {
+        let distance = self.difficulty.speed(seeking) * now.duration_since(self.stepped).as_secs_f64();
+        self.stepped = now;
+
+        let (target, roaming) = match goal {
+            Goal::Chase(pos) => (pos, false),
+            Goal::Flee(from) => match from.geodesic_bearing_distance(self.pos) {
+                (bearing, apart) if apart > 0.0 => (self.pos.geodesic_destination(bearing, distance), false),
+                _ => (self.path[self.waypoint], true),
+            },
+            Goal::Roam => (self.path[self.waypoint], true),
+        };
+
+        let (bearing, remaining) = self.pos.geodesic_bearing_distance(target);
+
+        if remaining <= distance {
+            self.pos = target;
+
+            if roaming {
+                self.waypoint = (self.waypoint + 1) % self.path.len();
+            }
+        } else {
+            self.pos = self.pos.geodesic_destination(bearing, distance);
+        }
+
+        self.pos
+    }
+}
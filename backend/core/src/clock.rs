@@ -0,0 +1,35 @@
+use std::{sync::{Arc, Mutex}, time::{Duration, Instant}};
+
+// Games read the time through a clock so tests can control it. A manual
+// clock only moves when advanced, and games using one don't schedule their
+// own ticks: whoever advances the clock sends `Tick`s instead.
+#[derive(Clone, Default)]
+pub enum Clock {
+    #[default]
+    System,
+    Manual(Arc<Mutex<Instant>>),
+}
+
+impl Clock {
+    pub fn manual() -> Self {
+        Clock::Manual(Arc::new(Mutex::new(Instant::now())))
+    }
+
+    pub fn now(&self) -> Instant {
+        match self {
+            Clock::System => Instant::now(),
+            Clock::Manual(now) => *now.lock().unwrap(),
+        }
+    }
+
+    pub fn advance(&self, by: Duration) {
+        match self {
+            Clock::System => panic!("the system clock can't be advanced"),
+            Clock::Manual(now) => *now.lock().unwrap() += by,
+        }
+    }
+
+    pub fn is_manual(&self) -> bool {
+        matches!(self, Clock::Manual(_))
+    }
+}
@@ -0,0 +1,100 @@
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+
+// Codes are part of the protocol: never rename or reuse them, only add new ones.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    InvalidMessage,
+    NotConnected,
+    AlreadyConnected,
+    ServerUnavailable,
+    ServerTimeout,
+    HeartbeatTimeout,
+    SlowClient,
+
+    PlayerNotFound,
+    NotInGame,
+    AlreadyInGame,
+    GameNotFound,
+    GameNotStarted,
+    GameAlreadyStarted,
+    GameAlreadyEnded,
+    NotHost,
+    NotEnoughPlayers,
+    TooManyBots,
+    BotsUnavailable,
+    InvalidPlayArea,
+    PlayAreaRequired,
+
+    MessageEmpty,
+    MessageTooLong,
+    RateLimited,
+    Muted,
+    ImageNotFound,
+    ChannelUnavailable,
+    ChannelForbidden,
+
+    NotSeeker,
+    InvalidTarget,
+    WrongTagMode,
+    TagAlreadyPending,
+    NoPendingTag,
+    NotReferee,
+    PhotoRequired,
+    InvalidNonce,
+    NonceExpired,
+    NonceUsed,
+}
+
+impl ErrorCode {
+    pub fn message(self) -> &'static str {
+        match self {
+            ErrorCode::InvalidMessage => "Invalid message",
+            ErrorCode::NotConnected => "Not connected",
+            ErrorCode::AlreadyConnected => "Already connected",
+            ErrorCode::ServerUnavailable => "Server closed",
+            ErrorCode::ServerTimeout => "Server timed out",
+            ErrorCode::HeartbeatTimeout => "Heartbeat failed",
+            ErrorCode::SlowClient => "Connection too slow",
+
+            ErrorCode::PlayerNotFound => "Player not found",
+            ErrorCode::NotInGame => "Not in a game",
+            ErrorCode::AlreadyInGame => "Already in a game",
+            ErrorCode::GameNotFound => "Game does not exist",
+            ErrorCode::GameNotStarted => "Game has not started",
+            ErrorCode::GameAlreadyStarted => "Game already started",
+            ErrorCode::GameAlreadyEnded => "Game already ended",
+            ErrorCode::NotHost => "Only the host can do that",
+            ErrorCode::NotEnoughPlayers => "Not enough players to start the game",
+            ErrorCode::TooManyBots => "This game already has the maximum number of bots",
+            ErrorCode::BotsUnavailable => "Bots can't take photos, so they can't play in photo mode",
+            ErrorCode::InvalidPlayArea => "The play area must reach between 25 and 5000 metres from the center",
+            ErrorCode::PlayAreaRequired => "Power-ups need a play area to spawn in",
+
+            ErrorCode::MessageEmpty => "Message is empty",
+            ErrorCode::MessageTooLong => "Message too long",
+            ErrorCode::RateLimited => "You are sending messages too fast",
+            ErrorCode::Muted => "You are muted",
+            ErrorCode::ImageNotFound => "Image not found",
+            ErrorCode::ChannelUnavailable => "Channel is only available during a game",
+            ErrorCode::ChannelForbidden => "You can't post in this channel",
+
+            ErrorCode::NotSeeker => "Only the seeker can tag",
+            ErrorCode::InvalidTarget => "Invalid target player",
+            ErrorCode::WrongTagMode => "This tag method is not used in this game",
+            ErrorCode::TagAlreadyPending => "A tag is already pending",
+            ErrorCode::NoPendingTag => "No tag to resolve",
+            ErrorCode::NotReferee => "Only the tagged player or the host can resolve a tag",
+            ErrorCode::PhotoRequired => "A photo is required to tag",
+            ErrorCode::InvalidNonce => "Invalid code",
+            ErrorCode::NonceExpired => "Code expired",
+            ErrorCode::NonceUsed => "Code already used",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub enum ErrorDetails {
+    RetryAfter { seconds: f64 },
+    MaxLength { max: usize },
+}
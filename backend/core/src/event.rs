@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+
+use crate::error::{ErrorCode, ErrorDetails};
+use crate::protocol::{Encoding, LEGACY_PROTOCOL_VERSION};
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TagMode {
+    #[default]
+    Instant,
+    Photo,
+    Handshake,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChatChannel {
+    #[default]
+    All,
+    Team,
+    Hiders,
+    Seeker,
+    Direct(i64),
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Medium,
+    Hard,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerUp {
+    ScoreBoost,
+    Invisibility,
+    Radar,
+    Shield,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct ChatEntry {
+    pub sender: i64,
+    pub message: String,
+    pub image: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub enum ClientEvent {
+    Connect {
+        name: String,
+        #[serde(default = "legacy_version")] version: u32,
+        #[serde(default)] encoding: Encoding,
+        #[serde(default)] score_deltas: bool,
+        // the token of an earlier session, to pick up where it left off
+        #[serde(default)] resume: Option<String>,
+    },
+    Chat { message: String, #[serde(default)] image: Option<String>, #[serde(default)] channel: ChatChannel },
+
+    JoinGame { game: u16 },
+    LeaveGame,
+    CreateGame { x: f64, y: f64, minutes: u64, #[serde(default)] tag_mode: TagMode, #[serde(default)] power_ups: bool, #[serde(default)] radius: Option<f64> },
+    StartGame,
+    MutePlayer { player: i64 },
+    UnmutePlayer { player: i64 },
+    AddBot { #[serde(default)] difficulty: Difficulty, #[serde(default)] path: Vec<(f64, f64)> },
+    RemoveBot { player: i64 },
+
+    UpdatePosition { x: f64, y: f64 },
+    TagPlayer { player: i64, #[serde(default)] image: Option<String> },
+    TagWithProof { nonce: String },
+    ConfirmTag,
+    DisputeTag,
+    ResyncScores,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub enum ServerEvent {
+    // the token authorizes uploads over HTTP and must not be shared
+    Connected { id: i64, version: u32, min_version: u32, max_version: u32, token: String },
+    IncompatibleProtocol { min_version: u32, max_version: u32 },
+
+    Chat { sender: i64, message: String, image: Option<String>, channel: ChatChannel },
+    Error { code: ErrorCode, message: String, details: Option<ErrorDetails>, request_id: Option<u32> },
+    Ack { request_id: u32 },
+
+    JoinedGame { id: u16, x: f64, y: f64, players: Vec<(i64, String)>, host: i64, tag_mode: TagMode, chat: Vec<ChatEntry> },
+    PlayerJoined { id: i64, name: String },
+    PlayerLeft { id: i64, new_host: i64 },
+    LeftGame,
+    PlayerMuted { id: i64, muted: bool },
+
+    GameStarted { seeker: i64 },
+    PlayerTagged { tagger: i64, tagged: i64 },
+    TagPending { tagger: i64, tagged: i64, image: String },
+    TagDisputed { tagger: i64, tagged: i64, by: i64 },
+    TagNonce { nonce: String, expires_in: u64 },
+    ScoreUpdate { scores: HashMap<i64, f32>, seconds_left: u64, },
+    ScoreSnapshot { seq: u64, scores: HashMap<i64, f32>, seconds_left: u64 },
+    ScoreDelta { seq: u64, changed: HashMap<i64, f32>, seconds_left: u64 },
+    PowerUpSpawned { id: u32, kind: PowerUp, x: f64, y: f64, expires_in: u64 },
+    PowerUpCollected { id: u32, kind: PowerUp, player: i64, expires_in: Option<u64> },
+    PowerUpExpired { id: u32 },
+    // only sent to the seeker who picked up the radar
+    RadarPing { positions: Vec<(i64, f64, f64)> },
+    ShieldBroken { player: i64, tagger: i64 },
+    // the token downloads the game's replay and tracks, if the server keeps them
+    GameEnded { winner: i64, #[serde(default)] download_token: Option<String> }
+}
+
+fn legacy_version() -> u32 {
+    LEGACY_PROTOCOL_VERSION
+}
+
+impl ClientEvent {
+    // MessagePack can carry NaN and infinities, which JSON never could
+    pub fn is_finite(&self) -> bool {
+        let finite = |&(x, y): &(f64, f64)| x.is_finite() && y.is_finite();
+
+        match self {
+            ClientEvent::CreateGame { x, y, radius, .. } => finite(&(*x, *y)) && radius.is_none_or(f64::is_finite),
+            ClientEvent::UpdatePosition { x, y } => finite(&(*x, *y)),
+            ClientEvent::AddBot { path, .. } => path.iter().all(finite),
+            _ => true,
+        }
+    }
+}
+
+impl ServerEvent {
+    pub fn error(code: ErrorCode) -> Self {
+        Self::error_with(code, None)
+    }
+
+    pub fn error_with(code: ErrorCode, details: Option<ErrorDetails>) -> Self {
+        Self::Error { code, message: code.message().to_string(), details, request_id: None }
+    }
+
+    // whether a queued `other` is worthless once this event is sent. Dropping a
+    // delta leaves a gap in seq, which makes the client ask for a snapshot.
+    pub fn supersedes(&self, other: &ServerEvent) -> bool {
+        matches!(
+            (self, other),
+            (Self::ScoreUpdate { .. }, Self::ScoreUpdate { .. })
+                | (Self::ScoreDelta { .. }, Self::ScoreDelta { .. })
+                | (Self::ScoreSnapshot { .. }, Self::ScoreDelta { .. } | Self::ScoreSnapshot { .. })
+        )
+    }
+}
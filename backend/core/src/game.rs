@@ -0,0 +1,1116 @@
+use std::{cmp::Ordering, collections::{BTreeMap, HashMap, HashSet, VecDeque}, fmt, mem, sync::Arc, time::{Duration, Instant}};
+use geo::{GeodesicDestination, GeodesicDistance, Point};
+use rand::{seq::{IteratorRandom, SliceRandom}, rngs::StdRng, Rng};
+use serde::{Deserialize, Serialize};
+
+use crate::bot::{Bot, Goal};
+use crate::error::{ErrorCode, ErrorDetails};
+use crate::event::*;
+use crate::moderation::{RateLimiter, WordFilter, MAX_MESSAGE_LENGTH};
+use crate::power_up::{Buffs, Item, KINDS};
+
+pub const UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+const TAG_CONFIRM_TIMEOUT: Duration = Duration::from_secs(60);
+const NONCE_INTERVAL: Duration = Duration::from_secs(30);
+const NONCE_LIFETIME: Duration = Duration::from_secs(45);
+const CHAT_HISTORY_LENGTH: usize = 50;
+// bots tag whoever they get this close to
+const BOT_TAG_RANGE: f64 = 30.0;
+const POSITION_MAX_AGE: Duration = Duration::from_secs(20);
+const MAX_BOTS: usize = 8;
+const MAX_TRACK_POINTS: usize = 20_000;
+pub const MIN_PLAY_AREA_RADIUS: f64 = 25.0;
+pub const MAX_PLAY_AREA_RADIUS: f64 = 5_000.0;
+const TAG_BACK_DELAY: Duration = Duration::from_secs(10);
+const POWER_UP_INTERVAL: Duration = Duration::from_secs(30);
+const POWER_UP_LIFETIME: Duration = Duration::from_secs(120);
+const MAX_POWER_UPS: usize = 3;
+const PICKUP_RANGE: f64 = 10.0;
+const SCORE_BOOST: f32 = 2.0;
+// deltas leave out scores that moved less than this since they were last sent
+const SCORE_DELTA_STEP: f32 = 1.0;
+
+// Where uploaded photos live is up to the embedder, the rules only need to
+// know whether one exists.
+pub trait Images: Send + Sync {
+    fn exists(&self, game: u16, name: &str) -> bool;
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Settings {
+    pub host: i64,
+    pub x: f64,
+    pub y: f64,
+    pub minutes: u64,
+    pub tag_mode: TagMode,
+    #[serde(default)] pub power_ups: bool,
+    // metres around the center, if the host marked out a play area
+    #[serde(default)] pub radius: Option<f64>,
+}
+
+#[derive(Clone)]
+pub struct Player {
+    pub name: String,
+    pub pos: Option<Point<f64>>,
+    pub moved: Instant,
+    pub chat_limiter: RateLimiter,
+    pub score_deltas: bool,
+}
+
+impl Player {
+    pub fn new(name: String, score_deltas: bool, now: Instant) -> Self {
+        Self { name, pos: None, moved: now, chat_limiter: RateLimiter::default(), score_deltas }
+    }
+}
+
+pub enum Command {
+    Join { id: i64, player: Player },
+    Client { sender: i64, event: ClientEvent },
+    // removes a player without a reply, e.g. when their connection is lost
+    Leave { id: i64 },
+    // a player is back on a new connection and has to catch up
+    Resume { id: i64 },
+    Tick,
+}
+
+// Everything the embedder has to carry out, in order. Events are only ever
+// addressed to players that are in the game.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum Effect {
+    Send { to: Vec<i64>, event: ServerEvent },
+    Joined(i64),
+    Left(i64),
+    // the game has a winner, its tracks are complete
+    Ended,
+    // the game is over and empty, nothing else will happen in it
+    Closed { players: Vec<i64> },
+}
+
+// Where a player went while the game was running, kept after they leave
+pub struct Track {
+    pub name: String,
+    pub points: Vec<(Instant, Point<f64>)>,
+}
+
+pub struct TagLocation {
+    pub at: Instant,
+    pub tagger: i64,
+    pub tagged: i64,
+    pub pos: Option<Point<f64>>,
+}
+
+pub struct Outcome {
+    pub reply: Option<ServerEvent>,
+    pub effects: Vec<Effect>,
+}
+
+enum GameState {
+    Waiting,
+    Playing {
+        seeker: i64,
+        start: Instant,
+        scores: HashMap<i64, f32>,
+        score_seq: u64,
+        pending_tag: Option<PendingTag>,
+        nonces: HashMap<String, TagNonce>,
+    },
+    Ended
+}
+
+struct TagNonce {
+    player: i64,
+    issued: Instant,
+    used: bool,
+}
+
+impl TagNonce {
+    fn expired(&self, now: Instant) -> bool {
+        now.duration_since(self.issued) >= NONCE_LIFETIME
+    }
+}
+
+struct PendingTag {
+    tagger: i64,
+    tagged: i64,
+    deadline: Instant,
+}
+
+enum HistoryEvent {
+    Tagged { tagger: i64, tagged: i64 },
+    TagDisputed { tagger: i64, tagged: i64, by: i64 },
+    Shielded { tagger: i64, tagged: i64 },
+    PowerUp { player: i64, kind: PowerUp },
+}
+
+impl fmt::Display for HistoryEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HistoryEvent::Tagged { tagger, tagged } => write!(f, "{} tagged {}", tagger, tagged),
+            HistoryEvent::TagDisputed { tagger, tagged, by } => write!(f, "tag of {} by {} disputed by {}", tagged, tagger, by),
+            HistoryEvent::Shielded { tagger, tagged } => write!(f, "{} was shielded from {}", tagged, tagger),
+            HistoryEvent::PowerUp { player, kind } => write!(f, "{} picked up {:?}", player, kind),
+        }
+    }
+}
+
+pub struct Game {
+    id: u16,
+    images: Arc<dyn Images>,
+    word_filter: Arc<WordFilter>,
+    rng: StdRng,
+    now: Instant,
+    effects: Vec<Effect>,
+
+    host: i64,
+    players: BTreeMap<i64, Player>,
+    pos: Point<f64>,
+    state: GameState,
+    // scores as of the last delta, small changes since then aren't sent yet
+    reported_scores: HashMap<i64, f32>,
+    length: Duration,
+    tag_mode: TagMode,
+    history: Vec<(Instant, HistoryEvent)>,
+    muted: HashSet<i64>,
+    chat: VecDeque<ChatEntry>,
+    bots: BTreeMap<i64, Bot>,
+    // bots are numbered by how many were ever added, so names never repeat
+    bots_added: u32,
+    tracks: BTreeMap<i64, Track>,
+    tags: Vec<TagLocation>,
+    power_ups: bool,
+    radius: Option<f64>,
+    items: BTreeMap<u32, Item>,
+    next_item: u32,
+    next_spawn: Instant,
+    buffs: HashMap<i64, Buffs>,
+}
+
+impl Game {
+    pub fn new(
+        id: u16,
+        settings: Settings,
+        host: Player,
+        images: Arc<dyn Images>,
+        word_filter: Arc<WordFilter>,
+        rng: StdRng,
+    ) -> Self {
+        Self {
+            id, images, word_filter, rng,
+            now: host.moved,
+            next_spawn: host.moved,
+            effects: Vec::new(),
+            host: settings.host,
+            players: BTreeMap::from([(settings.host, host)]),
+            pos: Point::new(settings.x, settings.y),
+            length: Duration::from_secs(settings.minutes * 60),
+            tag_mode: settings.tag_mode,
+            state: GameState::Waiting,
+            reported_scores: HashMap::new(),
+            history: Vec::new(),
+            muted: HashSet::new(),
+            chat: VecDeque::new(),
+            bots: BTreeMap::new(),
+            bots_added: 0,
+            tracks: BTreeMap::new(),
+            tags: Vec::new(),
+            power_ups: settings.power_ups,
+            radius: settings.radius,
+            items: BTreeMap::new(),
+            next_item: 0,
+            buffs: HashMap::new(),
+        }
+    }
+
+    pub fn id(&self) -> u16 {
+        self.id
+    }
+
+    pub fn center(&self) -> Point<f64> {
+        self.pos
+    }
+
+    pub fn radius(&self) -> Option<f64> {
+        self.radius
+    }
+
+    pub fn tracks(&self) -> &BTreeMap<i64, Track> {
+        &self.tracks
+    }
+
+    pub fn tags(&self) -> &[TagLocation] {
+        &self.tags
+    }
+
+    pub fn handle(&mut self, now: Instant, command: Command) -> Outcome {
+        self.now = now;
+
+        let reply = match command {
+            Command::Join { id, player } => self.join(id, player),
+            Command::Client { sender, event } => self.client_event(sender, event),
+            Command::Leave { id } => {
+                if self.players.contains_key(&id) {
+                    self.leave(id);
+                }
+                None
+            },
+            Command::Resume { id } => self.resume(id),
+            Command::Tick => {
+                self.expire_pending_tag();
+                self.move_bots();
+                self.update_power_ups();
+                self.update_game();
+                None
+            },
+        };
+
+        Outcome { reply, effects: mem::take(&mut self.effects) }
+    }
+
+    fn error(code: ErrorCode) -> Option<ServerEvent> {
+        Some(ServerEvent::error(code))
+    }
+
+    fn error_with(code: ErrorCode, details: ErrorDetails) -> Option<ServerEvent> {
+        Some(ServerEvent::error_with(code, Some(details)))
+    }
+
+    fn record(&mut self, event: HistoryEvent) {
+        self.history.push((self.now, event));
+    }
+
+    fn seconds_left(&self, start: Instant) -> u64 {
+        self.length.as_secs().saturating_sub(self.now.duration_since(start).as_secs())
+    }
+
+    fn score_snapshot(&self) -> Option<ServerEvent> {
+        match &self.state {
+            GameState::Playing { start, scores, score_seq, .. } => Some(ServerEvent::ScoreSnapshot {
+                seq: *score_seq,
+                scores: scores.clone(),
+                seconds_left: self.seconds_left(*start),
+            }),
+            _ => None,
+        }
+    }
+
+    fn fresh_pos(&self, id: i64) -> Option<Point> {
+        self.players.get(&id)
+            .filter(|player| self.now.duration_since(player.moved) < POSITION_MAX_AGE)
+            .and_then(|player| player.pos)
+    }
+
+    // only known if both players have reported their position recently
+    fn distance_between(&self, a: i64, b: i64) -> Option<f64> {
+        Some(self.fresh_pos(a)?.geodesic_distance(&self.fresh_pos(b)?))
+    }
+
+    fn humans(&self) -> usize {
+        self.players.len() - self.bots.len()
+    }
+
+    fn send_to(&mut self, to: Vec<i64>, event: ServerEvent) {
+        if !to.is_empty() {
+            self.effects.push(Effect::Send { to, event });
+        }
+    }
+
+    fn send(&mut self, player_id: i64, event: ServerEvent) {
+        if self.players.contains_key(&player_id) {
+            self.send_to(vec![player_id], event);
+        }
+    }
+
+    fn broadcast(&mut self, event: ServerEvent, exclude: Option<i64>) {
+        let to = self.players.keys().copied().filter(|&id| Some(id) != exclude).collect();
+        self.send_to(to, event);
+    }
+
+    fn cancel_game(&mut self) {
+        log::info!("Game {} canceled", self.id);
+
+        self.broadcast(ServerEvent::LeftGame, None);
+        self.effects.push(Effect::Closed { players: self.players.keys().copied().collect() });
+        self.players.clear();
+        self.bots.clear();
+    }
+
+    fn end_game(&mut self) {
+        if let GameState::Playing { scores, start, .. } = &self.state {
+            log::info!("Game {} ended", self.id);
+
+            for (time, event) in &self.history {
+                log::info!("  [{:>4}s] {}", time.duration_since(*start).as_secs(), event);
+            }
+
+            // ties go to the lowest id so the outcome doesn't depend on map order
+            let winner = match scores.iter().max_by(|a, b| {
+                a.1.partial_cmp(b.1).unwrap_or(Ordering::Equal).then(b.0.cmp(a.0))
+            }) {
+                Some(w) => *w.0,
+                None => {
+                    self.cancel_game();
+                    return;
+                },
+            };
+
+            self.state = GameState::Ended;
+            // only the server knows where downloads are kept, it fills in the token
+            self.broadcast(ServerEvent::GameEnded { winner, download_token: None }, None);
+            self.effects.push(Effect::Ended);
+        }
+    }
+
+    fn update_game(&mut self) {
+        let (seeker, start, scores, score_seq) = match &mut self.state {
+            GameState::Playing { seeker, start, scores, score_seq, .. } => (*seeker, *start, scores, score_seq),
+            _ => return,
+        };
+
+        for (id, score) in &mut *scores {
+            if *id == seeker {
+                continue;
+            }
+
+            if let Some(player) = self.players.get(id) {
+                if let Some(pos) = player.pos {
+                    let distance = pos.geodesic_distance(&self.pos);
+                    let mut gain = (1.0 / (distance + 2.0) * 20.0) as f32;
+
+                    if self.buffs.get(id).is_some_and(|buffs| buffs.boosted(self.now)) {
+                        gain *= SCORE_BOOST;
+                    }
+
+                    *score += gain * UPDATE_INTERVAL.as_secs_f32();
+                }
+            }
+        }
+
+        *score_seq += 1;
+
+        let ended = self.now.duration_since(start) >= self.length;
+        let seconds_left = self.length.as_secs().saturating_sub(self.now.duration_since(start).as_secs());
+
+        // small gains wait until they add up, the last delta has every final score
+        let changed: HashMap<i64, f32> = scores.iter()
+            .filter(|(id, &score)| {
+                let last = self.reported_scores.get(id).copied().unwrap_or(0.0);
+                (score - last).abs() >= SCORE_DELTA_STEP || (ended && score != last)
+            })
+            .map(|(&id, &score)| (id, score))
+            .collect();
+
+        self.reported_scores.extend(&changed);
+
+        let update = ServerEvent::ScoreUpdate { scores: scores.clone(), seconds_left };
+        let delta = ServerEvent::ScoreDelta { seq: *score_seq, changed, seconds_left };
+
+        // clients that opted into deltas resync themselves when they see a gap in seq
+        let (deltas, updates) = self.players.keys().partition(|id| self.players[id].score_deltas);
+
+        self.send_to(updates, update);
+        self.send_to(deltas, delta);
+
+        if ended {
+            self.end_game();
+        } else {
+            self.rotate_nonces();
+        }
+    }
+
+    fn rotate_nonces(&mut self) {
+        if self.tag_mode != TagMode::Handshake {
+            return;
+        }
+
+        let (seeker, nonces) = match &mut self.state {
+            GameState::Playing { seeker, nonces, .. } => (*seeker, nonces),
+            _ => return,
+        };
+
+        let now = self.now;
+        let players = &self.players;
+
+        // used nonces are kept until they expire so replays can be told apart
+        nonces.retain(|_, nonce| !nonce.expired(now) && (nonce.used || (nonce.player != seeker && players.contains_key(&nonce.player))));
+
+        let mut issued = Vec::new();
+
+        for &id in players.keys() {
+            let fresh = nonces.values().any(|nonce| {
+                nonce.player == id && !nonce.used && now.duration_since(nonce.issued) < NONCE_INTERVAL
+            });
+
+            if id == seeker || fresh {
+                continue;
+            }
+
+            let nonce = format!("{:016x}", self.rng.gen::<u64>());
+            nonces.insert(nonce.clone(), TagNonce { player: id, issued: now, used: false });
+            issued.push((id, nonce));
+        }
+
+        for (id, nonce) in issued {
+            self.send(id, ServerEvent::TagNonce { nonce, expires_in: NONCE_LIFETIME.as_secs() });
+        }
+    }
+
+    fn client_event(&mut self, sender: i64, event: ClientEvent) -> Option<ServerEvent> {
+        if !self.players.contains_key(&sender) {
+            return Self::error(ErrorCode::NotInGame);
+        }
+
+        if !event.is_finite() {
+            return Self::error(ErrorCode::InvalidMessage);
+        }
+
+        match event {
+            ClientEvent::Connect { .. } => Self::error(ErrorCode::AlreadyConnected),
+            ClientEvent::JoinGame { .. } | ClientEvent::CreateGame { .. } => Self::error(ErrorCode::AlreadyInGame),
+            ClientEvent::Chat { message, image, channel } => self.chat(sender, message, image, channel),
+            ClientEvent::LeaveGame => self.leave(sender),
+            ClientEvent::StartGame => self.start(sender),
+            ClientEvent::MutePlayer { player } => self.mute(sender, player, true),
+            ClientEvent::UnmutePlayer { player } => self.mute(sender, player, false),
+            ClientEvent::AddBot { difficulty, path } => self.add_bot(sender, difficulty, path),
+            ClientEvent::RemoveBot { player } => self.remove_bot(sender, player),
+            ClientEvent::UpdatePosition { x, y } => self.set_pos(sender, Point::new(x, y)),
+            ClientEvent::TagPlayer { player, image } => self.tag(sender, player, image),
+            ClientEvent::TagWithProof { nonce } => self.tag_with_proof(sender, nonce),
+            ClientEvent::ConfirmTag => self.resolve_tag(sender, true),
+            ClientEvent::DisputeTag => self.resolve_tag(sender, false),
+            ClientEvent::ResyncScores => self.resync_scores(),
+        }
+    }
+}
+
+// Commands
+
+impl Game {
+    fn joined_game(&self) -> ServerEvent {
+        ServerEvent::JoinedGame {
+            players: self.players.iter().map(|(id, player)| (*id, player.name.clone())).collect(),
+            id: self.id,
+            x: self.pos.x(),
+            y: self.pos.y(),
+            host: self.host,
+            tag_mode: self.tag_mode,
+            chat: self.chat.iter().cloned().collect(),
+        }
+    }
+
+    fn join(&mut self, player_id: i64, player: Player) -> Option<ServerEvent> {
+        match self.state {
+            GameState::Waiting => {
+                let event = self.joined_game();
+
+                self.broadcast(
+                    ServerEvent::PlayerJoined {
+                        name: player.name.clone(),
+                        id: player_id,
+                    },
+                    None
+                );
+
+                self.players.insert(player_id, player);
+                self.effects.push(Effect::Joined(player_id));
+
+                Some(event)
+            },
+            GameState::Playing { .. } => Self::error(ErrorCode::GameAlreadyStarted),
+            GameState::Ended => Self::error(ErrorCode::GameAlreadyEnded),
+        }
+    }
+
+    // whatever the old connection still had queued went out first, this is
+    // where the game stands now
+    fn resume(&mut self, player_id: i64) -> Option<ServerEvent> {
+        if !self.players.contains_key(&player_id) {
+            return None;
+        }
+
+        self.send(player_id, self.joined_game());
+
+        if let GameState::Playing { seeker, .. } = self.state {
+            self.send(player_id, ServerEvent::GameStarted { seeker });
+        }
+
+        if let Some(snapshot) = self.score_snapshot() {
+            self.send(player_id, snapshot);
+        }
+
+        None
+    }
+
+    fn chat(&mut self, player_id: i64, message: String, image: Option<String>, channel: ChatChannel) -> Option<ServerEvent> {
+        if message.chars().count() > MAX_MESSAGE_LENGTH {
+            return Self::error_with(ErrorCode::MessageTooLong, ErrorDetails::MaxLength { max: MAX_MESSAGE_LENGTH });
+        }
+
+        if message.trim().is_empty() && image.is_none() {
+            return Self::error(ErrorCode::MessageEmpty);
+        }
+
+        if let Some(image) = &image {
+            if !self.images.exists(self.id, image) {
+                return Self::error(ErrorCode::ImageNotFound);
+            }
+        }
+
+        if self.muted.contains(&player_id) {
+            return Self::error(ErrorCode::Muted);
+        }
+
+        let recipients = match self.chat_recipients(player_id, channel) {
+            Ok(value) => value,
+            Err(value) => return value,
+        };
+
+        // only messages that would go out count against the limit
+        if let Some(player) = self.players.get_mut(&player_id) {
+            if let Err(wait) = player.chat_limiter.check(self.now) {
+                return Self::error_with(ErrorCode::RateLimited, ErrorDetails::RetryAfter { seconds: wait.as_secs_f64() });
+            }
+        }
+
+        let message = self.word_filter.apply(&message);
+
+        // only public messages are kept, so history never leaks private channels
+        if channel == ChatChannel::All {
+            if self.chat.len() >= CHAT_HISTORY_LENGTH {
+                self.chat.pop_front();
+            }
+
+            self.chat.push_back(ChatEntry { sender: player_id, message: message.clone(), image: image.clone() });
+        }
+
+        self.send_to(recipients, ServerEvent::Chat { message, image, channel, sender: player_id });
+        None
+    }
+
+    fn mute(&mut self, player_id: i64, other_id: i64, muted: bool) -> Option<ServerEvent> {
+        if self.host != player_id {
+            return Self::error(ErrorCode::NotHost);
+        }
+
+        if other_id == player_id || !self.players.contains_key(&other_id) {
+            return Self::error(ErrorCode::InvalidTarget);
+        }
+
+        let changed = if muted {
+            self.muted.insert(other_id)
+        } else {
+            self.muted.remove(&other_id)
+        };
+
+        if changed {
+            self.broadcast(ServerEvent::PlayerMuted { id: other_id, muted }, None);
+        }
+
+        None
+    }
+
+    fn chat_recipients(&self, sender: i64, channel: ChatChannel) -> Result<Vec<i64>, Option<ServerEvent>> {
+        let seeker = match self.state {
+            GameState::Playing { seeker, .. } => Some(seeker),
+            _ => None,
+        };
+
+        let recipients = match (channel, seeker) {
+            (ChatChannel::All, _) => self.players.keys().copied().collect(),
+            (ChatChannel::Direct(id), _) => {
+                if !self.players.contains_key(&id) {
+                    return Err(Self::error(ErrorCode::InvalidTarget));
+                }
+
+                if id == sender { vec![id] } else { vec![sender, id] }
+            },
+            (ChatChannel::Team, Some(seeker)) => {
+                let is_seeker = sender == seeker;
+                self.players.keys().copied().filter(|&id| (id == seeker) == is_seeker).collect()
+            },
+            // the host may post in either role channel to referee the game
+            (ChatChannel::Hiders, Some(seeker)) => {
+                if sender == seeker && sender != self.host {
+                    return Err(Self::error(ErrorCode::ChannelForbidden));
+                }
+
+                let mut recipients: Vec<i64> = self.players.keys().copied().filter(|&id| id != seeker).collect();
+                if !recipients.contains(&sender) {
+                    recipients.push(sender);
+                }
+                recipients
+            },
+            (ChatChannel::Seeker, Some(seeker)) => {
+                if sender != seeker && sender != self.host {
+                    return Err(Self::error(ErrorCode::ChannelForbidden));
+                }
+
+                if sender == seeker { vec![seeker] } else { vec![sender, seeker] }
+            },
+            (_, None) => return Err(Self::error(ErrorCode::ChannelUnavailable)),
+        };
+
+        Ok(recipients)
+    }
+
+    fn leave(&mut self, player_id: i64) -> Option<ServerEvent> {
+        let mut new_host = self.host;
+        self.players.remove(&player_id);
+        self.buffs.remove(&player_id);
+
+        if self.bots.remove(&player_id).is_none() {
+            self.effects.push(Effect::Left(player_id));
+        }
+
+        if let GameState::Playing { ref mut seeker, ref mut pending_tag, .. } = &mut self.state {
+            if pending_tag.as_ref().is_some_and(|p| p.tagger == player_id || p.tagged == player_id) {
+                *pending_tag = None;
+            }
+
+            if self.players.len() < 2 {
+                self.end_game();
+            } else if *seeker == player_id {
+                *seeker = *self.players.keys().choose(&mut self.rng).unwrap();
+            }
+        } else if self.humans() == 0 {
+            self.cancel_game();
+        } else if self.host == player_id {
+            self.host = *self.players.keys().find(|id| !self.bots.contains_key(id)).unwrap();
+            new_host = self.host;
+        }
+
+        // bots don't keep a game going on their own
+        if self.humans() == 0 && !self.players.is_empty() {
+            self.cancel_game();
+        }
+
+        self.broadcast(ServerEvent::PlayerLeft { id: player_id, new_host }, Some(player_id));
+        Some(ServerEvent::LeftGame)
+    }
+
+    fn start(&mut self, player_id: i64) -> Option<ServerEvent> {
+        if self.host != player_id {
+            return Self::error(ErrorCode::NotHost);
+        }
+
+        if self.players.len() < 2 {
+            return Self::error(ErrorCode::NotEnoughPlayers);
+        }
+
+        if !matches!(self.state, GameState::Waiting) {
+            return Self::error(ErrorCode::GameAlreadyStarted);
+        }
+
+        let scores = self.players.keys().map(|&id| (id, 0.0)).collect();
+        let seeker = *self.players.keys().choose(&mut self.rng).unwrap();
+        self.state = GameState::Playing {
+            seeker,
+            start: self.now,
+            scores,
+            score_seq: 0,
+            pending_tag: None,
+            nonces: HashMap::new(),
+        };
+        self.next_spawn = self.now + POWER_UP_INTERVAL;
+
+        let positions: Vec<_> = self.players.iter().filter_map(|(&id, player)| Some((id, player.pos?))).collect();
+
+        for (id, pos) in positions {
+            self.move_player(id, pos);
+        }
+
+        self.broadcast(ServerEvent::GameStarted { seeker }, None);
+        self.send_snapshots();
+        self.rotate_nonces();
+        None
+    }
+
+    fn resync_scores(&self) -> Option<ServerEvent> {
+        match self.score_snapshot() {
+            Some(snapshot) => Some(snapshot),
+            None => Self::error(ErrorCode::GameNotStarted),
+        }
+    }
+
+    fn send_snapshots(&mut self) {
+        if let Some(snapshot) = self.score_snapshot() {
+            let to = self.players.iter().filter(|(_, player)| player.score_deltas).map(|(id, _)| *id).collect();
+            self.send_to(to, snapshot);
+        }
+    }
+
+    fn set_pos(&mut self, player_id: i64, pos: Point) -> Option<ServerEvent> {
+        if let Some(player) = self.players.get(&player_id) {
+            log::trace!("{} moved to {:?}", player.name, pos);
+            self.move_player(player_id, pos);
+        }
+
+        None
+    }
+
+    fn move_player(&mut self, player_id: i64, pos: Point) {
+        let player = match self.players.get_mut(&player_id) {
+            Some(player) => player,
+            None => return,
+        };
+
+        player.pos = Some(pos);
+        player.moved = self.now;
+
+        if !matches!(self.state, GameState::Playing { .. }) {
+            return;
+        }
+
+        // bots aren't anybody, their paths only add noise to the tracks
+        if !self.bots.contains_key(&player_id) {
+            let track = self.tracks.entry(player_id).or_insert_with(|| Track { name: player.name.clone(), points: Vec::new() });
+
+            if track.points.len() < MAX_TRACK_POINTS {
+                track.points.push((self.now, pos));
+            }
+        }
+
+        self.pick_up(player_id, pos);
+    }
+
+    fn tag(&mut self, player_id: i64, other_id: i64, image: Option<String>) -> Option<ServerEvent> {
+        if let GameState::Playing { seeker, ref mut pending_tag, .. } = self.state {
+            if player_id != seeker {
+                return Self::error(ErrorCode::NotSeeker);
+            }
+
+            if other_id == player_id || !self.players.contains_key(&other_id) {
+                return Self::error(ErrorCode::InvalidTarget);
+            }
+
+            // bots can't show a nonce, so they are tagged like in instant mode
+            let is_bot = self.bots.contains_key(&other_id);
+
+            if self.tag_mode == TagMode::Handshake && !is_bot {
+                return Self::error(ErrorCode::WrongTagMode);
+            }
+
+            if self.tag_mode == TagMode::Instant || self.tag_mode == TagMode::Handshake {
+                self.transfer_seeker(player_id, other_id);
+                return None;
+            }
+
+            if pending_tag.is_some() {
+                return Self::error(ErrorCode::TagAlreadyPending);
+            }
+
+            let image = match image {
+                Some(image) if self.images.exists(self.id, &image) => image,
+                Some(_) => return Self::error(ErrorCode::ImageNotFound),
+                None => return Self::error(ErrorCode::PhotoRequired),
+            };
+
+            let deadline = self.now + TAG_CONFIRM_TIMEOUT;
+            *pending_tag = Some(PendingTag { tagger: player_id, tagged: other_id, deadline });
+            self.broadcast(ServerEvent::TagPending { tagger: player_id, tagged: other_id, image }, None);
+            return None;
+        }
+
+        Self::error(ErrorCode::GameNotStarted)
+    }
+
+    fn tag_with_proof(&mut self, player_id: i64, nonce: String) -> Option<ServerEvent> {
+        if let GameState::Playing { seeker, ref mut nonces, .. } = self.state {
+            if player_id != seeker {
+                return Self::error(ErrorCode::NotSeeker);
+            }
+
+            if self.tag_mode != TagMode::Handshake {
+                return Self::error(ErrorCode::WrongTagMode);
+            }
+
+            let tagged = match nonces.get_mut(&nonce) {
+                Some(nonce) if nonce.used => return Self::error(ErrorCode::NonceUsed),
+                Some(nonce) if nonce.expired(self.now) => return Self::error(ErrorCode::NonceExpired),
+                Some(nonce) if nonce.player != seeker && self.players.contains_key(&nonce.player) => {
+                    nonce.used = true;
+                    nonce.player
+                },
+                _ => return Self::error(ErrorCode::InvalidNonce),
+            };
+
+            self.transfer_seeker(player_id, tagged);
+            self.rotate_nonces();
+            return None;
+        }
+
+        Self::error(ErrorCode::GameNotStarted)
+    }
+
+    fn resolve_tag(&mut self, player_id: i64, confirmed: bool) -> Option<ServerEvent> {
+        if let GameState::Playing { pending_tag: Some(ref pending), .. } = self.state {
+            let is_referee = player_id == self.host && player_id != pending.tagger;
+
+            if player_id != pending.tagged && !is_referee {
+                return Self::error(ErrorCode::NotReferee);
+            }
+
+            self.finish_tag(Some(player_id), confirmed);
+            return None;
+        }
+
+        Self::error(ErrorCode::NoPendingTag)
+    }
+
+    // only confirmed tags count, so one left unanswered is disputed on the
+    // tagged player's behalf
+    fn expire_pending_tag(&mut self) {
+        if let GameState::Playing { pending_tag: Some(ref pending), .. } = self.state {
+            if self.now >= pending.deadline {
+                self.finish_tag(None, false);
+            }
+        }
+    }
+
+    fn finish_tag(&mut self, resolver: Option<i64>, confirmed: bool) {
+        let pending = match &mut self.state {
+            GameState::Playing { pending_tag, .. } => pending_tag.take(),
+            _ => None,
+        };
+
+        if let Some(PendingTag { tagger, tagged, .. }) = pending {
+            if confirmed {
+                self.transfer_seeker(tagger, tagged);
+            } else {
+                let by = resolver.unwrap_or(tagged);
+                log::info!("Tag of {} by {} in game {} disputed by {}", tagged, tagger, self.id, by);
+
+                self.record(HistoryEvent::TagDisputed { tagger, tagged, by });
+                self.broadcast(ServerEvent::TagDisputed { tagger, tagged, by }, None);
+            }
+        }
+    }
+
+    fn transfer_seeker(&mut self, tagger: i64, tagged: i64) {
+        if let Some(buffs) = self.buffs.get_mut(&tagged).filter(|buffs| buffs.shield) {
+            buffs.shield = false;
+            self.record(HistoryEvent::Shielded { tagger, tagged });
+            self.broadcast(ServerEvent::ShieldBroken { player: tagged, tagger }, None);
+            return;
+        }
+
+        if let GameState::Playing { ref mut seeker, .. } = self.state {
+            *seeker = tagged;
+            self.record(HistoryEvent::Tagged { tagger, tagged });
+
+            let pos = self.fresh_pos(tagged).or_else(|| self.fresh_pos(tagger));
+            self.tags.push(TagLocation { at: self.now, tagger, tagged, pos });
+
+            self.broadcast(ServerEvent::PlayerTagged { tagger, tagged }, None);
+        }
+    }
+}
+
+// Bots
+
+impl Game {
+    fn add_bot(&mut self, player_id: i64, difficulty: Difficulty, path: Vec<(f64, f64)>) -> Option<ServerEvent> {
+        if self.host != player_id {
+            return Self::error(ErrorCode::NotHost);
+        }
+
+        match self.state {
+            GameState::Waiting => (),
+            GameState::Playing { .. } => return Self::error(ErrorCode::GameAlreadyStarted),
+            GameState::Ended => return Self::error(ErrorCode::GameAlreadyEnded),
+        }
+
+        // a bot seeker could never tag anyone with a photo
+        if self.tag_mode == TagMode::Photo {
+            return Self::error(ErrorCode::BotsUnavailable);
+        }
+
+        if self.bots.len() >= MAX_BOTS {
+            return Self::error(ErrorCode::TooManyBots);
+        }
+
+        let id = loop {
+            let id = self.rng.gen();
+
+            if !self.players.contains_key(&id) {
+                break id;
+            }
+        };
+
+        let path = path.into_iter().map(|(x, y)| Point::new(x, y)).collect();
+        let bot = Bot::new(difficulty, self.pos, path, &mut self.rng, self.now);
+
+        self.bots_added += 1;
+        let mut player = Player::new(format!("Bot {} ({:?})", self.bots_added, difficulty), false, self.now);
+        player.pos = Some(bot.pos());
+
+        log::info!("{} added to game {}", player.name, self.id);
+
+        self.broadcast(ServerEvent::PlayerJoined { id, name: player.name.clone() }, None);
+        self.players.insert(id, player);
+        self.bots.insert(id, bot);
+        None
+    }
+
+    fn remove_bot(&mut self, player_id: i64, bot_id: i64) -> Option<ServerEvent> {
+        if self.host != player_id {
+            return Self::error(ErrorCode::NotHost);
+        }
+
+        if !self.bots.contains_key(&bot_id) {
+            return Self::error(ErrorCode::InvalidTarget);
+        }
+
+        self.leave(bot_id);
+        None
+    }
+
+    // the closest player with a known position, leaving out whoever just
+    // tagged the bot
+    fn nearest_target(&self, bot_id: i64) -> Option<(i64, Point)> {
+        let from = self.bots[&bot_id].pos();
+
+        let tagged_by = match self.history.last() {
+            Some((time, HistoryEvent::Tagged { tagger, tagged }))
+                if *tagged == bot_id && self.now.duration_since(*time) < TAG_BACK_DELAY => Some(*tagger),
+            _ => None,
+        };
+
+        self.players.keys()
+            .filter(|&&id| id != bot_id && Some(id) != tagged_by && !self.invisible(id))
+            .filter_map(|&id| Some((id, self.fresh_pos(id)?)))
+            .min_by(|a, b| {
+                from.geodesic_distance(&a.1).partial_cmp(&from.geodesic_distance(&b.1)).unwrap_or(Ordering::Equal)
+            })
+    }
+
+    fn move_bots(&mut self) {
+        let ids: Vec<i64> = self.bots.keys().copied().collect();
+
+        for id in ids {
+            let seeker = match self.state {
+                GameState::Playing { seeker, .. } => Some(seeker),
+                _ => None,
+            };
+            let seeking = seeker == Some(id);
+            let target = seeking.then(|| self.nearest_target(id)).flatten();
+
+            let bot = &self.bots[&id];
+            let goal = match (target, seeker.filter(|_| !seeking).and_then(|seeker| self.fresh_pos(seeker))) {
+                (Some((_, pos)), _) => Goal::Chase(pos),
+                (None, Some(pos)) if bot.difficulty.flee_distance().is_some_and(|range| pos.geodesic_distance(&bot.pos()) < range) => {
+                    Goal::Flee(pos)
+                },
+                _ => Goal::Roam,
+            };
+
+            let pos = self.bots.get_mut(&id).unwrap().step(self.now, goal, seeking);
+            self.move_player(id, pos);
+
+            // bots can't take photos or read another player's nonce
+            if let Some((other, _)) = target {
+                let can_tag = self.tag_mode == TagMode::Instant || (self.tag_mode == TagMode::Handshake && self.bots.contains_key(&other));
+
+                if can_tag && self.distance_between(id, other).is_some_and(|meters| meters <= BOT_TAG_RANGE) {
+                    self.tag(id, other, None);
+                }
+            }
+        }
+
+        // bots never dispute being tagged
+        if let GameState::Playing { pending_tag: Some(ref pending), .. } = self.state {
+            if self.bots.contains_key(&pending.tagged) {
+                self.finish_tag(Some(pending.tagged), true);
+            }
+        }
+    }
+}
+
+// Power-ups
+
+impl Game {
+    fn invisible(&self, id: i64) -> bool {
+        self.buffs.get(&id).is_some_and(|buffs| buffs.invisible(self.now))
+    }
+
+    fn update_power_ups(&mut self) {
+        if !self.power_ups || !matches!(self.state, GameState::Playing { .. }) {
+            return;
+        }
+
+        let now = self.now;
+        let expired: Vec<u32> = self.items.iter().filter(|(_, item)| now >= item.expires).map(|(&id, _)| id).collect();
+
+        for id in expired {
+            self.items.remove(&id);
+            self.broadcast(ServerEvent::PowerUpExpired { id }, None);
+        }
+
+        if now < self.next_spawn {
+            return;
+        }
+
+        self.next_spawn = now + POWER_UP_INTERVAL;
+
+        if self.items.len() >= MAX_POWER_UPS {
+            return;
+        }
+
+        // power-ups are spread over the play area, games without one get none
+        let radius = match self.radius {
+            Some(radius) => radius,
+            None => return,
+        };
+
+        let kind = *KINDS.choose(&mut self.rng).unwrap();
+        let bearing = self.rng.gen_range(0.0..360.0);
+        // the square root spreads items evenly over the area instead of
+        // bunching them up in the middle
+        let distance = radius * self.rng.gen::<f64>().sqrt();
+        let pos = self.pos.geodesic_destination(bearing, distance);
+
+        let id = self.next_item;
+        self.next_item += 1;
+        self.items.insert(id, Item { kind, pos, expires: now + POWER_UP_LIFETIME });
+
+        self.broadcast(ServerEvent::PowerUpSpawned { id, kind, x: pos.x(), y: pos.y(), expires_in: POWER_UP_LIFETIME.as_secs() }, None);
+    }
+
+    fn pick_up(&mut self, player_id: i64, pos: Point) {
+        let seeking = match self.state {
+            GameState::Playing { seeker, .. } => seeker == player_id,
+            _ => return,
+        };
+
+        let id = match self.items.iter().find(|(_, item)| {
+            item.kind.for_seeker() == seeking && item.pos.geodesic_distance(&pos) <= PICKUP_RANGE
+        }) {
+            Some((&id, _)) => id,
+            None => return,
+        };
+
+        let kind = self.items.remove(&id).unwrap().kind;
+        self.buffs.entry(player_id).or_default().apply(kind, self.now);
+        self.record(HistoryEvent::PowerUp { player: player_id, kind });
+
+        let expires_in = kind.lasts().map(|lasts| lasts.as_secs());
+        self.broadcast(ServerEvent::PowerUpCollected { id, kind, player: player_id, expires_in }, None);
+
+        if kind == PowerUp::Radar {
+            self.radar_ping(player_id);
+        }
+    }
+
+    // where every hider that can be seen was last reported
+    fn radar_ping(&mut self, player_id: i64) {
+        let positions = self.players.keys()
+            .filter(|&&id| id != player_id && !self.invisible(id))
+            .filter_map(|&id| {
+                let pos = self.fresh_pos(id)?;
+                Some((id, pos.x(), pos.y()))
+            })
+            .collect();
+
+        self.send(player_id, ServerEvent::RadarPing { positions });
+    }
+}
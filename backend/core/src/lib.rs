@@ -0,0 +1,8 @@
+pub mod bot;
+pub mod clock;
+pub mod error;
+pub mod event;
+pub mod game;
+pub mod moderation;
+pub mod power_up;
+pub mod protocol;
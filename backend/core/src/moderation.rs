@@ -0,0 +1,97 @@
+use std::{collections::{HashSet, VecDeque}, fs, io, path::Path, time::{Duration, Instant}};
+
+pub const MAX_MESSAGE_LENGTH: usize = 500;
+const CHAT_RATE_LIMIT: usize = 5;
+const CHAT_RATE_WINDOW: Duration = Duration::from_secs(10);
+
+#[derive(Default)]
+pub struct WordFilter {
+    words: HashSet<String>,
+}
+
+impl WordFilter {
+    // one word per line, lines starting with # are ignored
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let words = fs::read_to_string(path)?
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| line.to_lowercase())
+            .collect();
+
+        Ok(Self { words })
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    pub fn apply(&self, message: &str) -> String {
+        if self.is_empty() {
+            return message.to_string();
+        }
+
+        let mut result = String::with_capacity(message.len());
+        let mut word = String::new();
+
+        for c in message.chars() {
+            if c.is_alphanumeric() {
+                word.push(c);
+            } else {
+                self.push_word(&mut result, &mut word);
+                result.push(c);
+            }
+        }
+
+        self.push_word(&mut result, &mut word);
+        result
+    }
+
+    fn push_word(&self, result: &mut String, word: &mut String) {
+        if self.words.contains(&word.to_lowercase()) {
+            result.extend(word.chars().map(|_| '*'));
+        } else {
+            result.push_str(word);
+        }
+
+        word.clear();
+    }
+}
+
+// At most `limit` in any `window`, chat's limits unless told otherwise
+#[derive(Clone)]
+pub struct RateLimiter {
+    sent: VecDeque<Instant>,
+    limit: usize,
+    window: Duration,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new(CHAT_RATE_LIMIT, CHAT_RATE_WINDOW)
+    }
+}
+
+impl RateLimiter {
+    pub fn new(limit: usize, window: Duration) -> Self {
+        Self { sent: VecDeque::new(), limit, window }
+    }
+
+    // returns how long to wait when over the limit
+    pub fn check(&mut self, now: Instant) -> Result<(), Duration> {
+        while self.sent.front().is_some_and(|&time| now.duration_since(time) >= self.window) {
+            self.sent.pop_front();
+        }
+
+        if self.sent.len() >= self.limit {
+            return Err(self.window - now.duration_since(self.sent[0]));
+        }
+
+        self.sent.push_back(now);
+        Ok(())
+    }
+}
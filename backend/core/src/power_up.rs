@@ -0,0 +1,57 @@
+use std::time::{Duration, Instant};
+use geo::Point;
+
+use crate::event::PowerUp;
+
+pub(crate) const KINDS: [PowerUp; 4] = [PowerUp::ScoreBoost, PowerUp::Invisibility, PowerUp::Radar, PowerUp::Shield];
+
+impl PowerUp {
+    // how long the effect lasts once picked up, one-off effects don't run out
+    pub(crate) fn lasts(self) -> Option<Duration> {
+        match self {
+            PowerUp::ScoreBoost => Some(Duration::from_secs(30)),
+            PowerUp::Invisibility => Some(Duration::from_secs(45)),
+            PowerUp::Radar | PowerUp::Shield => None,
+        }
+    }
+
+    // the radar only helps the seeker, everything else only helps hiders
+    pub(crate) fn for_seeker(self) -> bool {
+        self == PowerUp::Radar
+    }
+}
+
+pub(crate) struct Item {
+    pub kind: PowerUp,
+    pub pos: Point,
+    pub expires: Instant,
+}
+
+// What a player has picked up and not used up yet
+#[derive(Default)]
+pub(crate) struct Buffs {
+    pub boosted_until: Option<Instant>,
+    pub invisible_until: Option<Instant>,
+    pub shield: bool,
+}
+
+impl Buffs {
+    pub fn apply(&mut self, kind: PowerUp, now: Instant) {
+        let until = kind.lasts().map(|lasts| now + lasts);
+
+        match kind {
+            PowerUp::ScoreBoost => self.boosted_until = until,
+            PowerUp::Invisibility => self.invisible_until = until,
+            PowerUp::Shield => self.shield = true,
+            PowerUp::Radar => (),
+        }
+    }
+
+    pub fn boosted(&self, now: Instant) -> bool {
+        self.boosted_until.is_some_and(|until| now < until)
+    }
+
+    pub fn invisible(&self, now: Instant) -> bool {
+        self.invisible_until.is_some_and(|until| now < until)
+    }
+}
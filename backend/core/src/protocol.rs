@@ -0,0 +1,19 @@
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+
+pub const PROTOCOL_VERSION: u32 = 2;
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+
+// clients that predate the handshake don't send a version
+pub const LEGACY_PROTOCOL_VERSION: u32 = 1;
+
+pub fn is_supported(version: u32) -> bool {
+    (MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&version)
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Encoding {
+    #[default]
+    Json,
+    MessagePack,
+}
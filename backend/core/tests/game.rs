@@ -0,0 +1,495 @@
+use std::{sync::Arc, time::{Duration, Instant}};
+
+use geo::{GeodesicDistance, Point};
+use rand::{rngs::StdRng, SeedableRng};
+
+use hide_and_seek_core::{
+    error::{ErrorCode, ErrorDetails},
+    event::*,
+    game::{Command, Effect, Game, Images, Player, Settings},
+    moderation::WordFilter,
+};
+
+const HOST: i64 = 1;
+const GUEST: i64 = 2;
+
+struct NoImages;
+
+impl Images for NoImages {
+    fn exists(&self, _: u16, _: &str) -> bool {
+        false
+    }
+}
+
+fn new_game(now: Instant) -> Game {
+    let settings = Settings { host: HOST, x: 0.0, y: 0.0, minutes: 1, tag_mode: TagMode::Instant, power_ups: false, radius: None };
+    let host = Player::new("host".to_string(), false, now);
+
+    Game::new(7, settings, host, Arc::new(NoImages), Arc::new(WordFilter::default()), StdRng::seed_from_u64(0))
+}
+
+fn client(game: &mut Game, now: Instant, sender: i64, event: ClientEvent) -> (Option<ServerEvent>, Vec<Effect>) {
+    let outcome = game.handle(now, Command::Client { sender, event });
+    (outcome.reply, outcome.effects)
+}
+
+fn sent(effects: &[Effect]) -> Vec<(Vec<i64>, &ServerEvent)> {
+    effects.iter().filter_map(|effect| match effect {
+        Effect::Send { to, event } => Some((to.clone(), event)),
+        _ => None,
+    }).collect()
+}
+
+#[test]
+fn join_greets_existing_players() {
+    let now = Instant::now();
+    let mut game = new_game(now);
+
+    let outcome = game.handle(now, Command::Join { id: GUEST, player: Player::new("guest".to_string(), false, now) });
+
+    assert!(matches!(outcome.reply, Some(ServerEvent::JoinedGame { id: 7, host: HOST, .. })));
+    assert!(matches!(outcome.effects[0], Effect::Send { ref to, event: ServerEvent::PlayerJoined { id: GUEST, .. } } if to == &[HOST]));
+    assert!(matches!(outcome.effects[1], Effect::Joined(GUEST)));
+}
+
+#[test]
+fn rules_are_checked_without_side_effects() {
+    let now = Instant::now();
+    let mut game = new_game(now);
+
+    let (reply, effects) = client(&mut game, now, HOST, ClientEvent::StartGame);
+    assert!(matches!(reply, Some(ServerEvent::Error { code: ErrorCode::NotEnoughPlayers, .. })));
+    assert!(effects.is_empty());
+
+    let (reply, effects) = client(&mut game, now, GUEST, ClientEvent::LeaveGame);
+    assert!(matches!(reply, Some(ServerEvent::Error { code: ErrorCode::NotInGame, .. })));
+    assert!(effects.is_empty());
+
+    let (reply, effects) = client(&mut game, now, HOST, ClientEvent::UpdatePosition { x: f64::NAN, y: f64::INFINITY });
+    assert!(matches!(reply, Some(ServerEvent::Error { code: ErrorCode::InvalidMessage, .. })));
+    assert!(effects.is_empty());
+}
+
+#[test]
+fn scores_follow_the_given_time() {
+    let start = Instant::now();
+    let mut game = new_game(start);
+
+    game.handle(start, Command::Join { id: GUEST, player: Player::new("guest".to_string(), false, start) });
+    client(&mut game, start, HOST, ClientEvent::UpdatePosition { x: 0.0, y: 0.0 });
+    client(&mut game, start, GUEST, ClientEvent::UpdatePosition { x: 0.0, y: 0.0 });
+    client(&mut game, start, HOST, ClientEvent::StartGame);
+
+    let mut last = None;
+
+    for second in 1..=60 {
+        let effects = game.handle(start + Duration::from_secs(second), Command::Tick).effects;
+        last = Some(effects);
+    }
+
+    let effects = last.unwrap();
+    let events = sent(&effects);
+
+    let scores = match events[0] {
+        (ref to, ServerEvent::ScoreUpdate { scores, seconds_left: 0 }) if to == &[HOST, GUEST] => scores,
+        _ => panic!("no final score update"),
+    };
+
+    let (&winner, &score) = scores.iter().find(|(_, &score)| score > 0.0).unwrap();
+    assert_eq!(score, 600.0);
+    assert!(matches!(events[1].1, ServerEvent::GameEnded { winner: w, .. } if *w == winner));
+}
+
+#[test]
+fn direct_messages_reach_only_both_players() {
+    let now = Instant::now();
+    let mut game = new_game(now);
+
+    game.handle(now, Command::Join { id: GUEST, player: Player::new("guest".to_string(), false, now) });
+    game.handle(now, Command::Join { id: 3, player: Player::new("other".to_string(), false, now) });
+
+    let chat = ClientEvent::Chat { message: "hi".to_string(), image: None, channel: ChatChannel::Direct(GUEST) };
+    let (reply, effects) = client(&mut game, now, HOST, chat);
+
+    assert!(reply.is_none());
+    assert!(matches!(sent(&effects)[..], [(ref to, ServerEvent::Chat { .. })] if to == &[HOST, GUEST]));
+}
+
+#[test]
+fn last_player_leaving_closes_the_game() {
+    let now = Instant::now();
+    let mut game = new_game(now);
+
+    game.handle(now, Command::Join { id: GUEST, player: Player::new("guest".to_string(), false, now) });
+    game.handle(now, Command::Leave { id: HOST });
+
+    let (reply, effects) = client(&mut game, now, GUEST, ClientEvent::LeaveGame);
+
+    assert!(matches!(reply, Some(ServerEvent::LeftGame)));
+    assert!(matches!(effects[..], [Effect::Left(GUEST), Effect::Closed { ref players }] if players.is_empty()));
+}
+
+fn add_bot(game: &mut Game, now: Instant, difficulty: Difficulty) -> i64 {
+    let (reply, effects) = client(game, now, HOST, ClientEvent::AddBot { difficulty, path: Vec::new() });
+    assert!(reply.is_none());
+
+    match sent(&effects)[..] {
+        [(_, &ServerEvent::PlayerJoined { id, .. })] => id,
+        _ => panic!("bot never joined"),
+    }
+}
+
+#[test]
+fn only_the_host_adds_a_limited_number_of_bots() {
+    let now = Instant::now();
+    let mut game = new_game(now);
+
+    game.handle(now, Command::Join { id: GUEST, player: Player::new("guest".to_string(), false, now) });
+
+    let (reply, _) = client(&mut game, now, GUEST, ClientEvent::AddBot { difficulty: Difficulty::Easy, path: Vec::new() });
+    assert!(matches!(reply, Some(ServerEvent::Error { code: ErrorCode::NotHost, .. })));
+
+    for _ in 0..8 {
+        add_bot(&mut game, now, Difficulty::Easy);
+    }
+
+    let (reply, effects) = client(&mut game, now, HOST, ClientEvent::AddBot { difficulty: Difficulty::Easy, path: Vec::new() });
+    assert!(matches!(reply, Some(ServerEvent::Error { code: ErrorCode::TooManyBots, .. })));
+    assert!(effects.is_empty());
+}
+
+#[test]
+fn bot_names_never_repeat_and_photo_games_have_no_bots() {
+    let now = Instant::now();
+    let mut game = new_game(now);
+
+    let name = |effects: &[Effect]| match sent(effects)[..] {
+        [(_, ServerEvent::PlayerJoined { name, .. })] => name.clone(),
+        _ => panic!("bot never joined"),
+    };
+
+    let first = add_bot(&mut game, now, Difficulty::Easy);
+    add_bot(&mut game, now, Difficulty::Easy);
+    client(&mut game, now, HOST, ClientEvent::RemoveBot { player: first });
+
+    let (_, effects) = client(&mut game, now, HOST, ClientEvent::AddBot { difficulty: Difficulty::Easy, path: Vec::new() });
+    assert_eq!(name(&effects), "Bot 3 (Easy)");
+
+    let settings = Settings { host: HOST, x: 0.0, y: 0.0, minutes: 1, tag_mode: TagMode::Photo, power_ups: false, radius: None };
+    let host = Player::new("host".to_string(), false, now);
+    let mut game = Game::new(7, settings, host, Arc::new(NoImages), Arc::new(WordFilter::default()), StdRng::seed_from_u64(0));
+
+    let (reply, effects) = client(&mut game, now, HOST, ClientEvent::AddBot { difficulty: Difficulty::Easy, path: Vec::new() });
+    assert!(matches!(reply, Some(ServerEvent::Error { code: ErrorCode::BotsUnavailable, .. })));
+    assert!(effects.is_empty());
+}
+
+#[test]
+fn bot_seeker_catches_a_standing_hider() {
+    let start = Instant::now();
+    let mut game = new_game(start);
+    let bot = add_bot(&mut game, start, Difficulty::Hard);
+
+    let (_, effects) = client(&mut game, start, HOST, ClientEvent::StartGame);
+    let seeker = match sent(&effects)[..] {
+        [(_, &ServerEvent::GameStarted { seeker }), ..] => seeker,
+        _ => panic!("game never started"),
+    };
+
+    // hand the bot the seeker role, it won't tag straight back
+    if seeker == HOST {
+        client(&mut game, start, HOST, ClientEvent::TagPlayer { player: bot, image: None });
+    }
+
+    for second in 1..60 {
+        let now = start + Duration::from_secs(second);
+        client(&mut game, now, HOST, ClientEvent::UpdatePosition { x: 0.0, y: 0.0005 });
+
+        let effects = game.handle(now, Command::Tick).effects;
+
+        if sent(&effects).iter().any(|(_, event)| matches!(event, ServerEvent::PlayerTagged { tagger, tagged: HOST } if *tagger == bot)) {
+            assert!(seeker == bot || second > 10, "tagged back after {}s", second);
+
+            // only people leave tracks behind
+            assert!(game.tracks().contains_key(&HOST));
+            assert!(!game.tracks().contains_key(&bot));
+            return;
+        }
+    }
+
+    panic!("bot never caught the hider");
+}
+
+#[test]
+fn bots_do_not_keep_a_game_open() {
+    let now = Instant::now();
+    let mut game = new_game(now);
+    let bot = add_bot(&mut game, now, Difficulty::Medium);
+
+    let (reply, effects) = client(&mut game, now, HOST, ClientEvent::LeaveGame);
+
+    assert!(matches!(reply, Some(ServerEvent::LeftGame)));
+    assert!(matches!(effects[..], [Effect::Left(HOST), Effect::Send { .. }, Effect::Closed { ref players }] if players == &[bot]));
+}
+
+fn power_up_game(now: Instant) -> (Game, i64, i64) {
+    let settings = Settings { host: HOST, x: 0.0, y: 0.0, minutes: 10, tag_mode: TagMode::Instant, power_ups: true, radius: Some(200.0) };
+    let host = Player::new("host".to_string(), false, now);
+    let mut game = Game::new(7, settings, host, Arc::new(NoImages), Arc::new(WordFilter::default()), StdRng::seed_from_u64(0));
+
+    game.handle(now, Command::Join { id: GUEST, player: Player::new("guest".to_string(), false, now) });
+
+    let (_, effects) = client(&mut game, now, HOST, ClientEvent::StartGame);
+    let seeker = match sent(&effects)[..] {
+        [(_, &ServerEvent::GameStarted { seeker }), ..] => seeker,
+        _ => panic!("game never started"),
+    };
+
+    (game, seeker, if seeker == HOST { GUEST } else { HOST })
+}
+
+fn spawned(effects: &[Effect]) -> Option<(u32, PowerUp, f64, f64)> {
+    sent(effects).into_iter().find_map(|(_, event)| match *event {
+        ServerEvent::PowerUpSpawned { id, kind, x, y, .. } => Some((id, kind, x, y)),
+        _ => None,
+    })
+}
+
+#[test]
+fn power_ups_spawn_in_the_play_area_and_expire() {
+    let start = Instant::now();
+    let (mut game, _, _) = power_up_game(start);
+
+    let mut spawns = Vec::new();
+    let mut expired = Vec::new();
+
+    for second in 1..=200 {
+        let effects = game.handle(start + Duration::from_secs(second), Command::Tick).effects;
+
+        if let Some((id, _, x, y)) = spawned(&effects) {
+            assert!(Point::new(x, y).geodesic_distance(&Point::new(0.0, 0.0)) <= 200.0);
+            spawns.push((second, id));
+        }
+
+        for (_, event) in sent(&effects) {
+            if let ServerEvent::PowerUpExpired { id } = event {
+                expired.push((second, *id));
+            }
+        }
+    }
+
+    // never more than three on the map at once
+    assert_eq!(spawns, [(30, 0), (60, 1), (90, 2), (150, 3), (180, 4)]);
+    assert_eq!(expired, [(150, 0), (180, 1)]);
+}
+
+#[test]
+fn seeker_radar_reveals_hiders_and_shields_stop_one_tag() {
+    let start = Instant::now();
+    let (mut game, seeker, hider) = power_up_game(start);
+
+    let (mut pinged, mut shielded) = (false, false);
+
+    for second in 1..600 {
+        let now = start + Duration::from_secs(second);
+        client(&mut game, now, hider, ClientEvent::UpdatePosition { x: 0.001, y: 0.0 });
+
+        let (id, kind, x, y) = match spawned(&game.handle(now, Command::Tick).effects) {
+            Some(item) => item,
+            None => continue,
+        };
+
+        // only the player the item is meant for picks it up
+        let (wrong, right) = if kind == PowerUp::Radar { (hider, seeker) } else { (seeker, hider) };
+        let (_, effects) = client(&mut game, now, wrong, ClientEvent::UpdatePosition { x, y });
+        assert!(sent(&effects).is_empty());
+
+        let (_, effects) = client(&mut game, now, right, ClientEvent::UpdatePosition { x, y });
+        assert!(matches!(sent(&effects)[0], (ref to, &ServerEvent::PowerUpCollected { id: i, player, .. }) if to == &[HOST, GUEST] && i == id && player == right));
+
+        match kind {
+            PowerUp::Radar => {
+                assert!(matches!(sent(&effects)[1], (ref to, ServerEvent::RadarPing { positions }) if to == &[seeker] && positions == &[(hider, x, y)]));
+                pinged = true;
+            },
+            PowerUp::Shield if !shielded => {
+                client(&mut game, now, seeker, ClientEvent::UpdatePosition { x, y });
+
+                let (_, effects) = client(&mut game, now, seeker, ClientEvent::TagPlayer { player: hider, image: None });
+                assert!(matches!(sent(&effects)[..], [(_, &ServerEvent::ShieldBroken { player, tagger })] if player == hider && tagger == seeker));
+
+                let (_, effects) = client(&mut game, now, seeker, ClientEvent::TagPlayer { player: hider, image: None });
+                assert!(matches!(sent(&effects)[..], [(_, ServerEvent::PlayerTagged { .. })]));
+                client(&mut game, now, hider, ClientEvent::TagPlayer { player: seeker, image: None });
+                shielded = true;
+            },
+            _ => (),
+        }
+
+        if pinged && shielded {
+            return;
+        }
+    }
+
+    panic!("never found both a radar and a shield");
+}
+
+fn chat(message: &str, channel: ChatChannel) -> ClientEvent {
+    ClientEvent::Chat { message: message.to_string(), image: None, channel }
+}
+
+fn error_code(reply: Option<ServerEvent>) -> Option<ErrorCode> {
+    match reply {
+        Some(ServerEvent::Error { code, .. }) => Some(code),
+        _ => None,
+    }
+}
+
+#[test]
+fn chat_is_limited_in_length_and_rate() {
+    let now = Instant::now();
+    let mut game = new_game(now);
+
+    let (reply, effects) = client(&mut game, now, HOST, chat(&"a".repeat(501), ChatChannel::All));
+    assert!(matches!(reply, Some(ServerEvent::Error { code: ErrorCode::MessageTooLong, details: Some(ErrorDetails::MaxLength { max: 500 }), .. })));
+    assert!(effects.is_empty());
+
+    // posts that go nowhere don't use up the limit
+    for _ in 0..10 {
+        let (reply, _) = client(&mut game, now, HOST, chat("go team", ChatChannel::Team));
+        assert_eq!(error_code(reply), Some(ErrorCode::ChannelUnavailable));
+    }
+
+    for second in 0..5 {
+        let (reply, _) = client(&mut game, now + Duration::from_secs(second), HOST, chat(&"a".repeat(500), ChatChannel::All));
+        assert!(reply.is_none());
+    }
+
+    let (reply, effects) = client(&mut game, now + Duration::from_secs(6), HOST, chat("one more", ChatChannel::All));
+    assert!(matches!(reply, Some(ServerEvent::Error { code: ErrorCode::RateLimited, details: Some(ErrorDetails::RetryAfter { seconds }), .. }) if seconds == 4.0));
+    assert!(effects.is_empty());
+
+    let (reply, _) = client(&mut game, now + Duration::from_secs(10), HOST, chat("one more", ChatChannel::All));
+    assert!(reply.is_none());
+}
+
+#[test]
+fn only_the_host_mutes_and_unmutes() {
+    let now = Instant::now();
+    let mut game = new_game(now);
+
+    game.handle(now, Command::Join { id: GUEST, player: Player::new("guest".to_string(), false, now) });
+
+    let (reply, _) = client(&mut game, now, GUEST, ClientEvent::MutePlayer { player: HOST });
+    assert_eq!(error_code(reply), Some(ErrorCode::NotHost));
+
+    let (reply, _) = client(&mut game, now, HOST, ClientEvent::MutePlayer { player: HOST });
+    assert_eq!(error_code(reply), Some(ErrorCode::InvalidTarget));
+
+    let (_, effects) = client(&mut game, now, HOST, ClientEvent::MutePlayer { player: GUEST });
+    assert!(matches!(sent(&effects)[..], [(ref to, &ServerEvent::PlayerMuted { id: GUEST, muted: true })] if to == &[HOST, GUEST]));
+
+    // muting twice changes nothing
+    let (_, effects) = client(&mut game, now, HOST, ClientEvent::MutePlayer { player: GUEST });
+    assert!(effects.is_empty());
+
+    let (reply, _) = client(&mut game, now, GUEST, chat("let me talk", ChatChannel::All));
+    assert_eq!(error_code(reply), Some(ErrorCode::Muted));
+
+    let (_, effects) = client(&mut game, now, HOST, ClientEvent::UnmutePlayer { player: GUEST });
+    assert!(matches!(sent(&effects)[..], [(_, &ServerEvent::PlayerMuted { id: GUEST, muted: false })]));
+
+    let (reply, effects) = client(&mut game, now, GUEST, chat("thanks", ChatChannel::All));
+    assert!(reply.is_none());
+    assert!(matches!(sent(&effects)[..], [(_, ServerEvent::Chat { sender: GUEST, .. })]));
+}
+
+#[test]
+fn joining_players_get_the_recent_public_chat() {
+    let start = Instant::now();
+    let mut game = new_game(start);
+
+    game.handle(start, Command::Join { id: GUEST, player: Player::new("guest".to_string(), false, start) });
+    client(&mut game, start, GUEST, chat("just us", ChatChannel::Direct(HOST)));
+
+    // two seconds apart stays under the rate limit
+    for message in 0..55 {
+        let (reply, _) = client(&mut game, start + Duration::from_secs(message * 2), HOST, chat(&message.to_string(), ChatChannel::All));
+        assert!(reply.is_none());
+    }
+
+    let now = start + Duration::from_secs(120);
+    let outcome = game.handle(now, Command::Join { id: 3, player: Player::new("late".to_string(), false, now) });
+
+    let chat = match outcome.reply {
+        Some(ServerEvent::JoinedGame { chat, .. }) => chat,
+        _ => panic!("didn't join"),
+    };
+
+    let messages: Vec<String> = chat.iter().map(|entry| entry.message.clone()).collect();
+    assert_eq!(messages, (5..55).map(|message: u32| message.to_string()).collect::<Vec<_>>());
+    assert!(chat.iter().all(|entry| entry.sender == HOST));
+}
+
+#[test]
+fn score_deltas_carry_only_scores_that_moved() {
+    let start = Instant::now();
+    let mut game = new_game(start);
+
+    game.handle(start, Command::Join { id: GUEST, player: Player::new("guest".to_string(), true, start) });
+
+    let (reply, _) = client(&mut game, start, GUEST, ClientEvent::ResyncScores);
+    assert_eq!(error_code(reply), Some(ErrorCode::GameNotStarted));
+
+    // about 110 metres out, a hider gains a point every five or six seconds
+    client(&mut game, start, HOST, ClientEvent::UpdatePosition { x: 0.0, y: 0.001 });
+    client(&mut game, start, GUEST, ClientEvent::UpdatePosition { x: 0.0, y: 0.001 });
+
+    let (_, effects) = client(&mut game, start, HOST, ClientEvent::StartGame);
+    let events = sent(&effects);
+
+    let seeker = match events[..] {
+        [(_, &ServerEvent::GameStarted { seeker }), (ref to, ServerEvent::ScoreSnapshot { seq: 0, scores, seconds_left: 60 }), ..] => {
+            assert_eq!(to, &[GUEST]);
+            assert!(scores.values().all(|&score| score == 0.0));
+            seeker
+        },
+        _ => panic!("no snapshot at the start"),
+    };
+    let hider = if seeker == HOST { GUEST } else { HOST };
+
+    let mut reported = Vec::new();
+
+    for second in 1..=60 {
+        let now = start + Duration::from_secs(second);
+        let effects = game.handle(now, Command::Tick).effects;
+
+        for (to, event) in sent(&effects) {
+            match event {
+                ServerEvent::ScoreDelta { seq, changed, .. } => {
+                    assert_eq!((to, *seq), (vec![GUEST], second));
+                    assert!(!changed.contains_key(&seeker));
+
+                    if let Some(&score) = changed.get(&hider) {
+                        reported.push((second, score));
+                    }
+                },
+                ServerEvent::ScoreUpdate { scores, .. } => {
+                    assert_eq!(to, [HOST]);
+                    assert_eq!(scores.len(), 2);
+                },
+                _ => (),
+            }
+        }
+
+        if second == 30 {
+            let (reply, _) = client(&mut game, now, GUEST, ClientEvent::ResyncScores);
+            assert!(matches!(reply, Some(ServerEvent::ScoreSnapshot { seq: 30, ref scores, seconds_left: 30 }) if scores.len() == 2 && scores[&hider] > 5.0));
+        }
+    }
+
+    // a point at a time, and the exact score once the game is over
+    assert!(reported.len() > 5 && reported.len() < 15, "{:?}", reported);
+    assert!(reported.windows(2).all(|pair| pair[1].1 - pair[0].1 >= 1.0 || pair[1].0 == 60));
+    assert_eq!(reported.last().unwrap().0, 60);
+}
@@ -0,0 +1,23 @@
+use std::fs;
+
+use hide_and_seek_core::moderation::WordFilter;
+
+#[test]
+fn filter_loads_words_and_stars_them_out() {
+    let path = std::env::temp_dir().join(format!("hide_and_seek_{}_words.txt", std::process::id()));
+    fs::write(&path, "# words nobody should read\nDarn\n\n  heck  \n").unwrap();
+
+    let filter = WordFilter::load(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(filter.len(), 2);
+
+    // only whole words, whatever their case
+    assert_eq!(filter.apply("Darn it, what the HECK!"), "**** it, what the ****!");
+    assert_eq!(filter.apply("darned heckling"), "darned heckling");
+    assert_eq!(filter.apply("# words"), "# words");
+
+    assert!(WordFilter::default().is_empty());
+    assert_eq!(WordFilter::default().apply("darn"), "darn");
+    assert!(WordFilter::load(&path).is_err());
+}
@@ -0,0 +1,290 @@
+use std::{
+    collections::BTreeMap,
+    env,
+    f64::consts::TAU,
+    process,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use tokio::{runtime, task::JoinHandle, time};
+
+use hide_and_seek_client::*;
+
+const USAGE: &str = "Usage: loadtest [--url ws://127.0.0.1:2369/] [--players 1000] [--game-size 5] [--rate 1]
+                [--chat-interval 10] [--duration 60] [--ramp 10]";
+
+// chats carry the time they were sent so receivers can measure delivery
+const CHAT_PREFIX: &str = "load ";
+const WALKING_SPEED: f64 = 1.4;
+const METRES_PER_DEGREE: f64 = 111_320.0;
+
+struct Options {
+    url: String,
+    players: usize,
+    game_size: usize,
+    // position updates per second and player
+    rate: f64,
+    chat_interval: Duration,
+    duration: Duration,
+    // connections are spread over this long instead of opened all at once
+    ramp: Duration,
+}
+
+impl Options {
+    fn parse() -> Result<Self, String> {
+        let mut options = Options {
+            url: "ws://127.0.0.1:2369/".to_string(),
+            players: 1000,
+            game_size: 5,
+            rate: 1.0,
+            chat_interval: Duration::from_secs(10),
+            duration: Duration::from_secs(60),
+            ramp: Duration::from_secs(10),
+        };
+
+        let mut args = env::args().skip(1);
+
+        while let Some(arg) = args.next() {
+            let value = args.next().ok_or_else(|| format!("Missing value for {}", arg))?;
+
+            match arg.as_str() {
+                "--url" => options.url = value,
+                "--players" => options.players = number(&arg, &value)?,
+                "--game-size" => options.game_size = number(&arg, &value)?,
+                "--rate" => options.rate = number(&arg, &value)?,
+                "--chat-interval" => options.chat_interval = Duration::from_secs_f64(number(&arg, &value)?),
+                "--duration" => options.duration = Duration::from_secs_f64(number(&arg, &value)?),
+                "--ramp" => options.ramp = Duration::from_secs_f64(number(&arg, &value)?),
+                _ => return Err(format!("Unknown option {}", arg)),
+            }
+        }
+
+        if options.game_size == 0 || options.rate <= 0.0 || options.chat_interval.is_zero() {
+            return Err("--game-size, --rate and --chat-interval must be positive".to_string());
+        }
+
+        Ok(options)
+    }
+}
+
+fn number<T: FromStr>(arg: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("Invalid value for {}: {}", arg, value))
+}
+
+#[derive(Default)]
+struct Stats {
+    connected: usize,
+    games: usize,
+    disconnects: usize,
+    score_updates: usize,
+    connects: Vec<Duration>,
+    replies: Vec<Duration>,
+    broadcasts: Vec<Duration>,
+    errors: BTreeMap<String, usize>,
+}
+
+type Shared = Arc<Mutex<Stats>>;
+
+impl Stats {
+    fn error(&mut self, context: &str, err: Error) {
+        *self.errors.entry(format!("{}: {}", context, err)).or_default() += 1;
+    }
+
+    fn reply<T>(&mut self, context: &str, sent: Instant, result: Result<T, Error>) -> Option<T> {
+        match result {
+            Ok(value) => {
+                self.replies.push(sent.elapsed());
+                Some(value)
+            },
+            Err(err) => {
+                self.error(context, err);
+                None
+            },
+        }
+    }
+}
+
+fn percentiles(samples: &mut [Duration]) -> String {
+    if samples.is_empty() {
+        return "no samples".to_string();
+    }
+
+    samples.sort();
+    let at = |p: f64| samples[((samples.len() - 1) as f64 * p).round() as usize];
+    let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+
+    format!(
+        "p50 {:.2}ms  p90 {:.2}ms  p99 {:.2}ms  max {:.2}ms  ({} samples)",
+        ms(at(0.5)), ms(at(0.9)), ms(at(0.99)), ms(at(1.0)), samples.len(),
+    )
+}
+
+fn main() {
+    let options = Options::parse().unwrap_or_else(|err| {
+        eprintln!("{}\n{}", err, USAGE);
+        process::exit(2);
+    });
+
+    let runtime = runtime::Builder::new_multi_thread().enable_all().build().expect("Failed to start runtime");
+    let (mut stats, elapsed) = runtime.block_on(run(Arc::new(options)));
+
+    let requests = stats.replies.len() + stats.errors.values().sum::<usize>();
+
+    println!();
+    println!("Players:           {} connected, {} games", stats.connected, stats.games);
+    println!("Elapsed:           {:.1}s", elapsed.as_secs_f64());
+    println!("Requests:          {} ({:.1}/s)", requests, requests as f64 / elapsed.as_secs_f64());
+    println!("Score updates:     {}", stats.score_updates);
+    println!("Disconnects:       {}", stats.disconnects);
+    println!("Connect latency:   {}", percentiles(&mut stats.connects));
+    println!("Reply latency:     {}", percentiles(&mut stats.replies));
+    println!("Broadcast latency: {}", percentiles(&mut stats.broadcasts));
+
+    if !stats.errors.is_empty() {
+        println!("Errors:");
+
+        for (error, count) in &stats.errors {
+            println!("  {:>6}  {}", count, error);
+        }
+    }
+}
+
+async fn run(options: Arc<Options>) -> (Stats, Duration) {
+    let stats = Shared::default();
+    let origin = Instant::now();
+    let deadline = origin + options.ramp + options.duration;
+    let groups = options.players.div_ceil(options.game_size);
+
+    println!("Starting {} players in {} games against {}", options.players, groups, options.url);
+
+    let games: Vec<_> = (0..groups).map(|group| {
+        let size = options.game_size.min(options.players - group * options.game_size);
+        let start = origin + options.ramp.mul_f64(group as f64 / groups as f64);
+        let (options, stats) = (options.clone(), stats.clone());
+
+        tokio::spawn(async move {
+            time::sleep_until(start.into()).await;
+            run_game(options, stats, group, size, origin, deadline).await;
+        })
+    }).collect();
+
+    for game in games {
+        let _ = game.await;
+    }
+
+    let stats = std::mem::take(&mut *stats.lock().unwrap());
+    (stats, origin.elapsed())
+}
+
+async fn run_game(options: Arc<Options>, stats: Shared, group: usize, size: usize, origin: Instant, deadline: Instant) {
+    let mut players = Vec::new();
+
+    for index in 0..size {
+        let config = Config::new(&options.url, format!("load {}-{}", group, index));
+        let sent = Instant::now();
+
+        match connect(config).await {
+            Ok(player) => {
+                let mut stats = stats.lock().unwrap();
+                stats.connected += 1;
+                stats.connects.push(sent.elapsed());
+                players.push(player);
+            },
+            Err(err) => stats.lock().unwrap().error("connect", err),
+        }
+    }
+
+    let (host, guests) = match players.split_first() {
+        Some(split) => split,
+        None => return,
+    };
+
+    let mut rng = StdRng::from_entropy();
+    let center = (rng.gen_range(-180.0..180.0), rng.gen_range(-60.0..60.0));
+    let minutes = options.duration.as_secs() / 60 + 1;
+
+    let sent = Instant::now();
+    let created = host.0.create_game(center.0, center.1, minutes, TagMode::Instant, false, None).await;
+    let game = match stats.lock().unwrap().reply("create", sent, created) {
+        Some(game) => game,
+        None => return,
+    };
+
+    stats.lock().unwrap().games += 1;
+
+    for (guest, _) in guests {
+        let sent = Instant::now();
+        let joined = guest.join_game(game).await;
+        stats.lock().unwrap().reply("join", sent, joined);
+    }
+
+    if !guests.is_empty() {
+        let sent = Instant::now();
+        let started = host.0.start_game().await;
+        stats.lock().unwrap().reply("start", sent, started);
+    }
+
+    let tasks: Vec<JoinHandle<()>> = players.into_iter().flat_map(|(client, events)| [
+        tokio::spawn(play(client, options.clone(), stats.clone(), center, origin, deadline)),
+        tokio::spawn(listen(events, stats.clone(), origin, deadline)),
+    ]).collect();
+
+    for task in tasks {
+        let _ = task.await;
+    }
+}
+
+// walks around the game's centre, updating the position and chatting now and then
+async fn play(client: Client, options: Arc<Options>, stats: Shared, center: (f64, f64), origin: Instant, deadline: Instant) {
+    let mut rng = StdRng::from_entropy();
+    let interval = Duration::from_secs_f64(1.0 / options.rate);
+    let step = WALKING_SPEED * interval.as_secs_f64() / METRES_PER_DEGREE;
+
+    let mut pos = center;
+    let mut heading: f64 = rng.gen_range(0.0..TAU);
+    let mut next_chat = Instant::now() + options.chat_interval.mul_f64(rng.gen());
+
+    // players don't update in lockstep
+    time::sleep(interval.mul_f64(rng.gen())).await;
+    let mut ticker = time::interval(interval);
+
+    while Instant::now() < deadline {
+        ticker.tick().await;
+
+        heading += rng.gen_range(-0.5..0.5);
+        pos = (pos.0 + step * heading.cos() / center.1.to_radians().cos(), pos.1 + step * heading.sin());
+
+        let sent = Instant::now();
+        let result = client.update_position(pos.0, pos.1).await;
+        stats.lock().unwrap().reply("position", sent, result);
+
+        if Instant::now() >= next_chat {
+            next_chat += options.chat_interval;
+
+            let message = format!("{}{}", CHAT_PREFIX, origin.elapsed().as_micros());
+            let sent = Instant::now();
+            let result = client.chat(message, None, ChatChannel::All).await;
+            stats.lock().unwrap().reply("chat", sent, result);
+        }
+    }
+}
+
+async fn listen(mut events: Events, stats: Shared, origin: Instant, deadline: Instant) {
+    while let Ok(Some(event)) = time::timeout_at(deadline.into(), events.recv()).await {
+        let mut stats = stats.lock().unwrap();
+
+        match event {
+            Event::Server(ServerEvent::Chat { message, .. }) => {
+                if let Some(micros) = message.strip_prefix(CHAT_PREFIX).and_then(|micros| micros.parse().ok()) {
+                    stats.broadcasts.push(origin.elapsed().saturating_sub(Duration::from_micros(micros)));
+                }
+            },
+            Event::Server(ServerEvent::ScoreUpdate { .. }) => stats.score_updates += 1,
+            Event::Disconnected => stats.disconnects += 1,
+            _ => (),
+        }
+    }
+}
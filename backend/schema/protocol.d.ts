@@ -0,0 +1,120 @@
+// GENERATED CODE - DO NOT EDIT.
+// Run `cargo run --bin codegen` in backend/ to regenerate.
+
+export type ClientEvent =
+  | "LeaveGame"
+  | "StartGame"
+  | "ConfirmTag"
+  | "DisputeTag"
+  | "ResyncScores"
+  | { Connect: { name: string; version?: number; encoding?: Encoding; score_deltas?: boolean; resume?: string | null } }
+  | { Chat: { message: string; image?: string | null; channel?: ChatChannel } }
+  | { JoinGame: { game: number } }
+  | { CreateGame: { x: number; y: number; minutes: number; tag_mode?: TagMode; power_ups?: boolean; radius?: number | null } }
+  | { MutePlayer: { player: number } }
+  | { UnmutePlayer: { player: number } }
+  | { AddBot: { difficulty?: Difficulty; path?: [number, number][] } }
+  | { RemoveBot: { player: number } }
+  | { UpdatePosition: { x: number; y: number } }
+  | { TagPlayer: { player: number; image?: string | null } }
+  | { TagWithProof: { nonce: string } };
+
+export type Encoding =
+  | "Json"
+  | "MessagePack";
+
+export type ChatChannel =
+  | "All"
+  | "Team"
+  | "Hiders"
+  | "Seeker"
+  | { Direct: number };
+
+export type TagMode =
+  | "Instant"
+  | "Photo"
+  | "Handshake";
+
+export type Difficulty =
+  | "Easy"
+  | "Medium"
+  | "Hard";
+
+export type ServerEvent =
+  | "LeftGame"
+  | { Connected: { id: number; version: number; min_version: number; max_version: number; token: string } }
+  | { IncompatibleProtocol: { min_version: number; max_version: number } }
+  | { Chat: { sender: number; message: string; image?: string | null; channel: ChatChannel } }
+  | { Error: { code: ErrorCode; message: string; details?: ErrorDetails | null; request_id?: number | null } }
+  | { Ack: { request_id: number } }
+  | { JoinedGame: { id: number; x: number; y: number; players: [number, string][]; host: number; tag_mode: TagMode; chat: ChatEntry[] } }
+  | { PlayerJoined: { id: number; name: string } }
+  | { PlayerLeft: { id: number; new_host: number } }
+  | { PlayerMuted: { id: number; muted: boolean } }
+  | { GameStarted: { seeker: number } }
+  | { PlayerTagged: { tagger: number; tagged: number } }
+  | { TagPending: { tagger: number; tagged: number; image: string } }
+  | { TagDisputed: { tagger: number; tagged: number; by: number } }
+  | { TagNonce: { nonce: string; expires_in: number } }
+  | { ScoreUpdate: { scores: Record<string, number>; seconds_left: number } }
+  | { ScoreSnapshot: { seq: number; scores: Record<string, number>; seconds_left: number } }
+  | { ScoreDelta: { seq: number; changed: Record<string, number>; seconds_left: number } }
+  | { PowerUpSpawned: { id: number; kind: PowerUp; x: number; y: number; expires_in: number } }
+  | { PowerUpCollected: { id: number; kind: PowerUp; player: number; expires_in?: number | null } }
+  | { PowerUpExpired: { id: number } }
+  | { RadarPing: { positions: [number, number, number][] } }
+  | { ShieldBroken: { player: number; tagger: number } }
+  | { GameEnded: { winner: number; download_token?: string | null } };
+
+export type ErrorCode =
+  | "InvalidMessage"
+  | "NotConnected"
+  | "AlreadyConnected"
+  | "ServerUnavailable"
+  | "ServerTimeout"
+  | "HeartbeatTimeout"
+  | "SlowClient"
+  | "PlayerNotFound"
+  | "NotInGame"
+  | "AlreadyInGame"
+  | "GameNotFound"
+  | "GameNotStarted"
+  | "GameAlreadyStarted"
+  | "GameAlreadyEnded"
+  | "NotHost"
+  | "NotEnoughPlayers"
+  | "TooManyBots"
+  | "BotsUnavailable"
+  | "InvalidPlayArea"
+  | "PlayAreaRequired"
+  | "MessageEmpty"
+  | "MessageTooLong"
+  | "RateLimited"
+  | "Muted"
+  | "ImageNotFound"
+  | "ChannelUnavailable"
+  | "ChannelForbidden"
+  | "NotSeeker"
+  | "InvalidTarget"
+  | "WrongTagMode"
+  | "TagAlreadyPending"
+  | "NoPendingTag"
+  | "NotReferee"
+  | "PhotoRequired"
+  | "InvalidNonce"
+  | "NonceExpired"
+  | "NonceUsed";
+
+export type ErrorDetails =
+  | { RetryAfter: { seconds: number } }
+  | { MaxLength: { max: number } };
+
+export interface ChatEntry { sender: number; message: string; image?: string | null }
+
+export type PowerUp =
+  | "ScoreBoost"
+  | "Invisibility"
+  | "Radar"
+  | "Shield";
+
+export type ClientRequest = (Exclude<ClientEvent, string> | { LeaveGame: null } | { StartGame: null } | { ConfirmTag: null } | { DisputeTag: null } | { ResyncScores: null }) & { request_id?: number | null };
@@ -0,0 +1,118 @@
+use std::{
+    fs,
+    io,
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::Deserialize;
+
+pub const DEFAULT_RETENTION: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+// Game ids are reused and guessable, so downloads need the token everybody in
+// the game got with GameEnded. It picks the game and proves the downloader
+// played in it.
+#[derive(Deserialize)]
+pub struct DownloadQuery {
+    pub token: String,
+}
+
+// A directory of files kept after games, named
+// <game>-<millis>-<download token>.<extension>. Files older than the
+// retention are removed whenever a new one is added.
+#[derive(Clone)]
+pub struct Archive {
+    root: PathBuf,
+    extension: &'static str,
+    retention: Duration,
+}
+
+struct ArchivedFile {
+    path: PathBuf,
+    game: u16,
+    millis: u64,
+    token: String,
+}
+
+pub fn now_millis() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+}
+
+impl Archive {
+    pub fn new(root: impl Into<PathBuf>, extension: &'static str) -> io::Result<Self> {
+        let root = root.into();
+        fs::create_dir_all(&root)?;
+        Ok(Self { root, extension, retention: DEFAULT_RETENTION })
+    }
+
+    pub fn with_retention(mut self, retention: Duration) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    // where to put a new file, making room for it first
+    pub fn path(&self, game: u16, token: &str) -> PathBuf {
+        self.prune();
+        self.root.join(format!("{}-{}-{}.{}", game, now_millis(), token, self.extension))
+    }
+
+    // expired files are gone as far as anyone asking is concerned, even if
+    // nothing pruned them yet
+    pub fn find(&self, game: u16, token: &str) -> Option<PathBuf> {
+        let cutoff = self.cutoff();
+
+        self.files().into_iter()
+            .find(|file| file.game == game && file.token == token && file.millis > cutoff)
+            .map(|file| file.path)
+    }
+
+    // oldest first, with when each was added
+    pub fn paths(&self) -> Vec<(u64, PathBuf)> {
+        let mut files: Vec<_> = self.files().into_iter().map(|file| (file.millis, file.path)).collect();
+        files.sort();
+        files
+    }
+
+    // anything added at or before this is due to go
+    pub fn cutoff(&self) -> u64 {
+        now_millis().saturating_sub(self.retention.as_millis() as u64)
+    }
+
+    pub fn prune(&self) {
+        let cutoff = self.cutoff();
+
+        for file in self.files().into_iter().filter(|file| file.millis <= cutoff) {
+            if let Err(err) = fs::remove_file(&file.path) {
+                println!("Failed to remove {}: {}", file.path.display(), err);
+            }
+        }
+    }
+
+    // files that don't follow the naming scheme are left alone
+    fn files(&self) -> Vec<ArchivedFile> {
+        let entries = match fs::read_dir(&self.root) {
+            Ok(entries) => entries,
+            Err(_) => return Vec::new(),
+        };
+
+        entries
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+
+                if path.extension()? != self.extension {
+                    return None;
+                }
+
+                let (game, rest) = path.file_stem()?.to_str()?.split_once('-')?;
+                let (millis, token) = rest.split_once('-')?;
+
+                Some(ArchivedFile {
+                    game: game.parse().ok()?,
+                    millis: millis.parse().ok()?,
+                    token: token.to_string(),
+                    path,
+                })
+            })
+            .collect()
+    }
+}
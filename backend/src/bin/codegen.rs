@@ -0,0 +1,18 @@
+use std::{fs, path::Path};
+
+use hide_and_seek::codegen;
+
+fn main() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+
+    for (path, contents) in codegen::outputs() {
+        let path = root.join(path);
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).expect("Failed to create output directory");
+        }
+
+        fs::write(&path, contents).expect("Failed to write output");
+        println!("Wrote {}", path.display());
+    }
+}
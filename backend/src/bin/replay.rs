@@ -0,0 +1,57 @@
+use std::{fs, process};
+
+use hide_and_seek::{moderation::WordFilter, replay::Replay};
+
+const SHOWN_MISMATCHES: usize = 5;
+
+fn main() {
+    let path = match std::env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("Usage: replay <game.replay>");
+            process::exit(2);
+        },
+    };
+
+    let bytes = fs::read(&path).unwrap_or_else(|err| {
+        eprintln!("Failed to read {}: {}", path, err);
+        process::exit(1);
+    });
+
+    let replay = Replay::read(&bytes).unwrap_or_else(|err| {
+        eprintln!("Failed to decode {}: {}", path, err);
+        process::exit(1);
+    });
+
+    // chat has to go through the same filter as it did on the server
+    let word_filter = match std::env::var("WORD_FILTER") {
+        Ok(path) => WordFilter::load(path).expect("Failed to load word filter"),
+        Err(_) => WordFilter::default(),
+    };
+
+    let summary = replay.run(word_filter);
+
+    println!();
+    println!("Game {} (seed {}): {} commands replayed", replay.header.game, replay.header.seed, summary.entries);
+
+    match summary.winner {
+        Some(winner) => println!("Winner: {}", winner),
+        None => println!("The game didn't end"),
+    }
+
+    if summary.mismatches.is_empty() {
+        println!("Outcome matches the recording");
+        return;
+    }
+
+    println!("{} commands produced something else than recorded", summary.mismatches.len());
+
+    for mismatch in summary.mismatches.iter().take(SHOWN_MISMATCHES) {
+        println!();
+        println!("#{} at {:.3}s", mismatch.index, mismatch.at.as_secs_f64());
+        println!("  recorded: {}", mismatch.expected);
+        println!("  replayed: {}", mismatch.actual);
+    }
+
+    process::exit(1);
+}
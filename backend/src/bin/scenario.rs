@@ -0,0 +1,30 @@
+use std::{fs, path::Path, process};
+
+use hide_and_seek::scenario::{self, Scenario};
+
+#[actix::main]
+async fn main() {
+    let args: Vec<String> = std::env::args().collect();
+
+    let (input, output) = match &args[1..] {
+        [input, output] => (Path::new(input), Path::new(output)),
+        _ => {
+            eprintln!("Usage: {} <scenario.json> <report.json>", args[0]);
+            process::exit(2);
+        },
+    };
+
+    let scenario = Scenario::load(input).unwrap_or_else(|err| {
+        eprintln!("Failed to load {}: {:?}", input.display(), err);
+        process::exit(1);
+    });
+
+    let report = scenario::run(&scenario).await.unwrap_or_else(|err| {
+        eprintln!("Scenario failed: {:?}", err);
+        process::exit(1);
+    });
+
+    let json = serde_json::to_string_pretty(&report).expect("Failed to serialize report");
+    fs::write(output, json + "\n").expect("Failed to write report");
+    println!("Wrote {}", output.display());
+}
@@ -1,90 +1,204 @@
 use std::time::{Duration, Instant};
 
-use actix::prelude::*;
+use actix::{dev::ToEnvelope, prelude::*};
 use actix_web_actors::ws;
 
 use crate::server::*;
+use crate::error::ErrorCode;
+use crate::game::Game;
+use crate::outbox::{Flush, Outbox};
 use crate::message::*;
+use crate::protocol::{self, Encoding, Payload, LEGACY_PROTOCOL_VERSION, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION};
 
 const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
 const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
 
-#[derive(Debug)]
 pub struct Session {
     hb: Instant,
     id: Option<i64>,
-    server: Addr<GameServer>
+    version: u32,
+    encoding: Encoding,
+    server: Addr<GameServer>,
+    game: Option<Addr<Game>>,
+    outbox: Option<Outbox>,
 }
 
 impl Session {
     pub fn new(server_addr: Addr<GameServer>) -> Self {
         Self {
             id: None,
+            version: LEGACY_PROTOCOL_VERSION,
+            encoding: Encoding::Json,
             hb: Instant::now(),
             server: server_addr,
+            game: None,
+            outbox: None,
         }
     }
 
     fn heartbeat(&self, ctx: &mut ws::WebsocketContext<Self>) {
         ctx.run_interval(HEARTBEAT_INTERVAL, |act, ctx| {
             if Instant::now().duration_since(act.hb) > CLIENT_TIMEOUT {
-                act.error(ctx, "Heartbeat failed");
-
-                if let Some(id) = act.id {
-                    act.server.do_send(Disconnect { id });
-                }
-
+                act.error(ctx, ErrorCode::HeartbeatTimeout, None);
                 ctx.stop();
                 return;
             }
 
+            act.flush(ctx);
             ctx.ping(b"");
         });
     }
 
+    fn flush(&mut self, ctx: &mut ws::WebsocketContext<Self>) {
+        let outbox = match &self.outbox {
+            Some(outbox) => outbox.clone(),
+            None => return,
+        };
+
+        if outbox.is_closed() {
+            println!("Disconnecting slow client {:?}", self.id);
+            self.error(ctx, ErrorCode::SlowClient, None);
+            ctx.stop();
+            return;
+        }
+
+        for event in outbox.drain() {
+            let payload = event.encode(self.version, self.encoding);
+            self.send_payload(ctx, event.event(), payload);
+        }
+    }
+
+    // once in a game, events go straight to the game's actor
     fn send_server(
         &mut self,
         ctx: &mut ws::WebsocketContext<Self>,
         event: ClientEvent,
+        request_id: Option<u32>,
     ) {
-        if let Some(id) = self.id {
-            self.send_message_server(ctx, ClientMessage { sender: id, event }, |act, ctx, res| {
-                if let Some(res) = res {
-                    // forward message to client, if any
-                    act.send_client(ctx, res);
-                }
-            })
-        } else {
-            self.error(ctx, "Not connected");
+        let id = match self.id {
+            Some(id) => id,
+            None => return self.error(ctx, ErrorCode::NotConnected, request_id),
+        };
+
+        let message = ClientMessage { sender: id, event };
+        let handler = move |act: &mut Self, ctx: &mut ws::WebsocketContext<Self>, res| {
+            act.respond(ctx, res, request_id);
+        };
+
+        match self.game.clone() {
+            Some(game) => self.send_message(ctx, game, message, request_id, handler),
+            None => self.send_message(ctx, self.server.clone(), message, request_id, handler),
         }
     }
 
-    fn connect(&mut self, ctx: &mut ws::WebsocketContext<Self>, name: String) {
-        if let Some(_) = self.id {
-            self.error(ctx, "Already connected");
+    fn create_game(&mut self, ctx: &mut ws::WebsocketContext<Self>, settings: NewGame, request_id: Option<u32>) {
+        if self.game.is_some() {
+            return self.error(ctx, ErrorCode::AlreadyInGame, request_id);
         }
 
-        self.send_message_server(ctx, 
-            Connect { addr: ctx.address().recipient(), name }, 
-            |act, ctx, res| {
-                act.id = Some(res);
-                act.send_client(ctx, ServerEvent::Connected { id: res });
+        self.send_message(ctx, self.server.clone(), settings, request_id, move |act, ctx, res| {
+            match res {
+                Ok((game, event)) => {
+                    act.game = Some(game);
+                    act.respond(ctx, Some(event), request_id);
+                },
+                Err(error) => act.respond(ctx, Some(error), request_id),
+            }
+        });
+    }
+
+    fn join_game(&mut self, ctx: &mut ws::WebsocketContext<Self>, game: u16, request_id: Option<u32>) {
+        let player = match self.id {
+            Some(id) => id,
+            None => return self.error(ctx, ErrorCode::NotConnected, request_id),
+        };
+
+        if self.game.is_some() {
+            return self.error(ctx, ErrorCode::AlreadyInGame, request_id);
+        }
+
+        self.send_message(ctx, self.server.clone(), LookupGame { player, game }, request_id, move |act, ctx, res| {
+            let (game, info) = match res {
+                Ok(value) => value,
+                Err(error) => return act.respond(ctx, Some(error), request_id),
+            };
+
+            act.send_message(ctx, game.clone(), Join { id: player, connection: info }, request_id, move |act, ctx, res| {
+                if let Some(ServerEvent::JoinedGame { .. }) = res {
+                    act.game = Some(game);
+                }
+
+                act.respond(ctx, res, request_id);
+            });
+        });
+    }
+
+    fn connect(&mut self, ctx: &mut ws::WebsocketContext<Self>, event: ClientEvent, request_id: Option<u32>) {
+        let (name, version, encoding, score_deltas, resume) = match event {
+            ClientEvent::Connect { name, version, encoding, score_deltas, resume } => (name, version, encoding, score_deltas, resume),
+            _ => return,
+        };
+
+        if self.id.is_some() {
+            self.error(ctx, ErrorCode::AlreadyConnected, request_id);
+            return;
+        }
+
+        if !protocol::is_supported(version) {
+            println!("Rejected client with protocol version {}", version);
+
+            self.version = PROTOCOL_VERSION;
+            self.send_client(ctx, ServerEvent::IncompatibleProtocol {
+                min_version: MIN_PROTOCOL_VERSION,
+                max_version: PROTOCOL_VERSION,
+            });
+
+            ctx.close(Some(ws::CloseCode::Protocol.into()));
+            ctx.stop();
+            return;
+        }
+
+        self.version = version;
+        self.encoding = encoding;
+
+        let outbox = Outbox::new(ctx.address().recipient());
+        self.outbox = Some(outbox.clone());
+
+        self.send_message(ctx, self.server.clone(),
+            Connect { outbox, name, score_deltas, resume },
+            request_id,
+            move |act, ctx, (id, token, game)| {
+                act.id = Some(id);
+                act.game = game;
+
+                let event = ServerEvent::Connected {
+                    id,
+                    version: act.version,
+                    min_version: MIN_PROTOCOL_VERSION,
+                    max_version: PROTOCOL_VERSION,
+                    token,
+                };
+
+                act.respond(ctx, Some(event), request_id);
             }
         );
     }
 
-    fn send_message_server<F, M>(
+    fn send_message<A, F, M>(
         &mut self,
         ctx: &mut ws::WebsocketContext<Self>,
+        addr: Addr<A>,
         message: M,
+        request_id: Option<u32>,
         handler: F
-    ) where 
+    ) where
+        A: Handler<M>,
+        A::Context: ToEnvelope<A, M>,
         F: FnOnce(&mut Self, &mut ws::WebsocketContext<Self>, M::Result) + 'static,
         M: Message + 'static + Send,
         M::Result: Send,
-        GameServer: Handler<M>,
     {
-        self.server
+        addr
             .send(message)
             .into_actor(self)
             .then(move |res, act, ctx| {
@@ -92,8 +206,16 @@ impl Session {
                     Ok(res) => handler(act, ctx, res),
                     Err(err) => {
                         match err {
-                            MailboxError::Closed => act.error(ctx, "Server closed"),
-                            MailboxError::Timeout => act.error(ctx, "Server timed out")
+                            // a game's actor is gone once it closes, or if it died
+                            MailboxError::Closed if act.game.take().is_some() => {
+                                if let Some(player) = act.id {
+                                    act.server.do_send(GameGone { player });
+                                }
+
+                                act.error(ctx, ErrorCode::GameNotFound, request_id)
+                            },
+                            MailboxError::Closed => act.error(ctx, ErrorCode::ServerUnavailable, request_id),
+                            MailboxError::Timeout => act.error(ctx, ErrorCode::ServerTimeout, request_id)
                         }
                     }
                 }
@@ -103,12 +225,60 @@ impl Session {
             .wait(ctx);
     }
 
-    fn error(&self, ctx: &mut ws::WebsocketContext<Self>, msg: &str) {
-        self.send_client(ctx, ServerEvent::Error { message: msg.to_string() });
+    // Requests that carry an id always get exactly one Ack or Error back,
+    // after the event the server replied with, if any.
+    fn respond(&mut self, ctx: &mut ws::WebsocketContext<Self>, response: Option<ServerEvent>, request_id: Option<u32>) {
+        match response {
+            Some(ServerEvent::Error { code, message, details, .. }) => {
+                self.send_client(ctx, ServerEvent::Error { code, message, details, request_id });
+            },
+            Some(event) => {
+                self.send_client(ctx, event);
+                self.ack(ctx, request_id);
+            },
+            None => self.ack(ctx, request_id),
+        }
+    }
+
+    fn ack(&mut self, ctx: &mut ws::WebsocketContext<Self>, request_id: Option<u32>) {
+        if let Some(request_id) = request_id {
+            self.send_client(ctx, ServerEvent::Ack { request_id });
+        }
+    }
+
+    fn error(&mut self, ctx: &mut ws::WebsocketContext<Self>, code: ErrorCode, request_id: Option<u32>) {
+        self.respond(ctx, Some(ServerEvent::error(code)), request_id);
+    }
+
+    fn send_client(&mut self, ctx: &mut ws::WebsocketContext<Self>, event: ServerEvent) {
+        let payload = protocol::encode(&event, self.version, self.encoding);
+        self.send_payload(ctx, &event, payload);
+    }
+
+    fn send_payload(&mut self, ctx: &mut ws::WebsocketContext<Self>, event: &ServerEvent, payload: Option<Payload>) {
+        if let ServerEvent::LeftGame = event {
+            self.game = None;
+        }
+
+        match payload {
+            Some(Payload::Text(text)) => ctx.text(text),
+            Some(Payload::Binary(bytes)) => ctx.binary(bytes),
+            None => (),
+        }
     }
 
-    fn send_client(&self, ctx: &mut ws::WebsocketContext<Self>, event: ServerEvent) {
-        ctx.text(event.to_string());
+    fn handle_request(&mut self, ctx: &mut ws::WebsocketContext<Self>, request: ClientRequest) {
+        let ClientRequest { event, request_id } = request;
+
+        match event {
+            event @ ClientEvent::Connect { .. } => self.connect(ctx, event, request_id),
+            ClientEvent::CreateGame { x, y, minutes, tag_mode, power_ups, radius } => match self.id {
+                Some(player) => self.create_game(ctx, NewGame { player, x, y, minutes, tag_mode, power_ups, radius }, request_id),
+                None => self.error(ctx, ErrorCode::NotConnected, request_id),
+            },
+            ClientEvent::JoinGame { game } => self.join_game(ctx, game, request_id),
+            event => self.send_server(ctx, event, request_id),
+        }
     }
 }
 
@@ -120,19 +290,20 @@ impl Actor for Session {
     }
 
     fn stopping(&mut self, _: &mut Self::Context) -> Running {
-        if let Some(id) = self.id {
-            self.server.do_send(Disconnect { id });
+        // the server keeps the player's game for them in case they resume
+        if let (Some(id), Some(outbox)) = (self.id, self.outbox.clone()) {
+            self.server.do_send(Disconnect { id, outbox });
         }
 
         Running::Stop
     }
 }
 
-impl Handler<ServerMessage> for Session {
+impl Handler<Flush> for Session {
     type Result = ();
 
-    fn handle(&mut self, msg: ServerMessage, ctx: &mut Self::Context) -> Self::Result {
-        self.send_client(ctx, msg.event);
+    fn handle(&mut self, _: Flush, ctx: &mut Self::Context) -> Self::Result {
+        self.flush(ctx);
     }
 }
 
@@ -156,21 +327,26 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for Session {
                 self.hb = Instant::now();
             },
             ws::Message::Text(text) => {
-                if let Ok(event) = serde_json::from_str(&text) {
-                    if let ClientEvent::Connect { name } = event {
-                        self.connect(ctx, name);
-                    } else {
-                        self.send_server(ctx, event);
-                    }
+                if let Ok(request) = ClientRequest::from_json(&text) {
+                    self.handle_request(ctx, request);
+                } else {
+                    println!("Invalid event: {:?}", text);
+                    self.error(ctx, ErrorCode::InvalidMessage, None);
+                }
+            },
+            ws::Message::Binary(bytes) => {
+                if let Ok(request) = ClientRequest::from_msgpack(&bytes) {
+                    self.handle_request(ctx, request);
                 } else {
-                    self.error(ctx, format!("Invalid event: {:?}", text).as_str());
+                    println!("Invalid binary event of {} bytes", bytes.len());
+                    self.error(ctx, ErrorCode::InvalidMessage, None);
                 }
             },
             ws::Message::Close(reason) => {
                 ctx.close(reason);
                 ctx.stop();
             },
-            _ => self.error(ctx, format!("Invalid message: {:?}", message).as_str())
+            _ => self.error(ctx, ErrorCode::InvalidMessage, None)
         }
     }
 }
\ No newline at end of file
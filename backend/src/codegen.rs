@@ -0,0 +1,568 @@
+use std::fmt::Write;
+
+use serde_json::Value;
+
+use crate::schema::protocol_schema;
+
+pub const SCHEMA_FILE: &str = "schema/protocol.json";
+pub const TYPESCRIPT_FILE: &str = "schema/protocol.d.ts";
+pub const DART_FILE: &str = "../hide_and_seek/lib/protocol.g.dart";
+
+const HEADER: &str = "// GENERATED CODE - DO NOT EDIT.\n// Run `cargo run --bin codegen` in backend/ to regenerate.\n";
+
+// Paths are relative to the backend crate root.
+pub fn outputs() -> Vec<(&'static str, String)> {
+    let schema = protocol_schema();
+    let definitions = parse_definitions(&schema);
+
+    vec![
+        (SCHEMA_FILE, serde_json::to_string_pretty(&schema).unwrap() + "\n"),
+        (TYPESCRIPT_FILE, typescript(&definitions)),
+        (DART_FILE, dart(&definitions)),
+    ]
+}
+
+enum Type {
+    Int,
+    Float,
+    Bool,
+    String,
+    List(Box<Type>),
+    Tuple(Vec<Type>),
+    Map(Box<Type>),
+    Named(String),
+    Optional(Box<Type>),
+}
+
+struct Field {
+    name: String,
+    ty: Type,
+    default: Option<Value>,
+}
+
+enum Variant {
+    Unit(String),
+    Struct(String, Vec<Field>),
+    Newtype(String, Type),
+}
+
+enum Definition {
+    Enum(Vec<Variant>),
+    Struct(Vec<Field>),
+    // the fields sit next to the named enum's tag in the same object
+    Envelope(String, Vec<Field>),
+}
+
+fn parse_definitions(schema: &Value) -> Vec<(String, Definition)> {
+    schema["definitions"]
+        .as_object()
+        .expect("schema has no definitions")
+        .iter()
+        .map(|(name, schema)| (name.clone(), parse_definition(schema)))
+        .collect()
+}
+
+fn parse_definition(schema: &Value) -> Definition {
+    if let Some([inner, fields]) = schema["allOf"].as_array().map(Vec::as_slice) {
+        let Type::Named(inner) = parse_type(inner) else { panic!("envelope around a type without a name") };
+        return Definition::Envelope(inner, parse_fields(fields));
+    }
+
+    if let Some(names) = schema["enum"].as_array() {
+        return Definition::Enum(names.iter().map(|name| Variant::Unit(as_str(name))).collect());
+    }
+
+    match schema["oneOf"].as_array() {
+        Some(alternatives) => Definition::Enum(alternatives.iter().flat_map(parse_variants).collect()),
+        None => Definition::Struct(parse_fields(schema)),
+    }
+}
+
+// externally tagged: unit variants are strings, the rest single-key objects
+fn parse_variants(schema: &Value) -> Vec<Variant> {
+    if let Some(names) = schema["enum"].as_array() {
+        return names.iter().map(|name| Variant::Unit(as_str(name))).collect();
+    }
+
+    let (name, content) = schema["properties"]
+        .as_object()
+        .and_then(|properties| properties.iter().next())
+        .expect("enum variant has no content");
+
+    let variant = match content.get("properties") {
+        Some(_) => Variant::Struct(name.clone(), parse_fields(content)),
+        None => Variant::Newtype(name.clone(), parse_type(content)),
+    };
+
+    vec![variant]
+}
+
+fn parse_fields(schema: &Value) -> Vec<Field> {
+    let required: Vec<&str> = schema["required"]
+        .as_array()
+        .map(|names| names.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default();
+
+    schema["properties"]
+        .as_object()
+        .map(|properties| properties.iter().map(|(name, schema)| {
+            let ty = parse_type(schema);
+            let default = match required.contains(&name.as_str()) {
+                true => None,
+                false => Some(schema.get("default").cloned().unwrap_or(Value::Null)),
+            };
+
+            Field { name: name.clone(), ty, default }
+        }).collect())
+        .unwrap_or_default()
+}
+
+fn parse_type(schema: &Value) -> Type {
+    if let Some(reference) = schema["$ref"].as_str() {
+        return Type::Named(reference.rsplit('/').next().unwrap().to_string());
+    }
+
+    if let Some(alternatives) = schema["anyOf"].as_array() {
+        let inner = alternatives.iter().find(|alt| alt["type"] != "null").expect("anyOf without a value type");
+        return Type::Optional(Box::new(parse_type(inner)));
+    }
+
+    if let Some(types) = schema["type"].as_array() {
+        let inner = types.iter().find(|ty| *ty != "null").expect("nullable type without a value type");
+        let mut schema = schema.clone();
+        schema["type"] = inner.clone();
+        return Type::Optional(Box::new(parse_type(&schema)));
+    }
+
+    match schema["type"].as_str() {
+        Some("integer") => Type::Int,
+        Some("number") => Type::Float,
+        Some("boolean") => Type::Bool,
+        Some("string") => Type::String,
+        Some("array") => match &schema["items"] {
+            Value::Array(items) => Type::Tuple(items.iter().map(parse_type).collect()),
+            items => Type::List(Box::new(parse_type(items))),
+        },
+        Some("object") => Type::Map(Box::new(parse_type(&schema["additionalProperties"]))),
+        other => panic!("unsupported schema type {:?}", other),
+    }
+}
+
+fn as_str(value: &Value) -> String {
+    value.as_str().expect("expected a string").to_string()
+}
+
+// TypeScript
+
+fn typescript(definitions: &[(String, Definition)]) -> String {
+    let mut out = String::from(HEADER);
+
+    for (name, definition) in definitions {
+        out.push('\n');
+
+        match definition {
+            Definition::Struct(fields) => {
+                writeln!(out, "export interface {} {}", name, ts_object(fields)).unwrap();
+            },
+            Definition::Envelope(inner, fields) => {
+                let mut alternatives = vec![format!("Exclude<{}, string>", inner)];
+
+                if let Some((_, Definition::Enum(variants))) = definitions.iter().find(|(other, _)| other == inner) {
+                    for variant in variants {
+                        if let Variant::Unit(variant) = variant {
+                            alternatives.push(format!("{{ {}: null }}", variant));
+                        }
+                    }
+                }
+
+                writeln!(out, "export type {} = ({}) & {};", name, alternatives.join(" | "), ts_object(fields)).unwrap();
+            },
+            Definition::Enum(variants) => {
+                writeln!(out, "export type {} =", name).unwrap();
+
+                for variant in variants {
+                    let ty = match variant {
+                        Variant::Unit(name) => format!("\"{}\"", name),
+                        Variant::Struct(name, fields) => format!("{{ {}: {} }}", name, ts_object(fields)),
+                        Variant::Newtype(name, ty) => format!("{{ {}: {} }}", name, ts_type(ty)),
+                    };
+
+                    writeln!(out, "  | {}", ty).unwrap();
+                }
+
+                out.insert(out.len() - 1, ';');
+            },
+        }
+    }
+
+    out
+}
+
+fn ts_object(fields: &[Field]) -> String {
+    if fields.is_empty() {
+        return "{}".to_string();
+    }
+
+    let fields: Vec<String> = fields.iter().map(|field| {
+        let optional = if field.default.is_some() { "?" } else { "" };
+        format!("{}{}: {}", field.name, optional, ts_type(&field.ty))
+    }).collect();
+
+    format!("{{ {} }}", fields.join("; "))
+}
+
+fn ts_type(ty: &Type) -> String {
+    match ty {
+        Type::Int | Type::Float => "number".to_string(),
+        Type::Bool => "boolean".to_string(),
+        Type::String => "string".to_string(),
+        Type::List(inner) => format!("{}[]", ts_type(inner)),
+        Type::Tuple(items) => format!("[{}]", items.iter().map(ts_type).collect::<Vec<_>>().join(", ")),
+        Type::Map(inner) => format!("Record<string, {}>", ts_type(inner)),
+        Type::Named(name) => name.clone(),
+        Type::Optional(inner) => format!("{} | null", ts_type(inner)),
+    }
+}
+
+// Dart
+
+fn dart(definitions: &[(String, Definition)]) -> String {
+    let mut out = String::from(HEADER);
+    out.push_str("// ignore_for_file: constant_identifier_names\n");
+
+    for (name, definition) in definitions {
+        out.push('\n');
+
+        match definition {
+            Definition::Struct(fields) => dart_class(&mut out, name, None, fields, None, definitions),
+            Definition::Envelope(inner, fields) => dart_envelope(&mut out, name, inner, fields),
+            Definition::Enum(variants) if variants.iter().all(|v| matches!(v, Variant::Unit(_))) => {
+                dart_enum(&mut out, name, variants);
+            },
+            Definition::Enum(variants) => dart_sealed(&mut out, name, variants, definitions),
+        }
+    }
+
+    out
+}
+
+fn dart_enum(out: &mut String, name: &str, variants: &[Variant]) {
+    let names: Vec<&str> = variants.iter().map(|variant| match variant {
+        Variant::Unit(name) => name.as_str(),
+        _ => unreachable!(),
+    }).collect();
+
+    writeln!(out, "enum {} {{", name).unwrap();
+    writeln!(out, "  {};", names.join(",\n  ")).unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "  static {} fromJson(dynamic json) => values.byName(json as String);", name).unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "  dynamic toJson() => name;").unwrap();
+    writeln!(out, "}}").unwrap();
+}
+
+fn dart_sealed(out: &mut String, name: &str, variants: &[Variant], definitions: &[(String, Definition)]) {
+    writeln!(out, "sealed class {} {{", name).unwrap();
+    writeln!(out, "  const {}();", name).unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "  static {} fromJson(dynamic json) {{", name).unwrap();
+    writeln!(out, "    if (json is String) {{").unwrap();
+    writeln!(out, "      switch (json) {{").unwrap();
+
+    for variant in variants {
+        if let Variant::Unit(variant) = variant {
+            writeln!(out, "        case '{}': return const {}{}();", variant, name, variant).unwrap();
+        }
+    }
+
+    writeln!(out, "      }}").unwrap();
+    writeln!(out, "    }} else if (json is Map<String, dynamic> && json.length == 1) {{").unwrap();
+    writeln!(out, "      final MapEntry(:key, :value) = json.entries.first;").unwrap();
+    writeln!(out, "      switch (key) {{").unwrap();
+
+    for variant in variants {
+        match variant {
+            // unit variants may also be sent as {"Variant": null}
+            Variant::Unit(variant) => {
+                writeln!(out, "        case '{}': return const {}{}();", variant, name, variant).unwrap();
+            },
+            Variant::Struct(variant, _) => {
+                writeln!(out, "        case '{}': return {}{}.fromJson(value as Map<String, dynamic>);", variant, name, variant).unwrap();
+            },
+            Variant::Newtype(variant, ty) => {
+                writeln!(out, "        case '{}': return {}{}({});", variant, name, variant, dart_from_json(ty, "value", 0)).unwrap();
+            },
+        }
+    }
+
+    writeln!(out, "      }}").unwrap();
+    writeln!(out, "    }}").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "    throw ArgumentError('Unknown {}: $json');", name).unwrap();
+    writeln!(out, "  }}").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "  dynamic toJson();").unwrap();
+    writeln!(out, "}}").unwrap();
+
+    for variant in variants {
+        out.push('\n');
+
+        match variant {
+            Variant::Unit(variant) => {
+                let class = format!("{}{}", name, variant);
+                writeln!(out, "class {} extends {} {{", class, name).unwrap();
+                writeln!(out, "  const {}();", class).unwrap();
+                writeln!(out).unwrap();
+                writeln!(out, "  @override").unwrap();
+                writeln!(out, "  dynamic toJson() => '{}';", variant).unwrap();
+                writeln!(out, "}}").unwrap();
+            },
+            Variant::Struct(variant, fields) => {
+                dart_class(out, &format!("{}{}", name, variant), Some(name), fields, Some(variant), definitions);
+            },
+            Variant::Newtype(variant, ty) => {
+                let class = format!("{}{}", name, variant);
+                writeln!(out, "class {} extends {} {{", class, name).unwrap();
+                writeln!(out, "  final {} value;", dart_type(ty)).unwrap();
+                writeln!(out).unwrap();
+                writeln!(out, "  const {}(this.value);", class).unwrap();
+                writeln!(out).unwrap();
+                writeln!(out, "  @override").unwrap();
+                writeln!(out, "  dynamic toJson() => {{'{}': {}}};", variant, dart_to_json(ty, "value", 0)).unwrap();
+                writeln!(out, "}}").unwrap();
+            },
+        }
+    }
+}
+
+fn dart_envelope(out: &mut String, class: &str, inner: &str, fields: &[Field]) {
+    writeln!(out, "class {} {{", class).unwrap();
+    writeln!(out, "  final {} event;", inner).unwrap();
+
+    for field in fields {
+        writeln!(out, "  final {} {};", dart_type(&field.ty), camel_case(&field.name)).unwrap();
+    }
+
+    let params: Vec<String> = fields.iter().map(|field| match field.default {
+        Some(_) => format!("this.{}", camel_case(&field.name)),
+        None => format!("required this.{}", camel_case(&field.name)),
+    }).collect();
+    writeln!(out).unwrap();
+    writeln!(out, "  const {}(this.event, {{{}}});", class, params.join(", ")).unwrap();
+    writeln!(out).unwrap();
+
+    writeln!(out, "  factory {}.fromJson(dynamic json) {{", class).unwrap();
+    writeln!(out, "    if (json is! Map<String, dynamic>) return {}({}.fromJson(json));", class, inner).unwrap();
+    writeln!(out).unwrap();
+
+    let names: Vec<String> = fields.iter().map(|field| format!("'{}'", field.name)).collect();
+    writeln!(out, "    final event = Map.of(json)..removeWhere((key, _) => [{}].contains(key));", names.join(", ")).unwrap();
+    writeln!(out, "    return {}(", class).unwrap();
+    writeln!(out, "      {}.fromJson(event),", inner).unwrap();
+
+    for field in fields {
+        let value = format!("json['{}']", field.name);
+        writeln!(out, "      {}: {},", camel_case(&field.name), dart_from_json(&field.ty, &value, 0)).unwrap();
+    }
+
+    writeln!(out, "    );").unwrap();
+    writeln!(out, "  }}").unwrap();
+    writeln!(out).unwrap();
+
+    // unit variants are plain strings, which can't carry any fields
+    writeln!(out, "  dynamic toJson() {{").unwrap();
+    writeln!(out, "    final event = this.event.toJson();").unwrap();
+    writeln!(out, "    return <String, dynamic>{{").unwrap();
+    writeln!(out, "      ...(event is String ? {{event: null}} : event as Map<String, dynamic>),").unwrap();
+
+    for field in fields {
+        let name = camel_case(&field.name);
+        let condition = match field.default {
+            Some(_) => format!("if ({} != null) ", name),
+            None => String::new(),
+        };
+
+        writeln!(out, "      {}'{}': {},", condition, field.name, dart_to_json(&field.ty, &name, 0)).unwrap();
+    }
+
+    writeln!(out, "    }};").unwrap();
+    writeln!(out, "  }}").unwrap();
+    writeln!(out, "}}").unwrap();
+}
+
+fn dart_class(
+    out: &mut String,
+    class: &str,
+    parent: Option<&str>,
+    fields: &[Field],
+    tag: Option<&str>,
+    definitions: &[(String, Definition)],
+) {
+    match parent {
+        Some(parent) => writeln!(out, "class {} extends {} {{", class, parent).unwrap(),
+        None => writeln!(out, "class {} {{", class).unwrap(),
+    }
+
+    for field in fields {
+        writeln!(out, "  final {} {};", dart_type(&field.ty), camel_case(&field.name)).unwrap();
+    }
+
+    if !fields.is_empty() {
+        writeln!(out).unwrap();
+    }
+
+    let params: Vec<String> = fields.iter().map(|field| {
+        let name = camel_case(&field.name);
+        match (&field.default, &field.ty) {
+            (None, _) => format!("required this.{}", name),
+            (Some(Value::Null), _) => format!("this.{}", name),
+            (Some(default), ty) => format!("this.{} = {}", name, dart_default(ty, default, definitions)),
+        }
+    }).collect();
+
+    match params.is_empty() {
+        true => writeln!(out, "  const {}();", class).unwrap(),
+        false => writeln!(out, "  const {}({{{}}});", class, params.join(", ")).unwrap(),
+    }
+
+    writeln!(out).unwrap();
+    writeln!(out, "  factory {}.fromJson(Map<String, dynamic> json) => {}(", class, class).unwrap();
+
+    for field in fields {
+        let value = format!("json['{}']", field.name);
+        let expr = match &field.default {
+            Some(default) if !default.is_null() => format!(
+                "json.containsKey('{}') ? {} : {}",
+                field.name,
+                dart_from_json(&field.ty, &value, 0),
+                dart_default(&field.ty, default, definitions),
+            ),
+            _ => dart_from_json(&field.ty, &value, 0),
+        };
+
+        writeln!(out, "    {}: {},", camel_case(&field.name), expr).unwrap();
+    }
+
+    writeln!(out, "  );").unwrap();
+    writeln!(out).unwrap();
+
+    let entries: Vec<String> = fields.iter().map(|field| {
+        format!("'{}': {}", field.name, dart_to_json(&field.ty, &camel_case(&field.name), 0))
+    }).collect();
+
+    let object = format!("{{{}}}", entries.join(", "));
+
+    if parent.is_some() {
+        writeln!(out, "  @override").unwrap();
+    }
+
+    match tag {
+        Some(tag) => writeln!(out, "  dynamic toJson() => {{'{}': {}}};", tag, object).unwrap(),
+        None => writeln!(out, "  dynamic toJson() => {};", object).unwrap(),
+    }
+
+    writeln!(out, "}}").unwrap();
+}
+
+fn dart_type(ty: &Type) -> String {
+    match ty {
+        Type::Int => "int".to_string(),
+        Type::Float => "double".to_string(),
+        Type::Bool => "bool".to_string(),
+        Type::String => "String".to_string(),
+        Type::List(inner) => format!("List<{}>", dart_type(inner)),
+        Type::Tuple(items) => format!("({})", items.iter().map(dart_type).collect::<Vec<_>>().join(", ")),
+        Type::Map(inner) => format!("Map<String, {}>", dart_type(inner)),
+        Type::Named(name) => name.clone(),
+        Type::Optional(inner) => format!("{}?", dart_type(inner)),
+    }
+}
+
+fn dart_from_json(ty: &Type, value: &str, depth: usize) -> String {
+    let var = format!("e{}", depth);
+
+    match ty {
+        Type::Int => format!("({} as num).toInt()", value),
+        Type::Float => format!("({} as num).toDouble()", value),
+        Type::Bool => format!("{} as bool", value),
+        Type::String => format!("{} as String", value),
+        Type::List(inner) => format!(
+            "({} as List).map(({}) => {}).toList()",
+            value, var, dart_from_json(inner, &var, depth + 1),
+        ),
+        Type::Tuple(items) => {
+            let items: Vec<String> = items.iter().enumerate().map(|(i, item)| {
+                dart_from_json(item, &format!("({} as List)[{}]", value, i), depth + 1)
+            }).collect();
+
+            format!("({})", items.join(", "))
+        },
+        Type::Map(inner) => format!(
+            "({} as Map<String, dynamic>).map((k, {}) => MapEntry(k, {}))",
+            value, var, dart_from_json(inner, &var, depth + 1),
+        ),
+        Type::Named(name) => format!("{}.fromJson({})", name, value),
+        Type::Optional(inner) => format!("{} == null ? null : {}", value, dart_from_json(inner, value, depth)),
+    }
+}
+
+fn dart_to_json(ty: &Type, value: &str, depth: usize) -> String {
+    let var = format!("e{}", depth);
+
+    match ty {
+        Type::Int | Type::Float | Type::Bool | Type::String => value.to_string(),
+        Type::List(inner) if is_primitive(inner) => value.to_string(),
+        Type::List(inner) => format!("{}.map(({}) => {}).toList()", value, var, dart_to_json(inner, &var, depth + 1)),
+        Type::Tuple(items) => {
+            let items: Vec<String> = items.iter().enumerate().map(|(i, item)| {
+                dart_to_json(item, &format!("{}.${}", value, i + 1), depth + 1)
+            }).collect();
+
+            format!("[{}]", items.join(", "))
+        },
+        Type::Map(inner) if is_primitive(inner) => value.to_string(),
+        Type::Map(inner) => format!("{}.map((k, {}) => MapEntry(k, {}))", value, var, dart_to_json(inner, &var, depth + 1)),
+        Type::Named(_) => format!("{}.toJson()", value),
+        Type::Optional(inner) if is_primitive(inner) => value.to_string(),
+        Type::Optional(inner) => format!("{} == null ? null : {}", value, dart_to_json(inner, &format!("{}!", value), depth)),
+    }
+}
+
+// must be a const expression
+fn dart_default(ty: &Type, default: &Value, definitions: &[(String, Definition)]) -> String {
+    match (ty, default) {
+        (Type::Named(name), Value::String(variant)) => {
+            let definition = definitions.iter().find(|(other, _)| other == name).map(|(_, definition)| definition);
+
+            match definition {
+                Some(Definition::Enum(variants)) if variants.iter().all(|v| matches!(v, Variant::Unit(_))) => {
+                    format!("{}.{}", name, variant)
+                },
+                _ => format!("const {}{}()", name, variant),
+            }
+        },
+        (_, value) => value.to_string(),
+    }
+}
+
+fn is_primitive(ty: &Type) -> bool {
+    matches!(ty, Type::Int | Type::Float | Type::Bool | Type::String)
+}
+
+fn camel_case(name: &str) -> String {
+    let mut out = String::new();
+    let mut upper = false;
+
+    for c in name.chars() {
+        match c {
+            '_' => upper = true,
+            c if upper => {
+                out.extend(c.to_uppercase());
+                upper = false;
+            },
+            c => out.push(c),
+        }
+    }
+
+    out
+}
@@ -0,0 +1,195 @@
+use std::{collections::HashMap, sync::Arc};
+use actix::prelude::*;
+
+use hide_and_seek_core::game::{self as rules, Command, Effect, Player, UPDATE_INTERVAL};
+
+use crate::clock::Clock;
+use crate::message::*;
+use crate::outbox::Outbox;
+use crate::protocol::SharedEvent;
+use crate::replay::{RecordedCommand, Recorder};
+use crate::server::GameServer;
+use crate::tracks::{GameTracks, TrackStore, WallClock};
+
+// A connected player as the server sees them
+#[derive(Clone)]
+pub struct Connection {
+    pub outbox: Outbox,
+    pub player: Player,
+}
+
+// Runs the rules of one game and delivers whatever they produce to the
+// players' outboxes and the server.
+pub struct Game {
+    id: u16,
+    server: Addr<GameServer>,
+    clock: Clock,
+    outboxes: HashMap<i64, Outbox>,
+    rules: rules::Game,
+    recorder: Option<Recorder>,
+    tracks: Option<TrackStore>,
+    download_token: Option<String>,
+    wall_clock: WallClock,
+}
+
+impl Actor for Game {
+    type Context = Context<Self>;
+
+    // with a manual clock the ticks come from whoever advances it
+    fn started(&mut self, ctx: &mut Self::Context) {
+        if !self.clock.is_manual() {
+            ctx.run_interval(UPDATE_INTERVAL, |act, ctx| act.tick(ctx));
+        }
+    }
+}
+
+impl Game {
+    pub fn new(
+        server: Addr<GameServer>,
+        clock: Clock,
+        rules: rules::Game,
+        host: i64,
+        outbox: Outbox,
+        recorder: Option<Recorder>,
+        tracks: Option<TrackStore>,
+    ) -> Self {
+        Self {
+            id: rules.id(),
+            wall_clock: WallClock::new(clock.now()),
+            server, clock, rules, recorder, tracks,
+            download_token: None,
+            outboxes: HashMap::from([(host, outbox)]),
+        }
+    }
+
+    pub fn with_download_token(mut self, token: String) -> Self {
+        self.download_token = Some(token);
+        self
+    }
+
+    fn handle_command(&mut self, ctx: &mut Context<Self>, command: Command) -> Option<ServerEvent> {
+        let now = self.clock.now();
+        let recorded = self.recorder.is_some().then(|| RecordedCommand::new(&command, now));
+        let outcome = self.rules.handle(now, command);
+
+        let failed = match (&mut self.recorder, recorded) {
+            (Some(recorder), Some(command)) => recorder.record(now, command, &outcome).err(),
+            _ => None,
+        };
+
+        if let Some(err) = failed {
+            println!("Stopped recording game {}: {}", self.id, err);
+            self.recorder = None;
+        }
+
+        for effect in outcome.effects {
+            self.apply(ctx, effect);
+        }
+
+        outcome.reply
+    }
+
+    fn apply(&mut self, ctx: &mut Context<Self>, effect: Effect) {
+        match effect {
+            Effect::Send { to, mut event } => {
+                if let ServerEvent::GameEnded { download_token, .. } = &mut event {
+                    download_token.clone_from(&self.download_token);
+                }
+
+                let event = Arc::new(SharedEvent::new(event));
+
+                for id in to {
+                    if let Some(outbox) = self.outboxes.get(&id) {
+                        outbox.push(event.clone());
+                    }
+                }
+            },
+            Effect::Joined(player) => {
+                self.server.do_send(PlayerGame { player, game: Some(self.id) });
+            },
+            Effect::Left(player) => {
+                self.outboxes.remove(&player);
+                self.server.do_send(PlayerGame { player, game: None });
+            },
+            Effect::Ended => {
+                if let (Some(tracks), Some(token)) = (&self.tracks, &self.download_token) {
+                    if let Err(err) = tracks.save(GameTracks::new(&self.rules, self.wall_clock), token) {
+                        println!("Failed to save tracks of game {}: {}", self.id, err);
+                    }
+                }
+            },
+            Effect::Closed { players } => {
+                self.server.do_send(GameClosed { id: self.id, players });
+                ctx.stop();
+            },
+        }
+    }
+
+    fn tick(&mut self, ctx: &mut Context<Self>) {
+        self.drop_slow_players(ctx);
+        self.handle_command(ctx, Command::Tick);
+    }
+
+    fn drop_slow_players(&mut self, ctx: &mut Context<Self>) {
+        let slow: Vec<i64> = self.outboxes.iter()
+            .filter(|(_, outbox)| outbox.is_closed())
+            .map(|(id, _)| *id)
+            .collect();
+
+        for id in slow {
+            println!("Dropping {} from game {}: connection too slow", id, self.id);
+            self.handle_command(ctx, Command::Leave { id });
+        }
+    }
+}
+
+impl Handler<ClientMessage> for Game {
+    type Result = MessageResult<ClientMessage>;
+
+    fn handle(&mut self, msg: ClientMessage, ctx: &mut Context<Self>) -> Self::Result {
+        MessageResult(self.handle_command(ctx, Command::Client { sender: msg.sender, event: msg.event }))
+    }
+}
+
+impl Handler<Tick> for Game {
+    type Result = ();
+
+    fn handle(&mut self, _: Tick, ctx: &mut Context<Self>) -> Self::Result {
+        self.tick(ctx);
+    }
+}
+
+impl Handler<Join> for Game {
+    type Result = MessageResult<Join>;
+
+    fn handle(&mut self, msg: Join, ctx: &mut Context<Self>) -> Self::Result {
+        let Connection { outbox, player } = msg.connection;
+        let response = self.handle_command(ctx, Command::Join { id: msg.id, player });
+
+        if let Some(ServerEvent::JoinedGame { .. }) = response {
+            self.outboxes.insert(msg.id, outbox);
+        }
+
+        MessageResult(response)
+    }
+}
+
+impl Handler<Resume> for Game {
+    type Result = ();
+
+    fn handle(&mut self, msg: Resume, ctx: &mut Context<Self>) -> Self::Result {
+        // the player may have been dropped from the game in the meantime
+        let old = match self.outboxes.get(&msg.id) {
+            Some(outbox) => outbox.clone(),
+            None => return,
+        };
+
+        // whatever the old connection didn't get to send goes out on the new one
+        for event in old.drain() {
+            msg.outbox.push(event);
+        }
+
+        self.outboxes.insert(msg.id, msg.outbox);
+        self.handle_command(ctx, Command::Resume { id: msg.id });
+    }
+}
@@ -0,0 +1,141 @@
+use std::collections::{BTreeMap, HashSet};
+
+use actix_web::{get, web, HttpResponse};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::tracks::{Bounds, GameTracks, TrackStore};
+
+const METRES_PER_DEGREE: f64 = 111_320.0;
+const MAX_RADIUS: f64 = 5000.0;
+const MIN_CELL_SIZE: f64 = 5.0;
+const MAX_CELLS_PER_SIDE: f64 = 200.0;
+// Cells fewer players and games went through don't show up at all. Player
+// ids only last as long as a connection, so somebody who played several games
+// counts once in each of them: only playing somewhere again and again could
+// make their own paths show.
+pub const MIN_DISTINCT_PLAYERS: usize = 5;
+pub const MIN_DISTINCT_GAMES: usize = 3;
+
+// A square of cells `cell` metres wide reaching `radius` metres from the
+// center in every direction, flat enough at these sizes.
+pub struct Grid {
+    pub x: f64,
+    pub y: f64,
+    pub radius: f64,
+    pub cell: f64,
+}
+
+#[derive(Default)]
+struct Cell {
+    visits: u32,
+    tags: u32,
+    players: HashSet<(usize, i64)>,
+    games: HashSet<usize>,
+}
+
+impl Grid {
+    fn metres_per_degree_x(&self) -> f64 {
+        METRES_PER_DEGREE * self.y.to_radians().cos()
+    }
+
+    fn cell_of(&self, x: f64, y: f64) -> Option<(i64, i64)> {
+        let dx = (x - self.x) * self.metres_per_degree_x();
+        let dy = (y - self.y) * METRES_PER_DEGREE;
+
+        if dx.abs() > self.radius || dy.abs() > self.radius {
+            return None;
+        }
+
+        Some(((dx / self.cell).floor() as i64, (dy / self.cell).floor() as i64))
+    }
+
+    pub fn bounds(&self) -> Bounds {
+        let dx = self.radius / self.metres_per_degree_x();
+        let dy = self.radius / METRES_PER_DEGREE;
+
+        Bounds { west: self.x - dx, south: self.y - dy, east: self.x + dx, north: self.y + dy }
+    }
+
+    fn polygon(&self, (column, row): (i64, i64)) -> Vec<[f64; 2]> {
+        let corner = |column: i64, row: i64| [
+            self.x + (column as f64 * self.cell) / self.metres_per_degree_x(),
+            self.y + (row as f64 * self.cell) / METRES_PER_DEGREE,
+        ];
+
+        vec![corner(column, row), corner(column + 1, row), corner(column + 1, row + 1), corner(column, row + 1), corner(column, row)]
+    }
+
+    // Visits count how often a player entered a cell, not how long they
+    // stayed in it.
+    pub fn heatmap<'a>(&self, games: impl IntoIterator<Item = &'a GameTracks>) -> Value {
+        let mut cells: BTreeMap<(i64, i64), Cell> = BTreeMap::new();
+
+        for (index, game) in games.into_iter().enumerate() {
+            for track in &game.players {
+                let mut last = None;
+
+                for point in &track.points {
+                    let cell = self.cell_of(point.x, point.y);
+
+                    if let Some(cell) = cell.filter(|_| cell != last) {
+                        let cell = cells.entry(cell).or_default();
+                        cell.visits += 1;
+                        cell.players.insert((index, track.id));
+                        cell.games.insert(index);
+                    }
+
+                    last = cell;
+                }
+            }
+
+            for tag in &game.tags {
+                if let Some(cell) = tag.pos.and_then(|(x, y)| self.cell_of(x, y)) {
+                    cells.entry(cell).or_default().tags += 1;
+                }
+            }
+        }
+
+        let features: Vec<Value> = cells.iter()
+            .filter(|(_, cell)| cell.players.len() >= MIN_DISTINCT_PLAYERS && cell.games.len() >= MIN_DISTINCT_GAMES)
+            .map(|(&position, cell)| json!({
+                "type": "Feature",
+                "geometry": { "type": "Polygon", "coordinates": [self.polygon(position)] },
+                "properties": { "visits": cell.visits, "tags": cell.tags },
+            }))
+            .collect();
+
+        json!({ "type": "FeatureCollection", "features": features })
+    }
+}
+
+fn default_radius() -> f64 {
+    500.0
+}
+
+fn default_cell() -> f64 {
+    25.0
+}
+
+#[derive(Deserialize)]
+struct HeatmapQuery {
+    x: f64,
+    y: f64,
+    #[serde(default = "default_radius")] radius: f64,
+    #[serde(default = "default_cell")] cell: f64,
+}
+
+#[get("/heatmap")]
+async fn heatmap(query: web::Query<HeatmapQuery>, tracks: web::Data<TrackStore>) -> actix_web::Result<HttpResponse> {
+    let HeatmapQuery { x, y, radius, cell } = query.into_inner();
+
+    if !(radius > 0.0 && radius <= MAX_RADIUS) || cell < MIN_CELL_SIZE || radius / cell > MAX_CELLS_PER_SIDE {
+        return Ok(HttpResponse::BadRequest().body("Radius or cell size out of range"));
+    }
+
+    let grid = Grid { x, y, radius, cell };
+    let games = tracks.near(&grid.bounds());
+    let heatmap = web::block(move || grid.heatmap(games.iter().map(|game| game.as_ref()))).await?;
+
+    Ok(HttpResponse::Ok().content_type("application/geo+json").body(heatmap.to_string()))
+}
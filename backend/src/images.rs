@@ -0,0 +1,260 @@
+use std::{collections::HashMap, fs, io, path::PathBuf, sync::{Arc, Mutex}, time::{Duration, Instant}};
+
+use actix::prelude::*;
+use actix_files::NamedFile;
+use actix_web::{get, http::header, post, web, HttpResponse, Responder};
+use image::{DynamicImage, ImageFormat};
+use serde::{Deserialize, Serialize};
+use hide_and_seek_core::game::Images;
+
+use crate::message::FindGame;
+use crate::moderation::RateLimiter;
+use crate::server::GameServer;
+
+pub const MAX_IMAGE_SIZE: usize = 4 * 1024 * 1024;
+const MAX_DIMENSION: u32 = 8192;
+const THUMBNAIL_SIZE: u32 = 256;
+const MAX_IMAGES_PER_GAME: usize = 200;
+const MAX_BYTES_PER_GAME: usize = 100 * 1024 * 1024;
+pub const UPLOAD_LIMIT: usize = 10;
+const UPLOAD_WINDOW: Duration = Duration::from_secs(60);
+
+#[derive(Debug)]
+pub enum ImageError {
+    TooLarge,
+    UnsupportedType,
+    Invalid,
+    // how long until the uploader may try again
+    RateLimited(Duration),
+    GameFull,
+    Io(io::Error),
+}
+
+impl From<io::Error> for ImageError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+// What the players of a game have stored so far
+#[derive(Default)]
+struct Usage {
+    images: usize,
+    bytes: usize,
+    uploaders: HashMap<String, RateLimiter>,
+}
+
+#[derive(Clone)]
+pub struct ImageStore {
+    root: PathBuf,
+    usage: Arc<Mutex<HashMap<u16, Usage>>>,
+    max_images: usize,
+    max_bytes: usize,
+}
+
+impl ImageStore {
+    pub fn new(root: impl Into<PathBuf>) -> io::Result<Self> {
+        let root = root.into();
+        fs::create_dir_all(&root)?;
+
+        // games don't outlive the process, so anything left over is stale
+        for entry in fs::read_dir(&root)? {
+            let entry = entry?;
+            let is_game_dir = entry.file_name().to_str().is_some_and(|name| name.parse::<u16>().is_ok());
+
+            if is_game_dir && entry.file_type()?.is_dir() {
+                fs::remove_dir_all(entry.path())?;
+            }
+        }
+
+        Ok(Self { root, usage: Arc::default(), max_images: MAX_IMAGES_PER_GAME, max_bytes: MAX_BYTES_PER_GAME })
+    }
+
+    // how much the players of one game may store between them
+    pub fn with_quota(mut self, images: usize, bytes: usize) -> Self {
+        self.max_images = images;
+        self.max_bytes = bytes;
+        self
+    }
+
+    // the uploader is whoever holds the session token
+    pub fn store(&self, game_id: u16, uploader: &str, bytes: &[u8]) -> Result<String, ImageError> {
+        if bytes.len() > MAX_IMAGE_SIZE {
+            return Err(ImageError::TooLarge);
+        }
+
+        // every attempt counts, decoding is the expensive part
+        self.check_rate(game_id, uploader)?;
+
+        let (format, extension) = match image::guess_format(bytes) {
+            Ok(ImageFormat::Png) => (ImageFormat::Png, "png"),
+            Ok(ImageFormat::Jpeg) => (ImageFormat::Jpeg, "jpg"),
+            _ => return Err(ImageError::UnsupportedType),
+        };
+
+        let image = image::load_from_memory_with_format(bytes, format).map_err(|_| ImageError::Invalid)?;
+        if image.width() > MAX_DIMENSION || image.height() > MAX_DIMENSION {
+            return Err(ImageError::TooLarge);
+        }
+
+        self.reserve(game_id, bytes.len())?;
+        let stored = self.write(game_id, bytes, extension, image);
+
+        if stored.is_err() {
+            self.release(game_id, bytes.len());
+        }
+
+        stored
+    }
+
+    fn check_rate(&self, game_id: u16, uploader: &str) -> Result<(), ImageError> {
+        let mut usage = self.usage.lock().unwrap();
+        let limiter = usage.entry(game_id).or_default().uploaders
+            .entry(uploader.to_string())
+            .or_insert_with(|| RateLimiter::new(UPLOAD_LIMIT, UPLOAD_WINDOW));
+
+        limiter.check(Instant::now()).map_err(ImageError::RateLimited)
+    }
+
+    fn reserve(&self, game_id: u16, size: usize) -> Result<(), ImageError> {
+        let mut usages = self.usage.lock().unwrap();
+        let usage = usages.entry(game_id).or_default();
+
+        if usage.images >= self.max_images || usage.bytes + size > self.max_bytes {
+            return Err(ImageError::GameFull);
+        }
+
+        usage.images += 1;
+        usage.bytes += size;
+        Ok(())
+    }
+
+    fn release(&self, game_id: u16, size: usize) {
+        if let Some(usage) = self.usage.lock().unwrap().get_mut(&game_id) {
+            usage.images -= 1;
+            usage.bytes -= size;
+        }
+    }
+
+    fn write(&self, game_id: u16, bytes: &[u8], extension: &str, image: DynamicImage) -> Result<String, ImageError> {
+        let dir = self.game_dir(game_id);
+        fs::create_dir_all(&dir)?;
+
+        let id = format!("{:016x}", rand::random::<u64>());
+        let name = format!("{}.{}", id, extension);
+
+        fs::write(dir.join(&name), bytes)?;
+        image
+            .thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+            .to_rgb8()
+            .save_with_format(dir.join(thumbnail_name(&name)), ImageFormat::Jpeg)
+            .map_err(|_| ImageError::Invalid)?;
+
+        Ok(name)
+    }
+
+    pub fn exists(&self, game_id: u16, name: &str) -> bool {
+        self.path(game_id, name).is_some_and(|path| path.is_file())
+    }
+
+    pub fn path(&self, game_id: u16, name: &str) -> Option<PathBuf> {
+        let valid = !name.starts_with('.') && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.');
+
+        if valid {
+            Some(self.game_dir(game_id).join(name))
+        } else {
+            None
+        }
+    }
+
+    pub fn remove_game(&self, game_id: u16) {
+        self.usage.lock().unwrap().remove(&game_id);
+        let dir = self.game_dir(game_id);
+
+        if dir.exists() {
+            if let Err(err) = fs::remove_dir_all(&dir) {
+                println!("Failed to remove images of game {}: {}", game_id, err);
+            }
+        }
+    }
+
+    fn game_dir(&self, game_id: u16) -> PathBuf {
+        self.root.join(game_id.to_string())
+    }
+}
+
+impl Images for ImageStore {
+    fn exists(&self, game: u16, name: &str) -> bool {
+        ImageStore::exists(self, game, name)
+    }
+}
+
+fn thumbnail_name(name: &str) -> String {
+    let stem = name.split('.').next().unwrap_or(name);
+    format!("{}_thumb.jpg", stem)
+}
+
+#[derive(Deserialize)]
+struct UploadQuery {
+    token: String,
+}
+
+#[derive(Serialize)]
+struct UploadResponse {
+    image: String,
+    url: String,
+    thumbnail: String,
+}
+
+#[post("/images")]
+async fn upload(
+    query: web::Query<UploadQuery>,
+    body: web::Bytes,
+    images: web::Data<ImageStore>,
+    server: web::Data<Addr<GameServer>>,
+) -> actix_web::Result<HttpResponse> {
+    let token = query.into_inner().token;
+
+    let game_id = match server.send(FindGame { token: token.clone() }).await {
+        Ok(Some(game_id)) => game_id,
+        Ok(None) => return Ok(HttpResponse::Forbidden().body("Not in a game")),
+        Err(_) => return Ok(HttpResponse::ServiceUnavailable().body("Server unavailable")),
+    };
+
+    let store = images.get_ref().clone();
+    let result = web::block(move || store.store(game_id, &token, &body)).await?;
+
+    let response = match result {
+        Ok(image) => HttpResponse::Ok().json(UploadResponse {
+            url: format!("/images/{}/{}", game_id, image),
+            thumbnail: format!("/images/{}/{}", game_id, thumbnail_name(&image)),
+            image,
+        }),
+        Err(ImageError::TooLarge) => HttpResponse::PayloadTooLarge().body("Image too large"),
+        Err(ImageError::UnsupportedType) => HttpResponse::UnsupportedMediaType().body("Only PNG and JPEG images are supported"),
+        Err(ImageError::Invalid) => HttpResponse::BadRequest().body("Invalid image"),
+        Err(ImageError::RateLimited(wait)) => HttpResponse::TooManyRequests()
+            .insert_header((header::RETRY_AFTER, (wait.as_secs_f64().ceil() as u64).to_string()))
+            .body("Uploading too fast"),
+        Err(ImageError::GameFull) => HttpResponse::InsufficientStorage().body("This game has no room for more images"),
+        Err(ImageError::Io(err)) => {
+            println!("Failed to store image: {}", err);
+            HttpResponse::InternalServerError().body("Could not store image")
+        }
+    };
+
+    Ok(response)
+}
+
+#[get("/images/{game}/{name}")]
+async fn download(
+    path: web::Path<(u16, String)>,
+    images: web::Data<ImageStore>,
+) -> actix_web::Result<impl Responder> {
+    let (game_id, name) = path.into_inner();
+
+    match images.path(game_id, &name) {
+        Some(path) => Ok(NamedFile::open_async(path).await?),
+        None => Err(actix_web::error::ErrorNotFound("Image not found")),
+    }
+}
@@ -0,0 +1,17 @@
+pub mod archive;
+pub mod client;
+pub mod codegen;
+pub mod game;
+pub mod heatmap;
+pub mod images;
+pub mod message;
+pub mod outbox;
+pub mod protocol;
+pub mod replay;
+pub mod scenario;
+pub mod schema;
+pub mod server;
+pub mod tracks;
+pub mod util;
+
+pub use hide_and_seek_core::{clock, error, moderation};
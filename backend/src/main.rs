@@ -1,13 +1,10 @@
+use std::time::Duration;
+
 use actix::prelude::*;
 use actix_web::{web, App, HttpResponse, HttpServer, HttpRequest, get};
 use actix_web_actors::ws;
 
-use client::Session;
-
-mod server;
-mod client;
-mod message;
-mod util;
+use hide_and_seek::{archive, client::Session, heatmap, images::{self, ImageStore}, message::GetMetrics, moderation::WordFilter, replay::{self, ReplayStore}, server, tracks::{self, TrackStore}};
 
 #[get("/")]
 async fn entry_point(
@@ -22,15 +19,54 @@ async fn entry_point(
     )
 }
 
+#[get("/metrics")]
+async fn metrics(server: web::Data<Addr<server::GameServer>>) -> HttpResponse {
+    match server.send(GetMetrics).await {
+        Ok(metrics) => HttpResponse::Ok().json(metrics),
+        Err(_) => HttpResponse::ServiceUnavailable().finish(),
+    }
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
-    let server = server::GameServer::new().start();
+    // how long replays and tracks can be downloaded after a game
+    let retention = match std::env::var("RETENTION_DAYS") {
+        Ok(days) => Duration::from_secs(days.parse::<u64>().map_err(std::io::Error::other)? * 24 * 60 * 60),
+        Err(_) => archive::DEFAULT_RETENTION,
+    };
+
+    let images = ImageStore::new(std::env::var("IMAGE_DIR").unwrap_or_else(|_| "images".to_string()))?;
+    let replays = ReplayStore::new(std::env::var("REPLAY_DIR").unwrap_or_else(|_| "replays".to_string()))?.with_retention(retention);
+    let tracks = TrackStore::new(std::env::var("TRACK_DIR").unwrap_or_else(|_| "tracks".to_string()))?.with_retention(retention);
+
+    // whatever expired while the server was down goes right away
+    replays.prune();
+    tracks.prune();
+
+    let word_filter = match std::env::var("WORD_FILTER") {
+        Ok(path) => WordFilter::load(path)?,
+        Err(_) => WordFilter::default(),
+    };
+
+    println!("Loaded {} filtered words", word_filter.len());
+    let server = server::GameServer::new(images.clone(), word_filter).with_replays(replays.clone()).with_tracks(tracks.clone()).start();
 
     HttpServer::new(move || {
         App::new()
             .app_data(web::Data::new(server.clone()))
+            .app_data(web::Data::new(images.clone()))
+            .app_data(web::Data::new(replays.clone()))
+            .app_data(web::Data::new(tracks.clone()))
+            .app_data(web::PayloadConfig::new(images::MAX_IMAGE_SIZE))
             .service(entry_point)
+            .service(metrics)
+            .service(images::upload)
+            .service(images::download)
+            .service(replay::download)
+            .service(tracks::download_geojson)
+            .service(tracks::download_gpx)
+            .service(heatmap::heatmap)
     })
     .bind(("0.0.0.0", 2369))?
     .run()
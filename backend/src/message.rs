@@ -1,60 +1,116 @@
-use std::collections::HashMap;
 use actix::prelude::*;
 use serde::{Serialize, Deserialize};
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub enum ClientEvent {
-    Connect { name: String },
-    Chat { message: String },
+use crate::game::{Connection, Game};
+use crate::outbox::Outbox;
 
-    JoinGame { game: u16 },
-    LeaveGame,
-    CreateGame { x: f64, y: f64, minutes: u64 },
-    StartGame,
+pub use hide_and_seek_core::event::*;
 
-    UpdatePosition { x: f64, y: f64 },
-    TagPlayer { player: i64 },
-}
-
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub enum ServerEvent {
-    Connected { id: i64 },
-
-    Chat { sender: i64, message: String },
-    Error { message: String },
-
-    JoinedGame { id: u16, x: f64, y: f64, players: Vec<(i64, String)>, host: i64 },
-    PlayerJoined { id: i64, name: String },
-    PlayerLeft { id: i64, new_host: i64 },
-    LeftGame,
-
-    GameStarted { seeker: i64 },
-    PlayerTagged { tagger: i64, tagged: i64 },
-    ScoreUpdate { scores: HashMap<i64, f32>, seconds_left: u64, },
-    GameEnded { winner: i64 }
+#[derive(Deserialize, Debug, Clone)]
+pub struct ClientRequest {
+    #[serde(default)]
+    pub request_id: Option<u32>,
+    #[serde(flatten)]
+    pub event: ClientEvent,
 }
 
-impl ServerEvent {
-    pub fn to_string(&self) -> String {
-        serde_json::to_string(self).unwrap()
+// unit variants may also arrive as bare strings, which can't carry an id
+impl ClientRequest {
+    pub fn from_json(text: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(text).or_else(|_| {
+            serde_json::from_str(text).map(|event| Self { request_id: None, event })
+        })
     }
 
-    pub fn error(message: &str) -> Self {
-        Self::Error { message: message.to_string() }
+    pub fn from_msgpack(bytes: &[u8]) -> Result<Self, rmp_serde::decode::Error> {
+        rmp_serde::from_slice(bytes).or_else(|_| {
+            rmp_serde::from_slice(bytes).map(|event| Self { request_id: None, event })
+        })
     }
 }
 
 #[derive(Message)]
-#[rtype(i64)]
+#[rtype(result = "(i64, String, Option<Addr<Game>>)")]
 pub struct Connect {
-    pub addr: Recipient<ServerMessage>,
+    pub outbox: Outbox,
     pub name: String,
+    pub score_deltas: bool,
+    pub resume: Option<String>,
 }
 
 #[derive(Message)]
 #[rtype(result = "()")]
 pub struct Disconnect {
     pub id: i64,
+    pub outbox: Outbox,
+}
+
+// moves a player's events over to the connection they resumed on
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Resume {
+    pub id: i64,
+    pub outbox: Outbox,
+}
+
+// the game of whoever holds this session token
+#[derive(Message)]
+#[rtype(result = "Option<u16>")]
+pub struct FindGame {
+    pub token: String,
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<(Addr<Game>, ServerEvent), ServerEvent>")]
+pub struct NewGame {
+    pub player: i64,
+    pub x: f64,
+    pub y: f64,
+    pub minutes: u64,
+    pub tag_mode: TagMode,
+    pub power_ups: bool,
+    pub radius: Option<f64>,
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<(Addr<Game>, Connection), ServerEvent>")]
+pub struct LookupGame {
+    pub player: i64,
+    pub game: u16,
+}
+
+#[derive(Message)]
+#[rtype(result = "Option<ServerEvent>")]
+pub struct Join {
+    pub id: i64,
+    pub connection: Connection,
+}
+
+// sent by games to keep the server's player index up to date
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct PlayerGame {
+    pub player: i64,
+    pub game: Option<u16>,
+}
+
+// a player's game didn't take their message because its actor is gone
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct GameGone {
+    pub player: i64,
+}
+
+// drives a game's timers, sent by the game itself unless it runs on a manual clock
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Tick;
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct GameClosed {
+    pub id: u16,
+    pub players: Vec<i64>,
 }
 
 pub struct ClientMessage {
@@ -66,9 +122,16 @@ impl actix::Message for ClientMessage {
     type Result = Option<ServerEvent>;
 }
 
+#[derive(Serialize, Debug, Default)]
+pub struct Metrics {
+    pub players: usize,
+    pub games: usize,
+    pub queued_events: usize,
+    pub max_queue_depth: usize,
+    pub backed_up_clients: usize,
+    pub slow_clients: usize,
+}
 
 #[derive(Message)]
-#[rtype(result = "()")]
-pub struct ServerMessage {
-    pub event: ServerEvent,
-}
\ No newline at end of file
+#[rtype(result = "Metrics")]
+pub struct GetMetrics;
\ No newline at end of file
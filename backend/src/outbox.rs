@@ -0,0 +1,123 @@
+use std::{collections::VecDeque, sync::{Arc, Mutex}, time::{Duration, Instant}};
+use actix::prelude::*;
+
+use crate::clock::Clock;
+use crate::message::ServerEvent;
+use crate::protocol::SharedEvent;
+
+pub const MAX_QUEUE_DEPTH: usize = 64;
+const HARD_QUEUE_DEPTH: usize = MAX_QUEUE_DEPTH * 4;
+const SLOW_CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Tells a session there is something in its outbox. A session whose
+// connection is backed up isn't polled, so events wait in the outbox instead
+// of piling up in an unbounded mailbox.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Flush;
+
+// Wakes nobody, for outboxes that are drained by hand
+pub struct Sink;
+
+impl Actor for Sink {
+    type Context = Context<Self>;
+}
+
+impl Handler<Flush> for Sink {
+    type Result = ();
+
+    fn handle(&mut self, _: Flush, _: &mut Context<Self>) {}
+}
+
+#[derive(Default)]
+struct Queue {
+    events: VecDeque<Arc<SharedEvent>>,
+    over_since: Option<Instant>,
+    closed: bool,
+    parked: bool,
+}
+
+#[derive(Clone)]
+pub struct Outbox {
+    queue: Arc<Mutex<Queue>>,
+    wake: Recipient<Flush>,
+    clock: Clock,
+}
+
+impl Outbox {
+    pub fn new(wake: Recipient<Flush>) -> Self {
+        Self { queue: Arc::default(), wake, clock: Clock::System }
+    }
+
+    pub fn with_clock(mut self, clock: Clock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn send(&self, event: ServerEvent) {
+        self.push(Arc::new(SharedEvent::new(event)));
+    }
+
+    pub fn push(&self, event: Arc<SharedEvent>) {
+        let mut queue = self.queue.lock().unwrap();
+
+        if queue.closed {
+            return;
+        }
+
+        queue.events.retain(|queued| !event.event().supersedes(queued.event()));
+        queue.events.push_back(event);
+
+        let depth = queue.events.len();
+
+        if depth == 1 {
+            self.wake.do_send(Flush);
+        }
+
+        if depth <= MAX_QUEUE_DEPTH {
+            queue.over_since = None;
+            return;
+        }
+
+        // nobody reads a parked outbox, and whoever resumes is sent the whole
+        // state again anyway, so it only keeps as many of the latest events
+        // as the next connection can take at once
+        if queue.parked {
+            queue.events.pop_front();
+            return;
+        }
+
+        let now = self.clock.now();
+        let since = *queue.over_since.get_or_insert(now);
+
+        // events are never dropped, a client that can't keep up is disconnected instead
+        if depth > HARD_QUEUE_DEPTH || now.duration_since(since) >= SLOW_CLIENT_TIMEOUT {
+            queue.closed = true;
+            queue.events.clear();
+        }
+    }
+
+    pub fn drain(&self) -> Vec<Arc<SharedEvent>> {
+        let mut queue = self.queue.lock().unwrap();
+        queue.over_since = None;
+        queue.events.drain(..).collect()
+    }
+
+    // the connection is gone, but its player may still resume
+    pub fn park(&self) {
+        self.queue.lock().unwrap().parked = true;
+    }
+
+    pub fn depth(&self) -> usize {
+        self.queue.lock().unwrap().events.len()
+    }
+
+    // closed outboxes belong to clients that fell too far behind
+    pub fn is_closed(&self) -> bool {
+        self.queue.lock().unwrap().closed
+    }
+
+    pub fn same(&self, other: &Outbox) -> bool {
+        Arc::ptr_eq(&self.queue, &other.queue)
+    }
+}
@@ -0,0 +1,120 @@
+use std::{collections::HashMap, sync::OnceLock};
+use bytes::Bytes;
+use bytestring::ByteString;
+use serde::Serialize;
+
+use crate::message::{ChatChannel, ServerEvent};
+
+pub use hide_and_seek_core::protocol::*;
+
+const ENCODINGS: usize = 2;
+const VARIANTS: usize = (PROTOCOL_VERSION - MIN_PROTOCOL_VERSION + 1) as usize * ENCODINGS;
+
+// cheap to clone, so one payload can be handed to many sessions
+#[derive(Clone)]
+pub enum Payload {
+    Text(ByteString),
+    Binary(Bytes),
+}
+
+fn serialize<T: Serialize>(value: &T, encoding: Encoding) -> Payload {
+    match encoding {
+        Encoding::Json => Payload::Text(serde_json::to_string(value).unwrap().into()),
+        Encoding::MessagePack => Payload::Binary(rmp_serde::to_vec_named(value).unwrap().into()),
+    }
+}
+
+// Client events only ever gain optional fields and new variants, so older
+// clients parse into the current `ClientEvent` as is. Server events are
+// adapted per version here; `None` means the client has no use for the event.
+pub fn encode(event: &ServerEvent, version: u32, encoding: Encoding) -> Option<Payload> {
+    match version {
+        1 => v1::ServerEvent::from_event(event).map(|event| serialize(&event, encoding)),
+        _ => Some(serialize(event, encoding)),
+    }
+}
+
+// An event sent to many sessions. It is encoded at most once for every
+// protocol version and encoding, the first time a session asks for it.
+pub struct SharedEvent {
+    event: ServerEvent,
+    encoded: [OnceLock<Option<Payload>>; VARIANTS],
+}
+
+impl SharedEvent {
+    pub fn new(event: ServerEvent) -> Self {
+        Self { event, encoded: Default::default() }
+    }
+
+    pub fn event(&self) -> &ServerEvent {
+        &self.event
+    }
+
+    pub fn encode(&self, version: u32, encoding: Encoding) -> Option<Payload> {
+        let index = (version - MIN_PROTOCOL_VERSION) as usize * ENCODINGS + encoding as usize;
+        self.encoded[index].get_or_init(|| encode(&self.event, version, encoding)).clone()
+    }
+}
+
+// The event set understood by app builds before protocol versioning existed.
+// This must stay frozen.
+mod v1 {
+    use super::*;
+
+    #[derive(Serialize)]
+    pub enum ServerEvent {
+        Connected { id: i64 },
+
+        Chat { sender: i64, message: String },
+        Error { message: String },
+
+        JoinedGame { id: u16, x: f64, y: f64, players: Vec<(i64, String)>, host: i64 },
+        PlayerJoined { id: i64, name: String },
+        PlayerLeft { id: i64, new_host: i64 },
+        LeftGame,
+
+        GameStarted { seeker: i64 },
+        PlayerTagged { tagger: i64, tagged: i64 },
+        ScoreUpdate { scores: HashMap<i64, f32>, seconds_left: u64, },
+        GameEnded { winner: i64 }
+    }
+
+    impl ServerEvent {
+        pub fn from_event(event: &super::ServerEvent) -> Option<Self> {
+            use super::ServerEvent as Current;
+
+            let event = match event.clone() {
+                Current::Connected { id, .. } => Self::Connected { id },
+                Current::Chat { sender, message, image, channel } => {
+                    let message = match (message.is_empty(), image) {
+                        (true, Some(_)) => "[image]".to_string(),
+                        _ => message,
+                    };
+
+                    // v1 only knows public chat, so private messages must not pass for it
+                    let message = match channel {
+                        ChatChannel::All => message,
+                        ChatChannel::Team => format!("[team] {}", message),
+                        ChatChannel::Hiders => format!("[hiders] {}", message),
+                        ChatChannel::Seeker => format!("[seeker] {}", message),
+                        ChatChannel::Direct(_) => format!("[private] {}", message),
+                    };
+
+                    Self::Chat { sender, message }
+                },
+                Current::Error { message, .. } => Self::Error { message },
+                Current::JoinedGame { id, x, y, players, host, .. } => Self::JoinedGame { id, x, y, players, host },
+                Current::PlayerJoined { id, name } => Self::PlayerJoined { id, name },
+                Current::PlayerLeft { id, new_host } => Self::PlayerLeft { id, new_host },
+                Current::LeftGame => Self::LeftGame,
+                Current::GameStarted { seeker } => Self::GameStarted { seeker },
+                Current::PlayerTagged { tagger, tagged } => Self::PlayerTagged { tagger, tagged },
+                Current::ScoreUpdate { scores, seconds_left } => Self::ScoreUpdate { scores, seconds_left },
+                Current::GameEnded { winner, .. } => Self::GameEnded { winner },
+                _ => return None,
+            };
+
+            Some(event)
+        }
+    }
+}
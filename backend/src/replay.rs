@@ -0,0 +1,285 @@
+use std::{
+    collections::VecDeque,
+    fs::{File, OpenOptions},
+    io::{self, BufWriter, Cursor, Write},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use actix_files::NamedFile;
+use actix_web::{get, web, Responder};
+use geo::Point;
+use rand::{rngs::StdRng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use hide_and_seek_core::game::{self as rules, Command, Effect, Images, Outcome, Player, Settings};
+
+use crate::archive::{Archive, DownloadQuery};
+use crate::message::*;
+use crate::moderation::WordFilter;
+
+pub const FORMAT_VERSION: u32 = 2;
+const EXTENSION: &str = "replay";
+
+// A replay file is this header followed by one entry per command the game
+// handled, each a MessagePack value appended as it happens.
+#[derive(Serialize, Deserialize)]
+pub struct Header {
+    pub version: u32,
+    pub game: u16,
+    pub seed: u64,
+    pub settings: Settings,
+    pub host: RecordedPlayer,
+}
+
+// A player as they were handed to the rules, including where they said they
+// were before joining
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RecordedPlayer {
+    pub name: String,
+    pub score_deltas: bool,
+    pub pos: Option<(f64, f64)>,
+    // nanoseconds between their last move and the command
+    pub moved: u64,
+}
+
+impl RecordedPlayer {
+    pub fn new(player: &Player, now: Instant) -> Self {
+        Self {
+            name: player.name.clone(),
+            score_deltas: player.score_deltas,
+            pos: player.pos.map(|pos| (pos.x(), pos.y())),
+            moved: now.saturating_duration_since(player.moved).as_nanos() as u64,
+        }
+    }
+
+    fn player(&self, now: Instant) -> Player {
+        let mut player = Player::new(self.name.clone(), self.score_deltas, now);
+        player.pos = self.pos.map(Point::from);
+        player.moved = now.checked_sub(Duration::from_nanos(self.moved)).unwrap_or(now);
+        player
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub enum RecordedCommand {
+    Join { id: i64, player: RecordedPlayer },
+    Client { sender: i64, event: ClientEvent },
+    Leave { id: i64 },
+    Resume { id: i64 },
+    Tick,
+}
+
+impl RecordedCommand {
+    pub fn new(command: &Command, now: Instant) -> Self {
+        match command {
+            Command::Join { id, player } => RecordedCommand::Join { id: *id, player: RecordedPlayer::new(player, now) },
+            Command::Client { sender, event } => RecordedCommand::Client { sender: *sender, event: event.clone() },
+            Command::Leave { id } => RecordedCommand::Leave { id: *id },
+            Command::Resume { id } => RecordedCommand::Resume { id: *id },
+            Command::Tick => RecordedCommand::Tick,
+        }
+    }
+
+    fn command(self, now: Instant) -> Command {
+        match self {
+            RecordedCommand::Join { id, player } => Command::Join { id, player: player.player(now) },
+            RecordedCommand::Client { sender, event } => Command::Client { sender, event },
+            RecordedCommand::Leave { id } => Command::Leave { id },
+            RecordedCommand::Resume { id } => Command::Resume { id },
+            RecordedCommand::Tick => Command::Tick,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Entry {
+    // nanoseconds since the game was created
+    pub at: u64,
+    pub command: RecordedCommand,
+    // what the rules were told when they asked whether an image exists
+    pub images: Vec<bool>,
+    pub reply: Option<ServerEvent>,
+    pub effects: Vec<Effect>,
+}
+
+struct RecordingImages {
+    images: Arc<dyn Images>,
+    lookups: Arc<Mutex<Vec<bool>>>,
+}
+
+impl Images for RecordingImages {
+    fn exists(&self, game: u16, name: &str) -> bool {
+        let exists = self.images.exists(game, name);
+        self.lookups.lock().unwrap().push(exists);
+        exists
+    }
+}
+
+pub struct Recorder {
+    file: BufWriter<File>,
+    created: Instant,
+    lookups: Arc<Mutex<Vec<bool>>>,
+}
+
+impl Recorder {
+    // image lookups have to go through the recorder to end up in the replay
+    pub fn images(&self, images: Arc<dyn Images>) -> Arc<dyn Images> {
+        Arc::new(RecordingImages { images, lookups: self.lookups.clone() })
+    }
+
+    pub fn record(&mut self, now: Instant, command: RecordedCommand, outcome: &Outcome) -> io::Result<()> {
+        let entry = Entry {
+            at: now.duration_since(self.created).as_nanos() as u64,
+            images: self.lookups.lock().unwrap().drain(..).collect(),
+            reply: outcome.reply.clone(),
+            effects: outcome.effects.clone(),
+            command,
+        };
+
+        write(&mut self.file, &entry)?;
+
+        // ticks are the natural points for a download of a running game to end
+        match entry.command {
+            RecordedCommand::Tick => self.file.flush(),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn write(file: &mut BufWriter<File>, value: &impl Serialize) -> io::Result<()> {
+    rmp_serde::encode::write_named(file, value).map_err(io::Error::other)
+}
+
+#[derive(Clone)]
+pub struct ReplayStore {
+    archive: Archive,
+}
+
+impl ReplayStore {
+    pub fn new(root: impl Into<PathBuf>) -> io::Result<Self> {
+        Ok(Self { archive: Archive::new(root, EXTENSION)? })
+    }
+
+    pub fn with_retention(mut self, retention: Duration) -> Self {
+        self.archive = self.archive.with_retention(retention);
+        self
+    }
+
+    pub fn create(&self, header: &Header, token: &str, now: Instant) -> io::Result<Recorder> {
+        let path = self.archive.path(header.game, token);
+
+        let mut file = BufWriter::new(OpenOptions::new().append(true).create_new(true).open(path)?);
+        write(&mut file, header)?;
+        file.flush()?;
+
+        Ok(Recorder { file, created: now, lookups: Arc::default() })
+    }
+
+    pub fn find(&self, game: u16, token: &str) -> Option<PathBuf> {
+        self.archive.find(game, token)
+    }
+
+    pub fn prune(&self) {
+        self.archive.prune();
+    }
+}
+
+#[get("/replays/{game}")]
+async fn download(path: web::Path<u16>, query: web::Query<DownloadQuery>, replays: web::Data<ReplayStore>) -> actix_web::Result<impl Responder> {
+    match replays.find(path.into_inner(), &query.token) {
+        Some(path) => Ok(NamedFile::open_async(path).await?),
+        None => Err(actix_web::error::ErrorNotFound("Replay not found")),
+    }
+}
+
+#[derive(Default)]
+struct ReplayImages {
+    answers: Mutex<VecDeque<bool>>,
+}
+
+impl Images for ReplayImages {
+    fn exists(&self, _: u16, _: &str) -> bool {
+        self.answers.lock().unwrap().pop_front().unwrap_or(false)
+    }
+}
+
+pub struct Mismatch {
+    pub index: usize,
+    pub at: Duration,
+    pub expected: serde_json::Value,
+    pub actual: serde_json::Value,
+}
+
+pub struct Summary {
+    pub entries: usize,
+    pub mismatches: Vec<Mismatch>,
+    pub winner: Option<i64>,
+}
+
+pub struct Replay {
+    pub header: Header,
+    pub entries: Vec<Entry>,
+}
+
+impl Replay {
+    // a game that is still running, or whose server died, may end in a
+    // partly written entry, which is left out
+    pub fn read(bytes: &[u8]) -> Result<Self, rmp_serde::decode::Error> {
+        let mut cursor = Cursor::new(bytes);
+        let header: Header = rmp_serde::decode::from_read(&mut cursor)?;
+        let mut entries = Vec::new();
+
+        while (cursor.position() as usize) < bytes.len() {
+            match rmp_serde::decode::from_read(&mut cursor) {
+                Ok(entry) => entries.push(entry),
+                Err(_) => break,
+            }
+        }
+
+        Ok(Self { header, entries })
+    }
+
+    // Runs the recorded commands through the rules again and compares what
+    // they produce with what was recorded.
+    pub fn run(&self, word_filter: WordFilter) -> Summary {
+        let start = Instant::now();
+        let images = Arc::new(ReplayImages::default());
+        let host = self.header.host.player(start);
+
+        let mut game = rules::Game::new(
+            self.header.game,
+            self.header.settings.clone(),
+            host,
+            images.clone(),
+            Arc::new(word_filter),
+            StdRng::seed_from_u64(self.header.seed),
+        );
+
+        let mut mismatches = Vec::new();
+        let mut winner = None;
+
+        for (index, entry) in self.entries.iter().enumerate() {
+            let at = Duration::from_nanos(entry.at);
+            *images.answers.lock().unwrap() = entry.images.iter().copied().collect();
+
+            let outcome = game.handle(start + at, entry.command.clone().command(start + at));
+
+            for effect in &outcome.effects {
+                if let Effect::Send { event: ServerEvent::GameEnded { winner: w, .. }, .. } = effect {
+                    winner = Some(*w);
+                }
+            }
+
+            let expected = json!({ "reply": entry.reply, "effects": entry.effects });
+            let actual = json!({ "reply": outcome.reply, "effects": outcome.effects });
+
+            if expected != actual {
+                mismatches.push(Mismatch { index, at, expected, actual });
+            }
+        }
+
+        Summary { entries: self.entries.len(), mismatches, winner }
+    }
+}
@@ -0,0 +1,405 @@
+use std::{collections::{BTreeMap, HashMap}, fs, io, path::{Path, PathBuf}, time::Duration};
+
+use actix::prelude::*;
+use geo::Point;
+use serde::{Deserialize, Serialize};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::clock::Clock;
+use crate::error::ErrorCode;
+use crate::game::Game;
+use crate::images::{ImageError, ImageStore};
+use crate::message::*;
+use crate::moderation::WordFilter;
+use crate::outbox::{Outbox, Sink};
+use crate::server::GameServer;
+
+#[derive(Debug)]
+pub enum ScenarioError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    Gpx(String),
+    Image(ImageError),
+    NoPlayers,
+    UnknownPlayer(String),
+    Setup(ServerEvent),
+}
+
+impl From<io::Error> for ScenarioError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for ScenarioError {
+    fn from(err: serde_json::Error) -> Self {
+        Self::Json(err)
+    }
+}
+
+impl From<ImageError> for ScenarioError {
+    fn from(err: ImageError) -> Self {
+        Self::Image(err)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct GameSettings {
+    pub x: f64,
+    pub y: f64,
+    pub minutes: u64,
+    #[serde(default)] pub tag_mode: TagMode,
+    #[serde(default)] pub power_ups: bool,
+    #[serde(default)] pub radius: Option<f64>,
+}
+
+// The first player hosts the game. Track times are counted from the track's
+// first point, shifted by `offset` seconds.
+#[derive(Deserialize)]
+pub struct ScenarioPlayer {
+    pub name: String,
+    #[serde(default)] pub track: Option<PathBuf>,
+    #[serde(default)] pub offset: u64,
+    #[serde(skip)] pub points: Vec<(u64, Point)>,
+}
+
+#[derive(Deserialize)]
+pub enum Action {
+    Start,
+    // uses the target's latest nonce in handshake games
+    Tag { target: String, #[serde(default)] image: Option<PathBuf> },
+    Confirm,
+    Dispute,
+    Chat { message: String },
+    Leave,
+}
+
+#[derive(Deserialize)]
+pub struct ScriptedEvent {
+    pub at: u64,
+    pub player: String,
+    pub action: Action,
+}
+
+#[derive(Deserialize)]
+pub struct Scenario {
+    #[serde(default)] pub seed: u64,
+    pub game: GameSettings,
+    pub players: Vec<ScenarioPlayer>,
+    #[serde(default)] pub events: Vec<ScriptedEvent>,
+}
+
+#[derive(Serialize)]
+pub struct LogEntry {
+    pub at: u64,
+    pub to: Vec<String>,
+    pub event: ServerEvent,
+}
+
+#[derive(Serialize)]
+pub struct Report {
+    pub players: BTreeMap<String, i64>,
+    pub log: Vec<LogEntry>,
+    pub scores: BTreeMap<String, f32>,
+    pub winner: Option<String>,
+}
+
+impl Scenario {
+    // tracks and images are relative to the scenario file
+    pub fn load(path: &Path) -> Result<Self, ScenarioError> {
+        let dir = path.parent().unwrap_or(Path::new("."));
+        let mut scenario: Scenario = serde_json::from_str(&fs::read_to_string(path)?)?;
+
+        for player in &mut scenario.players {
+            if let Some(track) = &player.track {
+                player.points = parse_gpx(&fs::read_to_string(dir.join(track))?)?;
+            }
+        }
+
+        for event in &mut scenario.events {
+            if let Action::Tag { image: Some(image), .. } = &mut event.action {
+                *image = dir.join(&image);
+            }
+        }
+
+        scenario.validate()?;
+        Ok(scenario)
+    }
+
+    fn validate(&self) -> Result<(), ScenarioError> {
+        let known = |name: &String| match self.players.iter().any(|player| &player.name == name) {
+            true => Ok(()),
+            false => Err(ScenarioError::UnknownPlayer(name.clone())),
+        };
+
+        if self.players.is_empty() {
+            return Err(ScenarioError::NoPlayers);
+        }
+
+        for event in &self.events {
+            known(&event.player)?;
+
+            if let Action::Tag { target, .. } = &event.action {
+                known(target)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn length(&self) -> u64 {
+        let tracks = self.players.iter().filter_map(|player| Some(player.offset + player.points.last()?.0));
+        let events = self.events.iter().map(|event| event.at);
+
+        tracks.chain(events).max().unwrap_or(0).max(self.game.minutes * 60)
+    }
+}
+
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let start = tag.find(&format!(" {}=", name))? + name.len() + 2;
+    let quote = tag[start..].chars().next()?;
+    let value = &tag[start + 1..];
+
+    Some(&value[..value.find(quote)?])
+}
+
+// Only what the runner needs: every <trkpt> with its position and time, as
+// seconds since the first point. Self-closing points have no time to be
+// replayed at, so they are skipped.
+pub fn parse_gpx(text: &str) -> Result<Vec<(u64, Point)>, ScenarioError> {
+    let mut points = Vec::new();
+    let mut first = None;
+
+    for chunk in text.split("<trkpt").skip(1) {
+        let tag = match chunk.find('>') {
+            Some(end) => &chunk[..end],
+            None => return Err(ScenarioError::Gpx("Unclosed track point".to_string())),
+        };
+
+        if tag.ends_with('/') {
+            continue;
+        }
+
+        let point = match chunk.find("</trkpt>") {
+            Some(end) => &chunk[tag.len()..end],
+            None => return Err(ScenarioError::Gpx("Track point without </trkpt>".to_string())),
+        };
+
+        let coordinate = |name| {
+            attribute(tag, name)
+                .and_then(|value| value.parse::<f64>().ok())
+                .ok_or_else(|| ScenarioError::Gpx(format!("Track point without {}", name)))
+        };
+        let (lat, lon) = (coordinate("lat")?, coordinate("lon")?);
+
+        let time = point.split_once("<time>")
+            .and_then(|(_, rest)| rest.split_once("</time>"))
+            .ok_or_else(|| ScenarioError::Gpx("Track point without time".to_string()))?;
+        let time = OffsetDateTime::parse(time.0.trim(), &Rfc3339).map_err(|err| ScenarioError::Gpx(err.to_string()))?;
+
+        let seconds = (time - *first.get_or_insert(time)).whole_seconds();
+
+        match points.last() {
+            Some(&(last, _)) if seconds < last as i64 => return Err(ScenarioError::Gpx("Track points out of order".to_string())),
+            _ if seconds < 0 => return Err(ScenarioError::Gpx("Track points out of order".to_string())),
+            _ => points.push((seconds as u64, Point::new(lon, lat))),
+        }
+    }
+
+    if points.is_empty() {
+        return Err(ScenarioError::Gpx("No track points".to_string()));
+    }
+
+    Ok(points)
+}
+
+struct Runner<'a> {
+    player: &'a ScenarioPlayer,
+    id: i64,
+    token: String,
+    outbox: Outbox,
+    next_point: usize,
+    nonce: Option<String>,
+    left: bool,
+}
+
+struct Run<'a> {
+    scenario: &'a Scenario,
+    images: ImageStore,
+    game: Addr<Game>,
+    game_id: u16,
+    runners: Vec<Runner<'a>>,
+    report: Report,
+    ended: bool,
+}
+
+impl Run<'_> {
+    fn runner(&self, name: &str) -> usize {
+        self.runners.iter().position(|runner| runner.player.name == name).unwrap()
+    }
+
+    fn record(&mut self, at: u64, to: usize, event: ServerEvent) {
+        let name = self.runners[to].player.name.clone();
+
+        match &event {
+            ServerEvent::ScoreUpdate { scores, .. } => {
+                let names: HashMap<i64, &String> = self.runners.iter().map(|runner| (runner.id, &runner.player.name)).collect();
+                self.report.scores = scores.iter().map(|(id, score)| (names[id].clone(), *score)).collect();
+                return;
+            },
+            ServerEvent::ScoreSnapshot { .. } | ServerEvent::ScoreDelta { .. } => return,
+            ServerEvent::TagNonce { nonce, .. } => self.runners[to].nonce = Some(nonce.clone()),
+            ServerEvent::GameEnded { winner, .. } => {
+                self.ended = true;
+                self.report.winner = self.runners.iter().find(|runner| runner.id == *winner).map(|runner| runner.player.name.clone());
+            },
+            _ => (),
+        }
+
+        // an event sent to several players is logged once
+        let json = serde_json::to_value(&event).unwrap();
+        let same = self.report.log.iter_mut()
+            .rev()
+            .take_while(|entry| entry.at == at)
+            .find(|entry| serde_json::to_value(&entry.event).unwrap() == json);
+
+        match same {
+            Some(entry) if !entry.to.contains(&name) => entry.to.push(name),
+            _ => self.report.log.push(LogEntry { at, to: vec![name], event }),
+        }
+    }
+
+    fn collect(&mut self, at: u64) {
+        for index in 0..self.runners.len() {
+            for event in self.runners[index].outbox.drain() {
+                self.record(at, index, event.event().clone());
+            }
+        }
+    }
+
+    async fn send(&mut self, at: u64, index: usize, event: ClientEvent) {
+        let sender = self.runners[index].id;
+
+        // a closed game is only a problem for the events that follow
+        if let Ok(Some(reply)) = self.game.send(ClientMessage { sender, event }).await {
+            self.record(at, index, reply);
+        }
+    }
+
+    async fn move_players(&mut self, at: u64) {
+        for index in 0..self.runners.len() {
+            let runner = &mut self.runners[index];
+            let mut pos = None;
+
+            while let Some(&(time, point)) = runner.player.points.get(runner.next_point) {
+                if runner.player.offset + time > at {
+                    break;
+                }
+
+                pos = Some(point);
+                runner.next_point += 1;
+            }
+
+            if let (Some(pos), false) = (pos, runner.left) {
+                self.send(at, index, ClientEvent::UpdatePosition { x: pos.x(), y: pos.y() }).await;
+            }
+        }
+    }
+
+    async fn act(&mut self, at: u64, event: &ScriptedEvent) -> Result<(), ScenarioError> {
+        let index = self.runner(&event.player);
+
+        let event = match &event.action {
+            Action::Start => ClientEvent::StartGame,
+            Action::Tag { target, image } => {
+                let target = &self.runners[self.runner(target)];
+
+                match (self.scenario.game.tag_mode, image) {
+                    (TagMode::Handshake, _) => ClientEvent::TagWithProof { nonce: target.nonce.clone().unwrap_or_default() },
+                    (_, Some(image)) => {
+                        let image = self.images.store(self.game_id, &self.runners[index].token, &fs::read(image)?)?;
+                        ClientEvent::TagPlayer { player: target.id, image: Some(image) }
+                    },
+                    (_, None) => ClientEvent::TagPlayer { player: target.id, image: None },
+                }
+            },
+            Action::Confirm => ClientEvent::ConfirmTag,
+            Action::Dispute => ClientEvent::DisputeTag,
+            Action::Chat { message } => ClientEvent::Chat { message: message.clone(), image: None, channel: ChatChannel::All },
+            Action::Leave => {
+                self.runners[index].left = true;
+                ClientEvent::LeaveGame
+            },
+        };
+
+        self.send(at, index, event).await;
+        Ok(())
+    }
+}
+
+// Plays a scenario against an in-process server, one virtual second at a
+// time, until the game ends or the scenario runs out.
+pub async fn run(scenario: &Scenario) -> Result<Report, ScenarioError> {
+    let dir = std::env::temp_dir().join(format!("hide_and_seek_scenario_{}", std::process::id()));
+    let images = ImageStore::new(dir)?;
+    let clock = Clock::manual();
+    let server = GameServer::with_clock(images.clone(), WordFilter::default(), clock.clone(), scenario.seed).start();
+
+    let mut runners = Vec::new();
+
+    for player in &scenario.players {
+        let outbox = Outbox::new(Sink.start().recipient());
+        let connect = Connect { outbox: outbox.clone(), name: player.name.clone(), score_deltas: false, resume: None };
+        let (id, token, _) = server.send(connect).await.unwrap();
+
+        runners.push(Runner { player, id, token, outbox, next_point: 0, nonce: None, left: false });
+    }
+
+    let settings = &scenario.game;
+    let new_game = NewGame { player: runners[0].id, x: settings.x, y: settings.y, minutes: settings.minutes, tag_mode: settings.tag_mode, power_ups: settings.power_ups, radius: settings.radius };
+
+    let (game, game_id) = match server.send(new_game).await.unwrap() {
+        Ok((game, ServerEvent::JoinedGame { id, .. })) => (game, id),
+        Ok((_, event)) | Err(event) => return Err(ScenarioError::Setup(event)),
+    };
+
+    for runner in &runners[1..] {
+        let (addr, connection) = server.send(LookupGame { player: runner.id, game: game_id }).await.unwrap().map_err(ScenarioError::Setup)?;
+
+        match addr.send(Join { id: runner.id, connection }).await.unwrap() {
+            Some(ServerEvent::JoinedGame { .. }) => (),
+            Some(event) => return Err(ScenarioError::Setup(event)),
+            None => return Err(ScenarioError::Setup(ServerEvent::error(ErrorCode::GameNotFound))),
+        }
+    }
+
+    let players = runners.iter().map(|runner| (runner.player.name.clone(), runner.id)).collect();
+    let report = Report { players, log: Vec::new(), scores: BTreeMap::new(), winner: None };
+    let mut run = Run { scenario, images, game, game_id, runners, report, ended: false };
+
+    run.collect(0);
+
+    for at in 0..=scenario.length() {
+        if at > 0 {
+            clock.advance(Duration::from_secs(1));
+
+            if run.game.send(Tick).await.is_err() {
+                break;
+            }
+        }
+
+        run.move_players(at).await;
+
+        for event in scenario.events.iter().filter(|event| event.at == at) {
+            run.act(at, event).await?;
+        }
+
+        run.collect(at);
+
+        if run.ended {
+            break;
+        }
+    }
+
+    Ok(run.report)
+}
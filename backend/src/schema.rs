@@ -0,0 +1,38 @@
+use schemars::gen::SchemaSettings;
+use serde_json::{json, Value};
+
+use crate::message::{ClientEvent, ServerEvent};
+
+pub fn protocol_schema() -> Value {
+    let mut gen = SchemaSettings::draft07().into_generator();
+    let client = gen.subschema_for::<ClientEvent>();
+    let server = gen.subschema_for::<ServerEvent>();
+    let request_id = gen.subschema_for::<Option<u32>>();
+
+    let mut definitions = serde_json::to_value(gen.definitions()).unwrap();
+
+    // `ClientRequest` flattens the event into the envelope, which schemars
+    // can't describe for an enum. Unit variants need the {"Variant": null}
+    // form to carry an id.
+    definitions["ClientRequest"] = json!({
+        "allOf": [
+            client,
+            {
+                "properties": {
+                    "request_id": request_id,
+                },
+            },
+        ],
+    });
+
+    json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": "HideAndSeekProtocol",
+        "definitions": definitions,
+        "properties": {
+            "client": client,
+            "request": { "$ref": "#/definitions/ClientRequest" },
+            "server": server,
+        },
+    })
+}
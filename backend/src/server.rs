@@ -1,365 +1,319 @@
-use std::{cmp::Ordering, collections::HashMap, time::{Duration, Instant}};
+use std::{collections::HashMap, num::NonZeroUsize, sync::Arc, thread, time::Duration};
 use actix::prelude::*;
-use geo::{Point, GeodesicDistance};
-use rand::{seq::SliceRandom, rngs::ThreadRng};
-
+use geo::Point;
+use hide_and_seek_core::game::{self as rules, Images, Player, Settings, MAX_PLAY_AREA_RADIUS, MIN_PLAY_AREA_RADIUS};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::clock::Clock;
+use crate::error::ErrorCode;
+use crate::game::{Connection, Game};
+use crate::images::ImageStore;
 use crate::message::*;
-use crate::util::generate_id;
-
-const UPDATE_INTERVAL: Duration = Duration::from_secs(1);
-
-enum GameState {
-    Waiting,
-    Playing {
-        seeker: i64, 
-        handle: SpawnHandle,
-        start: Instant,
-        scores: HashMap<i64, f32>,
-    },
-    Ended
-}
+use crate::moderation::WordFilter;
+use crate::outbox::{Outbox, MAX_QUEUE_DEPTH};
+use crate::replay::{self, ReplayStore};
+use crate::tracks::TrackStore;
+use crate::util::{generate_id, generate_token, random_token};
 
-struct Player {
-    name: String,
-    addr: Recipient<ServerMessage>,
-    pos: Option<Point<f64>>,
-}
+// how long a player who lost their connection keeps their place in a game
+const RESUME_TIMEOUT: Duration = Duration::from_secs(60);
 
+// Keeps track of connected players and running games. Everything that
+// happens inside a game is handled by that game's own actor.
 pub struct GameServer {
-    players: HashMap<i64, Player>,
-    games: HashMap<u16, Game>,
-    rng: ThreadRng,
+    players: HashMap<i64, Connection>,
+    games: HashMap<u16, Addr<Game>>,
+    player_games: HashMap<i64, u16>,
+    tokens: HashMap<String, i64>,
+    images: ImageStore,
+    word_filter: Arc<WordFilter>,
+    clock: Clock,
+    rng: StdRng,
+    replays: Option<ReplayStore>,
+    tracks: Option<TrackStore>,
+    arbiters: Vec<ArbiterHandle>,
 }
 
 impl Actor for GameServer {
     type Context = Context<Self>;
-}
 
-struct Game {
-    host: i64,
-    players: Vec<i64>,
-    pos: Point<f64>,
-    state: GameState,
-    length: Duration,
-}
-
-impl Game {
-    pub fn new(host: i64, pos: Point<f64>, length: Duration) -> Self {
-        let players = vec![host];
+    // games run on threads of their own so a busy one doesn't hold up the rest
+    fn started(&mut self, _: &mut Self::Context) {
+        if self.arbiters.is_empty() {
+            let threads = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+            self.arbiters = (0..threads).map(|_| Arbiter::new().handle()).collect();
+        }
+    }
 
-        Self {
-            host, pos, players, length,
-            state: GameState::Waiting,
+    fn stopped(&mut self, _: &mut Self::Context) {
+        for arbiter in &self.arbiters {
+            arbiter.stop();
         }
     }
 }
 
 impl GameServer {
-    pub fn new() -> Self {
+    pub fn new(images: ImageStore, word_filter: WordFilter) -> Self {
+        Self::with_rng(images, word_filter, Clock::System, StdRng::from_entropy())
+    }
+
+    // every game gets its own rng seeded from this one, so a seeded server
+    // hands out the same ids, seekers and nonces on every run
+    pub fn with_clock(images: ImageStore, word_filter: WordFilter, clock: Clock, seed: u64) -> Self {
+        Self::with_rng(images, word_filter, clock, StdRng::seed_from_u64(seed))
+    }
+
+    fn with_rng(images: ImageStore, word_filter: WordFilter, clock: Clock, rng: StdRng) -> Self {
         Self {
             games: HashMap::new(),
+            player_games: HashMap::new(),
+            tokens: HashMap::new(),
             players: HashMap::new(),
-            rng: rand::thread_rng(),
+            images,
+            word_filter: Arc::new(word_filter),
+            clock,
+            rng,
+            replays: None,
+            tracks: None,
+            arbiters: Vec::new(),
         }
     }
 
-    fn error(message: &str) -> Option<ServerEvent> {
-        Some(ServerEvent::error(message))
+    pub fn with_replays(mut self, replays: ReplayStore) -> Self {
+        self.replays = Some(replays);
+        self
     }
 
-    fn get_player_mut(&mut self, player_id: i64) -> Result<&mut Player, Option<ServerEvent>> {
-        match self.players.get_mut(&player_id) {
-            Some(player) => return Ok(player),
-            None => return Err(Self::error("Player not found")),
-        }
+    pub fn with_tracks(mut self, tracks: TrackStore) -> Self {
+        self.tracks = Some(tracks);
+        self
     }
 
-    fn get_player(&self, player_id: i64) -> Result<&Player, Option<ServerEvent>> {
-        match self.players.get(&player_id) {
-            Some(player) => return Ok(player),
-            None => return Err(Self::error("Player not found")),
-        }
+    pub fn with_arbiters(mut self, arbiters: Vec<ArbiterHandle>) -> Self {
+        self.arbiters = arbiters;
+        self
     }
 
-    fn broadcast_if(&self, game_id: Option<u16>, event: ServerEvent, exclude: Option<i64>) {
-        if let Some(game_id) = game_id {
-            self.broadcast(game_id, event, exclude);
-        }
+    fn error(code: ErrorCode) -> Option<ServerEvent> {
+        Some(ServerEvent::error(code))
     }
 
-    fn broadcast(&self, game_id: u16, event: ServerEvent, exclude: Option<i64>) {
-        if let Some(game) = self.games.get(&game_id) {
-            for id in &game.players {
-                if Some(*id) != exclude {
-                    if let Some(player) = self.players.get(id) {
-                        player.addr.do_send(ServerMessage { event: event.clone() });
-                    }
-                }
-            }
-        }
+    fn find_game(&self, player_id: i64) -> Option<u16> {
+        self.player_games.get(&player_id).copied()
     }
 
-    fn find_game(&self, player_id: i64) -> Option<u16> {
-        for (id, game) in &self.games {
-            if game.players.contains(&player_id) {
-                return Some(*id);
-            }
+    // A game whose actor died without closing, say after a panic, would
+    // otherwise keep its players from ever joining another one.
+    fn purge_dead_game(&mut self, id: u16) -> bool {
+        if self.games.get(&id).is_none_or(Addr::connected) {
+            return false;
         }
 
-        None
+        println!("Game {} stopped without closing", id);
+        self.games.remove(&id);
+        self.player_games.retain(|_, game| *game != id);
+        self.images.remove_game(id);
+        true
     }
 
-    fn cancel_game(&mut self, ctx: &mut Context<Self>, id: u16) {
-        if let Some(game) = self.games.get(&id) {
-            println!("Game {} canceled", id);
+    fn current_game(&mut self, player_id: i64) -> Option<u16> {
+        let game = self.find_game(player_id)?;
+        (!self.purge_dead_game(game)).then_some(game)
+    }
 
-            if let GameState::Playing { handle, .. } = game.state {
-                ctx.cancel_future(handle);
-            }
-            
-            self.broadcast(id, ServerEvent::LeftGame, None);
-            self.games.remove(&id);
+    fn remove_player(&mut self, id: i64) {
+        if let Some(connection) = self.players.remove(&id) {
+            println!("{} disconnected", connection.player.name);
+            self.player_games.remove(&id);
+            self.tokens.retain(|_, player| *player != id);
         }
     }
 
-    fn end_game(&mut self, ctx: &mut Context<Self>, id: u16) {
-        if let Some(game) = self.games.get_mut(&id) {
-            if let GameState::Playing { handle, scores, .. } = &game.state {
-                println!("Game {} ended", id);
-                ctx.cancel_future(*handle);
-
-                let winner = match scores.iter().max_by(|a, b| {
-                    return a.partial_cmp(b).unwrap_or(Ordering::Equal);
-                }) {
-                    Some(w) => *w.0,
-                    None => {
-                        self.cancel_game(ctx, id);
-                        return;
-                    },
-                };
-
-                game.state = GameState::Ended;
-                self.broadcast(id, ServerEvent::GameEnded { winner }, None);
-            }
-        }
-    } 
+    // a resumed player has moved on to another connection already
+    fn is_connected_with(&self, id: i64, outbox: &Outbox) -> bool {
+        self.players.get(&id).is_some_and(|connection| connection.outbox.same(outbox))
+    }
 
-    fn update_game(&mut self, ctx: &mut Context<Self>, game_id: u16) {
-        let game = match self.games.get_mut(&game_id) {
-            Some(game) => game,
-            None => return,
-        };
+    fn resume(&mut self, token: &str, outbox: Outbox) -> Option<(i64, Option<Addr<Game>>)> {
+        let id = *self.tokens.get(token)?;
+        let connection = self.players.get_mut(&id)?;
 
-        let (seeker, start, scores) = match &mut game.state {
-            GameState::Playing { seeker, start, scores, .. } => (*seeker, *start, scores),
-            _ => return,
-        };
+        println!("{} resumed their session", connection.player.name);
+        connection.outbox = outbox.clone();
 
-        for (id, score) in &mut *scores {
-            if *id == seeker {
-                continue;
-            }
+        let game = self.current_game(id).and_then(|game| self.games.get(&game)).cloned();
 
-            if let Some(player) = self.players.get(&id) {
-                if let Some(pos) = player.pos {
-                    let distance = pos.geodesic_distance(&game.pos);
-                    let gain = 1.0 / (distance + 2.0) * 20.0;
-                    *score += gain as f32 * UPDATE_INTERVAL.as_secs_f32();
-                }
-            }
+        if let Some(game) = &game {
+            game.do_send(Resume { id, outbox });
         }
 
-        let ended = Instant::now().duration_since(start) >= game.length;
-        let time_left = game.length.as_secs() - Instant::now().duration_since(start).as_secs();
+        Some((id, game))
+    }
 
-        let update = ServerEvent::ScoreUpdate {
-            seconds_left: time_left,
-            scores: scores.clone(),
+    fn set_pos(&mut self, player_id: i64, pos: Point) -> Option<ServerEvent> {
+        let player = match self.players.get_mut(&player_id) {
+            Some(connection) => &mut connection.player,
+            None => return Self::error(ErrorCode::PlayerNotFound),
         };
 
-        self.broadcast(game_id, update, None);
-
-        if ended {
-            self.end_game(ctx, game_id);
-            return;
-        }
+        player.pos = Some(pos);
+        player.moved = self.clock.now();
+        println!("{} moved to {:?}", player.name, pos);
+        None
     }
 }
 
+// Events from players that aren't in a game
 impl Handler<ClientMessage> for GameServer {
     type Result = MessageResult<ClientMessage>;
 
-    fn handle(&mut self, msg: ClientMessage, ctx: &mut Context<Self>) -> Self::Result {
+    fn handle(&mut self, msg: ClientMessage, _: &mut Context<Self>) -> Self::Result {
+        if !msg.event.is_finite() {
+            return MessageResult(Self::error(ErrorCode::InvalidMessage));
+        }
+
         let response = match msg.event {
-            ClientEvent::Connect { .. } => Self::error("Connect should be handled with Handler<Connect>"),
-            ClientEvent::Chat { message } => self.chat(msg.sender, message),
-            ClientEvent::JoinGame { game } => self.join(msg.sender, game),
-            ClientEvent::LeaveGame => self.leave(ctx, msg.sender),
-            ClientEvent::CreateGame { x, y, minutes } => self.create(msg.sender, Point::new(x, y), minutes),
-            ClientEvent::StartGame => self.start(ctx, msg.sender),
+            ClientEvent::Connect { .. } => Self::error(ErrorCode::AlreadyConnected),
             ClientEvent::UpdatePosition { x, y } => self.set_pos(msg.sender, Point::new(x, y)),
-            ClientEvent::TagPlayer { player } => self.tag(msg.sender, player),
+            _ => Self::error(ErrorCode::NotInGame),
         };
 
-        return MessageResult(response);
+        MessageResult(response)
     }
 }
 
-// Message handlers
+impl Handler<NewGame> for GameServer {
+    type Result = MessageResult<NewGame>;
 
-impl GameServer {
-    fn join(&mut self, player_id: i64, game_id: u16) -> Option<ServerEvent> {
-        let name = match self.get_player(player_id) {
-            Ok(value) => value,
-            Err(value) => return value,
-        }.name.clone();
-
-        if self.find_game(player_id).is_some() {
-            return Self::error("Already in a game");
+    fn handle(&mut self, msg: NewGame, ctx: &mut Context<Self>) -> Self::Result {
+        if self.current_game(msg.player).is_some() {
+            return MessageResult(Err(ServerEvent::error(ErrorCode::AlreadyInGame)));
         }
 
-        if let Some(game) = self.games.get_mut(&game_id) {
-            match game.state {
-                GameState::Waiting => {
-                    let players = game.players.iter().map(|id| {
-                        (*id, self.players.get(id).unwrap().name.clone())
-                    }).collect();
-
-                    let event = ServerEvent::JoinedGame { 
-                        players,
-                        id: game_id,
-                        x: game.pos.x(),
-                        y: game.pos.y(),
-                        host: game.host
-                    };
-
-                    game.players.push(player_id);
-
-                    self.broadcast(
-                        game_id, 
-                        ServerEvent::PlayerJoined { 
-                            name,
-                            id: player_id,
-                        }, 
-                        Some(player_id)
-                    );
-
-                    return Some(event);
-                },
-                GameState::Playing { .. } => return Self::error("Game already started"),
-                GameState::Ended { .. } => return Self::error("Game already ended"),
-            };
+        let host = match self.players.get(&msg.player) {
+            Some(player) => player.clone(),
+            None => return MessageResult(Err(ServerEvent::error(ErrorCode::PlayerNotFound))),
+        };
+
+        if !(msg.x.is_finite() && msg.y.is_finite() && msg.radius.is_none_or(f64::is_finite)) {
+            return MessageResult(Err(ServerEvent::error(ErrorCode::InvalidMessage)));
         }
-        
-        Self::error("Game does not exist")
-    }
 
-    fn create(&mut self, host_id: i64, pos: Point<f64>, minutes: u64) -> Option<ServerEvent> {
-        if self.find_game(host_id).is_some() {
-            return Self::error("Already in a game");
+        match msg.radius {
+            Some(radius) if !(MIN_PLAY_AREA_RADIUS..=MAX_PLAY_AREA_RADIUS).contains(&radius) => {
+                return MessageResult(Err(ServerEvent::error(ErrorCode::InvalidPlayArea)));
+            },
+            None if msg.power_ups => return MessageResult(Err(ServerEvent::error(ErrorCode::PlayAreaRequired))),
+            _ => (),
         }
 
         let id = generate_id(&mut self.rng, &self.games);
+        let (player, x, y, tag_mode) = (msg.player, msg.x, msg.y, msg.tag_mode);
+
+        let settings = Settings { host: player, x, y, minutes: msg.minutes, tag_mode, power_ups: msg.power_ups, radius: msg.radius };
+        let seed = self.rng.gen();
+        let mut images: Arc<dyn Images> = Arc::new(self.images.clone());
+
+        // only games that leave something behind to download get a token
+        let download_token = (self.replays.is_some() || self.tracks.is_some()).then(random_token);
+
+        let recorder = self.replays.as_ref().zip(download_token.as_deref()).and_then(|(replays, token)| {
+            let header = replay::Header {
+                version: replay::FORMAT_VERSION,
+                game: id,
+                seed,
+                settings: settings.clone(),
+                host: replay::RecordedPlayer::new(&host.player, self.clock.now()),
+            };
 
-        self.games.insert(id, Game::new(host_id, pos, Duration::from_secs(minutes * 60)));
-        println!("Created game with id {} at lat {}, lng {}", id, pos.x(), pos.y());    
-        Some(ServerEvent::JoinedGame { id, x: pos.x(), y: pos.y(), players: vec![], host: host_id })
-    }
+            match replays.create(&header, token, self.clock.now()) {
+                Ok(recorder) => Some(recorder),
+                Err(err) => {
+                    println!("Failed to start recording game {}: {}", id, err);
+                    None
+                },
+            }
+        });
 
-    fn chat(&mut self, player_id: i64, message: String) -> Option<ServerEvent> {
-        self.broadcast_if(self.find_game(player_id), ServerEvent::Chat { message, sender: player_id }, None);
-        None
-    }
+        if let Some(recorder) = &recorder {
+            images = recorder.images(images);
+        }
 
-    fn leave(&mut self, ctx: &mut Context<GameServer>, player_id: i64) -> Option<ServerEvent> {
-        if let Some(game_id) = self.find_game(player_id) {
-            if let Some(game) = self.games.get_mut(&game_id) {
-                let mut new_host = game.host;
-                game.players.retain(|&id| id != player_id);
-
-                if let GameState::Playing { ref mut seeker, .. } = &mut game.state {
-                    if game.players.len() < 2 {
-                        self.end_game(ctx, game_id);
-                    } else if *seeker == player_id {
-                        *seeker = *game.players.choose(&mut self.rng).unwrap();
-                    }
-                } else if game.players.is_empty() {
-                    self.cancel_game(ctx, game_id);
-                } else if game.host == player_id {
-                    game.host = game.players[0];
-                    new_host = game.host;
-                }
-                
-                self.broadcast(game_id, ServerEvent::PlayerLeft { id: player_id, new_host }, Some(player_id));
-                return Some(ServerEvent::LeftGame);
-            }
+        let rules = rules::Game::new(id, settings, host.player, images, self.word_filter.clone(), StdRng::seed_from_u64(seed));
+        let mut game = Game::new(ctx.address(), self.clock.clone(), rules, player, host.outbox, recorder, self.tracks.clone());
+
+        if let Some(token) = download_token {
+            game = game.with_download_token(token);
         }
 
-        Self::error("Could not leave game")
+        let arbiter = &self.arbiters[id as usize % self.arbiters.len()];
+        let game = Game::start_in_arbiter(arbiter, |_| game);
+
+        self.games.insert(id, game.clone());
+        self.player_games.insert(player, id);
+        println!("Created game with id {} at lat {}, lng {}", id, x, y);
+
+        let event = ServerEvent::JoinedGame { id, x, y, players: vec![], host: player, tag_mode, chat: vec![] };
+        MessageResult(Ok((game, event)))
     }
+}
 
-    fn start(&mut self, ctx: &mut Context<GameServer>, player_id: i64) -> Option<ServerEvent> {
-        if let Some(game_id) = self.find_game(player_id) {
-            if let Some(game) = self.games.get_mut(&game_id) {
-                if game.host != player_id {
-                    return Self::error("Only the host can start the game");
-                }
-
-                if game.players.len() < 2 {
-                    return Self::error("Not enough players to start the game");
-                }
-
-                let scores = game.players.iter().map(|&id| (id, 0.0)).collect();
-                let seeker = *game.players.choose(&mut self.rng).unwrap();
-                game.state = GameState::Playing { 
-                    handle: ctx.run_interval(
-                        UPDATE_INTERVAL,
-                        move |act, ctx| {
-                            act.update_game(ctx, game_id);
-                        }
-                    ),
-                    seeker: seeker,
-                    start: Instant::now(),
-                    scores
-                };
-
-                self.broadcast(game_id, ServerEvent::GameStarted { seeker }, None);
-                return None;
-            }
+impl Handler<LookupGame> for GameServer {
+    type Result = MessageResult<LookupGame>;
+
+    fn handle(&mut self, msg: LookupGame, _: &mut Context<Self>) -> Self::Result {
+        if self.current_game(msg.player).is_some() {
+            return MessageResult(Err(ServerEvent::error(ErrorCode::AlreadyInGame)));
         }
 
-        Self::error("Could not start game")
+        let player = match self.players.get(&msg.player) {
+            Some(player) => player.clone(),
+            None => return MessageResult(Err(ServerEvent::error(ErrorCode::PlayerNotFound))),
+        };
+
+        self.purge_dead_game(msg.game);
+
+        match self.games.get(&msg.game) {
+            Some(game) => MessageResult(Ok((game.clone(), player))),
+            None => MessageResult(Err(ServerEvent::error(ErrorCode::GameNotFound))),
+        }
     }
+}
 
-    fn set_pos(&mut self, player_id: i64, pos: Point) -> Option<ServerEvent> {
-        let player = match self.get_player_mut(player_id) {
-            Ok(value) => value,
-            Err(value) => return value,
-        };
+impl Handler<PlayerGame> for GameServer {
+    type Result = ();
 
-        player.pos = Some(pos);
-        println!("{} moved to {:?}", player.name, pos);
-        None
+    fn handle(&mut self, msg: PlayerGame, _: &mut Context<Self>) -> Self::Result {
+        match msg.game {
+            Some(game) if self.players.contains_key(&msg.player) => {
+                self.player_games.insert(msg.player, game);
+            },
+            Some(_) => (),
+            None => {
+                self.player_games.remove(&msg.player);
+            },
+        }
     }
+}
 
-    fn tag(&mut self, player_id: i64, other_id: i64) -> Option<ServerEvent> {
-        if let Some(game_id) = self.find_game(player_id) {
-            if let Some(game) = self.games.get_mut(&game_id) {
-                if let GameState::Playing { ref mut seeker, .. } = game.state {
-                    if player_id != *seeker {
-                        return Self::error("Only the seeker can tag");
-                    }
-
-                    if game.players.contains(&other_id) {
-                        *seeker = other_id;
-                        self.broadcast(game_id, ServerEvent::PlayerTagged { tagger: player_id, tagged: other_id }, None);
-                        return None;
-                    } 
-                }
-            }
+impl Handler<GameGone> for GameServer {
+    type Result = ();
+
+    fn handle(&mut self, msg: GameGone, _: &mut Context<Self>) -> Self::Result {
+        self.current_game(msg.player);
+    }
+}
+
+impl Handler<GameClosed> for GameServer {
+    type Result = ();
+
+    fn handle(&mut self, msg: GameClosed, _: &mut Context<Self>) -> Self::Result {
+        for player in msg.players {
+            self.player_games.remove(&player);
         }
 
-        Self::error("Could not tag player")
+        self.games.remove(&msg.id);
+        self.images.remove_game(msg.id);
     }
 }
 
@@ -367,12 +321,60 @@ impl Handler<Disconnect> for GameServer {
     type Result = ();
 
     fn handle(&mut self, msg: Disconnect, ctx: &mut Context<Self>) -> Self::Result {
-        if let Some(player) = self.players.get_mut(&msg.id) {
-            println!("{} disconnected", player.name);
+        if !self.is_connected_with(msg.id, &msg.outbox) {
+            return;
+        }
 
-            self.leave(ctx, msg.id);
-            self.players.remove(&msg.id);
+        if self.find_game(msg.id).is_none() {
+            return self.remove_player(msg.id);
         }
+
+        // players in a game get a while to come back with their token
+        msg.outbox.park();
+        ctx.run_later(RESUME_TIMEOUT, move |act, _| {
+            if !act.is_connected_with(msg.id, &msg.outbox) {
+                return;
+            }
+
+            let game = act.find_game(msg.id).and_then(|game| act.games.get(&game));
+
+            if let Some(game) = game {
+                game.do_send(ClientMessage { sender: msg.id, event: ClientEvent::LeaveGame });
+            }
+
+            act.remove_player(msg.id);
+        });
+    }
+}
+
+impl Handler<FindGame> for GameServer {
+    type Result = Option<u16>;
+
+    fn handle(&mut self, msg: FindGame, _: &mut Context<Self>) -> Self::Result {
+        self.find_game(*self.tokens.get(&msg.token)?)
+    }
+}
+
+impl Handler<GetMetrics> for GameServer {
+    type Result = MessageResult<GetMetrics>;
+
+    fn handle(&mut self, _: GetMetrics, _: &mut Context<Self>) -> Self::Result {
+        let mut metrics = Metrics { players: self.players.len(), games: self.games.len(), ..Default::default() };
+
+        for connection in self.players.values() {
+            let depth = connection.outbox.depth();
+
+            metrics.queued_events += depth;
+            metrics.max_queue_depth = metrics.max_queue_depth.max(depth);
+
+            if connection.outbox.is_closed() {
+                metrics.slow_clients += 1;
+            } else if depth > MAX_QUEUE_DEPTH {
+                metrics.backed_up_clients += 1;
+            }
+        }
+
+        MessageResult(metrics)
     }
 }
 
@@ -380,17 +382,21 @@ impl Handler<Connect> for GameServer {
     type Result = MessageResult<Connect>;
 
     fn handle(&mut self, msg: Connect, _: &mut Context<Self>) -> Self::Result {
+        if let Some(token) = msg.resume {
+            if let Some((id, game)) = self.resume(&token, msg.outbox.clone()) {
+                return MessageResult((id, token, game));
+            }
+        }
+
         let id = generate_id(&mut self.rng, &self.players);
         println!("{} connected", msg.name);
-        
-        let player = Player {
-            name: msg.name,
-            addr: msg.addr,
-            pos: None,
-        };
 
-        self.players.insert(id, player);
+        let player = Player::new(msg.name, msg.score_deltas, self.clock.now());
+        self.players.insert(id, Connection { outbox: msg.outbox, player });
+
+        let token = generate_token(&self.tokens);
+        self.tokens.insert(token.clone(), id);
 
-        MessageResult(id)
+        MessageResult((id, token, None))
     }
-}
\ No newline at end of file
+}
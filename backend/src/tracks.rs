@@ -0,0 +1,333 @@
+use std::{
+    collections::VecDeque,
+    fmt::Write,
+    fs,
+    io,
+    path::PathBuf,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use actix_web::{get, web, HttpResponse};
+use geo::{GeodesicDestination, Point};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use hide_and_seek_core::game as rules;
+
+use crate::archive::{self, Archive, DownloadQuery};
+
+const EXTENSION: &str = "json";
+const PLAY_AREA_VERTICES: usize = 64;
+// older games stay downloadable but no longer count toward the heatmap
+const MAX_INDEXED_GAMES: usize = 1000;
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct TrackPoint {
+    // milliseconds since the unix epoch
+    pub time: u64,
+    pub x: f64,
+    pub y: f64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PlayerTrack {
+    pub id: i64,
+    pub name: String,
+    pub points: Vec<TrackPoint>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TagPoint {
+    pub time: u64,
+    pub tagger: i64,
+    pub tagged: i64,
+    pub pos: Option<(f64, f64)>,
+}
+
+// Everything worth drawing after a game, with wall clock times
+#[derive(Serialize, Deserialize)]
+pub struct GameTracks {
+    pub game: u16,
+    pub center: (f64, f64),
+    pub radius: Option<f64>,
+    pub players: Vec<PlayerTrack>,
+    pub tags: Vec<TagPoint>,
+}
+
+// Edges in degrees
+#[derive(Clone, Copy)]
+pub struct Bounds {
+    pub west: f64,
+    pub south: f64,
+    pub east: f64,
+    pub north: f64,
+}
+
+impl Bounds {
+    fn around(x: f64, y: f64) -> Self {
+        Self { west: x, south: y, east: x, north: y }
+    }
+
+    fn extend(self, x: f64, y: f64) -> Self {
+        Self { west: self.west.min(x), south: self.south.min(y), east: self.east.max(x), north: self.north.max(y) }
+    }
+
+    pub fn overlaps(&self, other: &Bounds) -> bool {
+        self.west <= other.east && other.west <= self.east && self.south <= other.north && other.south <= self.north
+    }
+}
+
+// Turns the rules' instants into wall clock time
+#[derive(Clone, Copy)]
+pub struct WallClock {
+    instant: Instant,
+    time: SystemTime,
+}
+
+impl WallClock {
+    pub fn new(now: Instant) -> Self {
+        Self { instant: now, time: SystemTime::now() }
+    }
+
+    fn millis(&self, at: Instant) -> u64 {
+        let time = match at.checked_duration_since(self.instant) {
+            Some(after) => self.time + after,
+            None => self.time - self.instant.duration_since(at),
+        };
+
+        time.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+    }
+}
+
+impl GameTracks {
+    pub fn new(game: &rules::Game, clock: WallClock) -> Self {
+        let point = |at, pos: Point| TrackPoint { time: clock.millis(at), x: pos.x(), y: pos.y() };
+
+        let players = game.tracks().iter().map(|(&id, track)| PlayerTrack {
+            id,
+            name: track.name.clone(),
+            points: track.points.iter().map(|&(at, pos)| point(at, pos)).collect(),
+        }).collect();
+
+        let tags = game.tags().iter().map(|tag| TagPoint {
+            time: clock.millis(tag.at),
+            tagger: tag.tagger,
+            tagged: tag.tagged,
+            pos: tag.pos.map(|pos| (pos.x(), pos.y())),
+        }).collect();
+
+        Self { game: game.id(), center: (game.center().x(), game.center().y()), radius: game.radius(), players, tags }
+    }
+
+    // everywhere somebody went or got tagged, None if nobody moved at all
+    pub fn bounds(&self) -> Option<Bounds> {
+        let points = self.players.iter().flat_map(|track| track.points.iter().map(|point| (point.x, point.y)));
+        let tags = self.tags.iter().filter_map(|tag| tag.pos);
+
+        points.chain(tags).fold(None, |bounds, (x, y)| match bounds {
+            Some(bounds) => Some(Bounds::extend(bounds, x, y)),
+            None => Some(Bounds::around(x, y)),
+        })
+    }
+
+    pub fn gpx(&self, player: i64) -> Option<String> {
+        let track = self.players.iter().find(|track| track.id == player)?;
+        let mut gpx = String::new();
+
+        writeln!(gpx, r#"<?xml version="1.0" encoding="UTF-8"?>"#).unwrap();
+        writeln!(gpx, r#"<gpx version="1.1" creator="hide_and_seek" xmlns="http://www.topografix.com/GPX/1/1">"#).unwrap();
+
+        for tag in self.tags.iter().filter(|tag| tag.tagger == player || tag.tagged == player) {
+            if let Some((x, y)) = tag.pos {
+                let name = if tag.tagger == player { "Tagged someone" } else { "Got tagged" };
+                writeln!(gpx, r#"  <wpt lat="{}" lon="{}"><time>{}</time><name>{}</name></wpt>"#, y, x, timestamp(tag.time), name).unwrap();
+            }
+        }
+
+        writeln!(gpx, "  <trk>").unwrap();
+        writeln!(gpx, "    <name>{}</name>", escape(&track.name)).unwrap();
+        writeln!(gpx, "    <trkseg>").unwrap();
+
+        for point in &track.points {
+            writeln!(gpx, r#"      <trkpt lat="{}" lon="{}"><time>{}</time></trkpt>"#, point.y, point.x, timestamp(point.time)).unwrap();
+        }
+
+        writeln!(gpx, "    </trkseg>").unwrap();
+        writeln!(gpx, "  </trk>").unwrap();
+        writeln!(gpx, "</gpx>").unwrap();
+
+        Some(gpx)
+    }
+
+    pub fn geojson(&self) -> Value {
+        let mut features = vec![json!({
+            "type": "Feature",
+            "geometry": { "type": "Point", "coordinates": [self.center.0, self.center.1] },
+            "properties": { "kind": "center", "game": self.game },
+        })];
+
+        // only drawn if the host marked one out
+        if let Some(radius) = self.radius {
+            let center = Point::new(self.center.0, self.center.1);
+            let mut area: Vec<[f64; 2]> = (0..PLAY_AREA_VERTICES).map(|vertex| {
+                let pos = center.geodesic_destination(vertex as f64 * 360.0 / PLAY_AREA_VERTICES as f64, radius);
+                [pos.x(), pos.y()]
+            }).collect();
+            area.push(area[0]);
+
+            features.push(json!({
+                "type": "Feature",
+                "geometry": { "type": "Polygon", "coordinates": [area] },
+                "properties": { "kind": "play_area", "radius": radius },
+            }));
+        }
+
+        for track in self.players.iter().filter(|track| !track.points.is_empty()) {
+            let coordinates: Vec<[f64; 2]> = track.points.iter().map(|point| [point.x, point.y]).collect();
+
+            // a line needs two positions
+            let geometry = match coordinates.len() {
+                1 => json!({ "type": "Point", "coordinates": coordinates[0] }),
+                _ => json!({ "type": "LineString", "coordinates": coordinates }),
+            };
+
+            features.push(json!({
+                "type": "Feature",
+                "geometry": geometry,
+                "properties": {
+                    "kind": "track",
+                    "player": track.id,
+                    "name": track.name,
+                    "coordTimes": track.points.iter().map(|point| timestamp(point.time)).collect::<Vec<_>>(),
+                },
+            }));
+        }
+
+        for tag in &self.tags {
+            if let Some((x, y)) = tag.pos {
+                features.push(json!({
+                    "type": "Feature",
+                    "geometry": { "type": "Point", "coordinates": [x, y] },
+                    "properties": { "kind": "tag", "tagger": tag.tagger, "tagged": tag.tagged, "time": timestamp(tag.time) },
+                }));
+            }
+        }
+
+        json!({ "type": "FeatureCollection", "features": features })
+    }
+}
+
+fn timestamp(millis: u64) -> String {
+    let time = OffsetDateTime::UNIX_EPOCH + Duration::from_millis(millis);
+    time.format(&Rfc3339).unwrap_or_default()
+}
+
+fn escape(text: &str) -> String {
+    text.chars().fold(String::new(), |mut escaped, c| {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+        escaped
+    })
+}
+
+struct Indexed {
+    millis: u64,
+    bounds: Bounds,
+    tracks: Arc<GameTracks>,
+}
+
+// The newest games are kept in memory, so the heatmap doesn't have to read
+// every file each time somebody asks for it.
+#[derive(Clone)]
+pub struct TrackStore {
+    archive: Archive,
+    index: Arc<RwLock<VecDeque<Indexed>>>,
+}
+
+fn read(path: PathBuf) -> Option<GameTracks> {
+    serde_json::from_slice(&fs::read(path).ok()?).ok()
+}
+
+impl TrackStore {
+    // unreadable files are skipped rather than failing everything
+    pub fn new(root: impl Into<PathBuf>) -> io::Result<Self> {
+        let archive = Archive::new(root, EXTENSION)?;
+        let mut paths = archive.paths();
+        let skip = paths.len().saturating_sub(MAX_INDEXED_GAMES);
+
+        let index = paths.drain(skip..)
+            .filter_map(|(millis, path)| {
+                let tracks = read(path)?;
+                Some(Indexed { millis, bounds: tracks.bounds()?, tracks: Arc::new(tracks) })
+            })
+            .collect();
+
+        Ok(Self { archive, index: Arc::new(RwLock::new(index)) })
+    }
+
+    pub fn with_retention(mut self, retention: Duration) -> Self {
+        self.archive = self.archive.with_retention(retention);
+        self
+    }
+
+    pub fn save(&self, tracks: GameTracks, token: &str) -> io::Result<()> {
+        fs::write(self.archive.path(tracks.game, token), serde_json::to_vec(&tracks)?)?;
+
+        let cutoff = self.archive.cutoff();
+        let mut index = self.index.write().unwrap();
+        index.retain(|indexed| indexed.millis > cutoff);
+
+        if let Some(bounds) = tracks.bounds() {
+            index.push_back(Indexed { millis: archive::now_millis(), bounds, tracks: Arc::new(tracks) });
+        }
+
+        while index.len() > MAX_INDEXED_GAMES {
+            index.pop_front();
+        }
+
+        Ok(())
+    }
+
+    pub fn find(&self, game: u16, token: &str) -> Option<GameTracks> {
+        read(self.archive.find(game, token)?)
+    }
+
+    pub fn prune(&self) {
+        self.archive.prune();
+    }
+
+    // indexed games that went anywhere inside the bounds
+    pub fn near(&self, bounds: &Bounds) -> Vec<Arc<GameTracks>> {
+        let cutoff = self.archive.cutoff();
+
+        self.index.read().unwrap().iter()
+            .filter(|indexed| indexed.millis > cutoff && indexed.bounds.overlaps(bounds))
+            .map(|indexed| indexed.tracks.clone())
+            .collect()
+    }
+}
+
+#[get("/tracks/{game}")]
+async fn download_geojson(path: web::Path<u16>, query: web::Query<DownloadQuery>, tracks: web::Data<TrackStore>) -> HttpResponse {
+    match tracks.find(path.into_inner(), &query.token) {
+        Some(tracks) => HttpResponse::Ok().content_type("application/geo+json").body(tracks.geojson().to_string()),
+        None => HttpResponse::NotFound().body("No tracks for this game"),
+    }
+}
+
+#[get("/tracks/{game}/{player}")]
+async fn download_gpx(path: web::Path<(u16, i64)>, query: web::Query<DownloadQuery>, tracks: web::Data<TrackStore>) -> HttpResponse {
+    let (game, player) = path.into_inner();
+
+    match tracks.find(game, &query.token).and_then(|tracks| tracks.gpx(player)) {
+        Some(gpx) => HttpResponse::Ok().content_type("application/gpx+xml").body(gpx),
+        None => HttpResponse::NotFound().body("No track for this player"),
+    }
+}
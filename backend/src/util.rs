@@ -2,9 +2,9 @@ use std::collections::HashMap;
 use std::hash::Hash;
 
 use rand::prelude::*;
-use rand::{distributions::Standard, rngs::ThreadRng, Rng};
+use rand::{distributions::Standard, Rng};
 
-pub fn generate_id<K, V>(rng: &mut ThreadRng, map: &HashMap<K, V>) -> K where
+pub fn generate_id<K, V>(rng: &mut impl Rng, map: &HashMap<K, V>) -> K where
     Standard: Distribution<K>,
     K: Eq + Hash
 {
@@ -14,4 +14,18 @@ pub fn generate_id<K, V>(rng: &mut ThreadRng, map: &HashMap<K, V>) -> K where
             return id;
         }
     }
+}
+
+// tokens are secrets, so they never come from a seeded rng
+pub fn random_token() -> String {
+    format!("{:032x}", rand::random::<u128>())
+}
+
+pub fn generate_token<V>(map: &HashMap<String, V>) -> String {
+    loop {
+        let token = random_token();
+        if !map.contains_key(&token) {
+            return token;
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,137 @@
+// Not every test file uses all of the harness
+#![allow(dead_code)]
+
+use std::{cell::RefCell, path::PathBuf, time::Duration};
+
+use actix::prelude::*;
+
+use hide_and_seek::{
+    clock::Clock,
+    game::Game,
+    images::ImageStore,
+    message::*,
+    moderation::WordFilter,
+    outbox::{Outbox, Sink},
+    server::GameServer,
+};
+
+pub fn temp_dir(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("hide_and_seek_{}_{}", std::process::id(), name))
+}
+
+pub struct Client {
+    pub id: i64,
+    pub token: String,
+    pub outbox: Outbox,
+    pub seen: RefCell<Vec<ServerEvent>>,
+}
+
+impl Client {
+    // everything sent to the client since the last call
+    pub fn events(&self) -> Vec<ServerEvent> {
+        let mut events = self.seen.take();
+        events.extend(self.outbox.drain().iter().map(|event| event.event().clone()));
+        events
+    }
+}
+
+pub struct Harness {
+    pub server: Addr<GameServer>,
+    pub images: ImageStore,
+    pub clock: Clock,
+}
+
+impl Harness {
+    pub fn new(name: &str, seed: u64) -> Self {
+        Self::with(name, seed, |server| server)
+    }
+
+    // replays and tracks have to be set up before the server starts
+    pub fn with(name: &str, seed: u64, setup: impl FnOnce(GameServer) -> GameServer) -> Self {
+        let images = ImageStore::new(temp_dir(name)).unwrap();
+        let clock = Clock::manual();
+        let server = setup(GameServer::with_clock(images.clone(), WordFilter::default(), clock.clone(), seed)).start();
+
+        Self { server, images, clock }
+    }
+
+    pub async fn connect(&self, name: &str) -> Client {
+        self.connect_with(name, false).await
+    }
+
+    pub async fn connect_with(&self, name: &str, score_deltas: bool) -> Client {
+        let outbox = Outbox::new(Sink.start().recipient()).with_clock(self.clock.clone());
+        let connect = Connect { outbox: outbox.clone(), name: name.to_string(), score_deltas, resume: None };
+        let (id, token, _) = self.server.send(connect).await.unwrap();
+
+        Client { id, token, outbox, seen: RefCell::default() }
+    }
+
+    pub async fn create(&self, host: &Client, minutes: u64, tag_mode: TagMode) -> (Addr<Game>, u16) {
+        let created = self.server.send(NewGame { player: host.id, x: 0.0, y: 0.0, minutes, tag_mode, power_ups: false, radius: None });
+
+        match created.await.unwrap() {
+            Ok((game, ServerEvent::JoinedGame { id, .. })) => (game, id),
+            _ => panic!("game wasn't created"),
+        }
+    }
+
+    pub async fn join(&self, player: &Client, game: u16) -> Option<ServerEvent> {
+        let (addr, info) = self.server.send(LookupGame { player: player.id, game }).await.unwrap().unwrap();
+        addr.send(Join { id: player.id, connection: info }).await.unwrap()
+    }
+
+    // a started game with the host and one guest, returns (seeker, hider)
+    pub async fn start(&self, minutes: u64, tag_mode: TagMode) -> (Addr<Game>, u16, Client, Client) {
+        let host = self.connect("host").await;
+        let guest = self.connect("guest").await;
+
+        let (game, id) = self.create(&host, minutes, tag_mode).await;
+        self.join(&guest, id).await;
+        assert!(send(&game, &host, ClientEvent::StartGame).await.is_none());
+
+        let events = host.events();
+        let seeker = match events.iter().find(|event| matches!(event, ServerEvent::GameStarted { .. })) {
+            Some(ServerEvent::GameStarted { seeker }) => *seeker,
+            _ => panic!("game didn't start"),
+        };
+
+        host.seen.replace(events);
+
+        match seeker == host.id {
+            true => (game, id, host, guest),
+            false => (game, id, guest, host),
+        }
+    }
+
+    pub async fn advance(&self, game: &Addr<Game>, seconds: u64) {
+        for _ in 0..seconds {
+            self.clock.advance(Duration::from_secs(1));
+            game.send(Tick).await.unwrap();
+        }
+    }
+
+    // ticks one second at a time, sending whatever `each` returns for that
+    // second first, and stops early once the game closes
+    pub async fn run_out(&self, game: &Addr<Game>, seconds: u64, mut each: impl FnMut(u64) -> Vec<(i64, ClientEvent)>) {
+        for second in 1..=seconds {
+            self.clock.advance(Duration::from_secs(1));
+
+            for (sender, event) in each(second) {
+                game.send(ClientMessage { sender, event }).await.unwrap();
+            }
+
+            if game.send(Tick).await.is_err() {
+                return;
+            }
+        }
+    }
+
+    pub async fn find_game(&self, player: &Client) -> Option<u16> {
+        self.server.send(FindGame { token: player.token.clone() }).await.unwrap()
+    }
+}
+
+pub async fn send(game: &Addr<Game>, player: &Client, event: ClientEvent) -> Option<ServerEvent> {
+    game.send(ClientMessage { sender: player.id, event }).await.unwrap()
+}
@@ -0,0 +1,85 @@
+mod common;
+
+use std::time::Duration;
+
+use hide_and_seek::{
+    heatmap::Grid,
+    tracks::{Bounds, GameTracks, PlayerTrack, TagPoint, TrackPoint, TrackStore},
+};
+
+use common::temp_dir;
+
+const METRES: f64 = 1.0 / 111_320.0;
+
+fn point(time: u64, east: f64, north: f64) -> TrackPoint {
+    TrackPoint { time, x: east * METRES, y: north * METRES }
+}
+
+fn game(players: Vec<(i64, Vec<TrackPoint>)>, tags: Vec<(f64, f64)>) -> GameTracks {
+    GameTracks {
+        game: 1,
+        center: (0.0, 0.0),
+        radius: None,
+        players: players.into_iter().map(|(id, points)| PlayerTrack { id, name: id.to_string(), points }).collect(),
+        tags: tags.into_iter().map(|(east, north)| TagPoint { time: 0, tagger: 1, tagged: 2, pos: Some((east * METRES, north * METRES)) }).collect(),
+    }
+}
+
+#[test]
+fn cells_need_enough_distinct_players_and_games() {
+    let grid = Grid { x: 0.0, y: 0.0, radius: 100.0, cell: 50.0 };
+
+    // player 1 enters the busy cell twice, the quiet cell only has two players
+    // and the far away point is outside the grid
+    let games = [
+        game(vec![
+            (1, vec![point(0, 10.0, 10.0), point(1, 20.0, 10.0), point(2, -60.0, 10.0), point(3, 10.0, 10.0), point(4, 900.0, 0.0)]),
+            (2, vec![point(0, 30.0, 30.0), point(1, -70.0, 10.0)]),
+        ], vec![(15.0, 15.0), (-65.0, 10.0)]),
+        // ids are handed out per connection, so the same id in another game
+        // may well be somebody else
+        game(vec![(1, vec![point(0, 40.0, 5.0)]), (4, vec![point(0, 5.0, 40.0)])], vec![]),
+        game(vec![(3, vec![point(0, 45.0, 45.0)])], vec![]),
+    ];
+
+    // four players in two games aren't enough to hide behind
+    assert!(grid.heatmap(&games[..2])["features"].as_array().unwrap().is_empty());
+
+    // and neither is one game, however many played in it
+    let crowd = game((1..=8).map(|id| (id, vec![point(0, 10.0, 10.0)])).collect(), vec![]);
+    assert!(grid.heatmap([&crowd])["features"].as_array().unwrap().is_empty());
+
+    let heatmap = grid.heatmap(&games);
+    let features = heatmap["features"].as_array().unwrap();
+
+    assert_eq!(heatmap["type"], "FeatureCollection");
+    assert_eq!(features.len(), 1);
+    assert_eq!(features[0]["properties"]["visits"], 6);
+    assert_eq!(features[0]["properties"]["tags"], 1);
+
+    let corners = features[0]["geometry"]["coordinates"][0].as_array().unwrap();
+    assert_eq!(corners.len(), 5);
+    assert_eq!(corners[0][0], 0.0);
+    assert!((corners[2][1].as_f64().unwrap() - 50.0 * METRES).abs() < 1e-12);
+}
+
+#[test]
+fn store_keeps_recent_games_at_hand() {
+    let root = temp_dir("heatmap_store");
+    let store = TrackStore::new(&root).unwrap();
+    let grid = Grid { x: 0.0, y: 0.0, radius: 100.0, cell: 50.0 };
+
+    store.save(game(vec![(1, vec![point(0, 10.0, 10.0)])], vec![]), "near").unwrap();
+    store.save(game(vec![(2, vec![point(0, 5000.0, 0.0), point(1, 5100.0, 0.0)])], vec![]), "far").unwrap();
+    // nobody moved, nothing to draw
+    store.save(game(vec![(3, vec![])], vec![]), "still").unwrap();
+
+    assert_eq!(store.near(&grid.bounds()).len(), 1);
+
+    // a restarted server picks up where the last one left off
+    let restarted = TrackStore::new(&root).unwrap();
+    assert_eq!(restarted.near(&grid.bounds()).len(), 1);
+    assert_eq!(restarted.near(&Bounds { west: -1.0, south: -1.0, east: 1.0, north: 1.0 }).len(), 2);
+
+    assert!(restarted.with_retention(Duration::ZERO).near(&grid.bounds()).is_empty());
+}
@@ -0,0 +1,132 @@
+mod common;
+
+use std::io::Cursor;
+
+use actix_web::{http::{header, StatusCode}, test::{call_service, init_service, read_body, read_body_json, TestRequest}, web, App};
+use image::{ImageFormat, RgbImage};
+use serde_json::Value;
+
+use hide_and_seek::{
+    images::{self, ImageError, ImageStore, MAX_IMAGE_SIZE, UPLOAD_LIMIT},
+    message::*,
+};
+
+use common::{temp_dir, Harness};
+
+fn png(width: u32, height: u32) -> Vec<u8> {
+    let mut png = Vec::new();
+    RgbImage::new(width, height).write_to(&mut Cursor::new(&mut png), ImageFormat::Png).unwrap();
+    png
+}
+
+macro_rules! app {
+    ($harness:expr) => {
+        app!($harness, $harness.images)
+    };
+    ($harness:expr, $images:expr) => {
+        init_service(App::new()
+            .app_data(web::Data::new($harness.server.clone()))
+            .app_data(web::Data::new($images.clone()))
+            .app_data(web::PayloadConfig::new(MAX_IMAGE_SIZE))
+            .service(images::upload)
+            .service(images::download)).await
+    };
+}
+
+fn upload(token: &str, body: Vec<u8>) -> TestRequest {
+    TestRequest::post().uri(&format!("/images?token={}", token)).set_payload(body)
+}
+
+#[actix::test]
+async fn players_in_a_game_upload_photos_with_thumbnails() {
+    let harness = Harness::new("images_upload", 5);
+    let (_, game_id, seeker, _) = harness.start(10, TagMode::Photo).await;
+    let app = app!(harness);
+
+    assert_eq!(call_service(&app, upload("guessed", png(4, 4)).to_request()).await.status(), StatusCode::FORBIDDEN);
+
+    let response = call_service(&app, upload(&seeker.token, png(600, 300)).to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let uploaded: Value = read_body_json(response).await;
+    let image = uploaded["image"].as_str().unwrap();
+    assert!(image.ends_with(".png"));
+    assert_eq!(uploaded["url"], format!("/images/{}/{}", game_id, image));
+    assert!(harness.images.exists(game_id, image));
+
+    let response = call_service(&app, TestRequest::get().uri(uploaded["thumbnail"].as_str().unwrap()).to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let thumbnail = image::load_from_memory(&read_body(response).await).unwrap();
+    assert_eq!((thumbnail.width(), thumbnail.height()), (256, 128));
+
+    let response = call_service(&app, TestRequest::get().uri(uploaded["url"].as_str().unwrap()).to_request()).await;
+    assert_eq!(read_body(response).await, png(600, 300));
+
+    // nobody gets out of the game's directory
+    let response = call_service(&app, TestRequest::get().uri(&format!("/images/{}/..%2F..%2Fsecret", game_id)).to_request()).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix::test]
+async fn uploads_must_be_small_png_or_jpeg_images() {
+    let harness = Harness::new("images_rejected", 5);
+    let (_, _, seeker, _) = harness.start(10, TagMode::Photo).await;
+    let app = app!(harness);
+
+    let status = |body| {
+        let request = upload(&seeker.token, body).to_request();
+        async { call_service(&app, request).await.status() }
+    };
+
+    assert_eq!(status(b"GIF89a".to_vec()).await, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    assert_eq!(status(png(64, 64)[..40].to_vec()).await, StatusCode::BAD_REQUEST);
+    assert_eq!(status(png(8193, 1)).await, StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(status(vec![0; MAX_IMAGE_SIZE + 1]).await, StatusCode::PAYLOAD_TOO_LARGE);
+
+    let mut jpeg = Vec::new();
+    RgbImage::new(4, 4).write_to(&mut Cursor::new(&mut jpeg), ImageFormat::Jpeg).unwrap();
+    assert_eq!(status(jpeg).await, StatusCode::OK);
+}
+
+#[actix::test]
+async fn uploads_are_limited_per_player_and_game() {
+    let harness = Harness::new("images_quota", 5);
+    let (_, game_id, seeker, hider) = harness.start(10, TagMode::Photo).await;
+    let images = harness.images.clone().with_quota(20, 1024 * 1024);
+    let app = app!(harness, images);
+
+    for _ in 0..UPLOAD_LIMIT {
+        assert_eq!(call_service(&app, upload(&seeker.token, png(4, 4)).to_request()).await.status(), StatusCode::OK);
+    }
+
+    let response = call_service(&app, upload(&seeker.token, png(4, 4)).to_request()).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(response.headers().contains_key(header::RETRY_AFTER));
+
+    // the others can still upload, until the game is full
+    assert_eq!(call_service(&app, upload(&hider.token, png(4, 4)).to_request()).await.status(), StatusCode::OK);
+
+    let stored = (0..).take_while(|uploader| images.store(game_id, &uploader.to_string(), &png(4, 4)).is_ok()).count();
+    assert_eq!(stored + UPLOAD_LIMIT + 1, 20);
+    assert!(matches!(images.store(game_id, "late", &png(4, 4)), Err(ImageError::GameFull)));
+
+    let response = call_service(&app, upload(&hider.token, png(4, 4)).to_request()).await;
+    assert_eq!(response.status(), StatusCode::INSUFFICIENT_STORAGE);
+
+    // a removed game takes its images and its quota along
+    let image = harness.images.store(game_id + 1, "other", &png(4, 4)).unwrap();
+    harness.images.remove_game(game_id);
+
+    assert!(!temp_dir("images_quota").join(game_id.to_string()).exists());
+    assert!(images.store(game_id, "late", &png(4, 4)).is_ok());
+
+    // bytes count as well as images
+    let small = harness.images.clone().with_quota(20, 100);
+    assert!(matches!(small.store(game_id + 2, "late", &png(64, 64)), Err(ImageError::GameFull)));
+
+    // and a restarted server starts over
+    ImageStore::new(temp_dir("images_quota")).unwrap();
+    assert!(!harness.images.exists(game_id + 1, &image));
+}
+
@@ -0,0 +1,328 @@
+mod common;
+
+use std::{io::Cursor, time::Duration};
+
+use actix::prelude::*;
+use image::{ImageFormat, RgbImage};
+
+use hide_and_seek::{
+    error::ErrorCode,
+    message::*,
+    outbox::{Outbox, Sink, MAX_QUEUE_DEPTH},
+};
+
+use common::{send, Harness};
+
+const SEED: u64 = 2369;
+
+fn error_code(event: Option<ServerEvent>) -> Option<ErrorCode> {
+    match event {
+        Some(ServerEvent::Error { code, .. }) => Some(code),
+        _ => None,
+    }
+}
+
+fn last_scores(events: &[ServerEvent]) -> Option<(f32, u64)> {
+    events.iter().rev().find_map(|event| match event {
+        ServerEvent::ScoreUpdate { scores, seconds_left } => Some((scores.values().copied().fold(0.0, f32::max), *seconds_left)),
+        _ => None,
+    })
+}
+
+fn tagged(events: &[ServerEvent]) -> Option<(i64, i64)> {
+    events.iter().find_map(|event| match event {
+        ServerEvent::PlayerTagged { tagger, tagged } => Some((*tagger, *tagged)),
+        _ => None,
+    })
+}
+
+fn nonce(events: &[ServerEvent]) -> Option<String> {
+    events.iter().find_map(|event| match event {
+        ServerEvent::TagNonce { nonce, .. } => Some(nonce.clone()),
+        _ => None,
+    })
+}
+
+// player and game ids, who became seeker and the hider's first nonce
+async fn seeded_run(seed: u64) -> (u16, i64, i64, Option<String>) {
+    let harness = Harness::new(&format!("seeded_{}", seed), seed);
+    let (_, id, seeker, hider) = harness.start(10, TagMode::Handshake).await;
+
+    (id, seeker.id, hider.id, nonce(&hider.events()))
+}
+
+#[actix::test]
+async fn same_seed_gives_same_game() {
+    let first = seeded_run(SEED).await;
+
+    assert_eq!(first, seeded_run(SEED).await);
+    assert!(first.3.is_some());
+}
+
+#[actix::test]
+async fn hider_scores_until_game_ends() {
+    let harness = Harness::new("scores", SEED);
+    let (game, _, seeker, hider) = harness.start(1, TagMode::Instant).await;
+
+    // the hider stands in the middle of the area, where points come fastest
+    send(&game, &hider, ClientEvent::UpdatePosition { x: 0.0, y: 0.0 }).await;
+    send(&game, &seeker, ClientEvent::UpdatePosition { x: 0.0, y: 0.0 }).await;
+
+    harness.advance(&game, 5).await;
+    assert_eq!(last_scores(&hider.events()), Some((50.0, 55)));
+
+    harness.advance(&game, 54).await;
+    let events = seeker.events();
+    assert_eq!(last_scores(&events), Some((590.0, 1)));
+    assert!(!events.iter().any(|event| matches!(event, ServerEvent::GameEnded { .. })));
+
+    harness.advance(&game, 1).await;
+    let events = seeker.events();
+    assert_eq!(last_scores(&events), Some((600.0, 0)));
+    assert!(matches!(events.last(), Some(ServerEvent::GameEnded { winner, .. }) if *winner == hider.id));
+
+    harness.advance(&game, 5).await;
+    assert!(seeker.events().is_empty());
+    assert_eq!(error_code(send(&game, &hider, ClientEvent::ResyncScores).await), Some(ErrorCode::GameNotStarted));
+}
+
+#[actix::test]
+async fn seeker_gets_no_points() {
+    let harness = Harness::new("seeker_points", SEED);
+    let (game, _, seeker, hider) = harness.start(1, TagMode::Instant).await;
+
+    send(&game, &seeker, ClientEvent::UpdatePosition { x: 0.0, y: 0.0 }).await;
+    harness.advance(&game, 3).await;
+
+    let scores = hider.events().into_iter().rev().find_map(|event| match event {
+        ServerEvent::ScoreUpdate { scores, .. } => Some(scores),
+        _ => None,
+    }).unwrap();
+
+    assert_eq!(scores[&seeker.id], 0.0);
+    assert_eq!(scores[&hider.id], 0.0);
+}
+
+#[actix::test]
+async fn instant_tag_transfers_seeker() {
+    let harness = Harness::new("instant_tag", SEED);
+    let (game, _, seeker, hider) = harness.start(10, TagMode::Instant).await;
+
+    assert_eq!(error_code(send(&game, &hider, ClientEvent::TagPlayer { player: seeker.id, image: None }).await), Some(ErrorCode::NotSeeker));
+    assert!(send(&game, &seeker, ClientEvent::TagPlayer { player: hider.id, image: None }).await.is_none());
+    assert_eq!(tagged(&hider.events()), Some((seeker.id, hider.id)));
+    seeker.events();
+
+    assert!(send(&game, &hider, ClientEvent::TagPlayer { player: seeker.id, image: None }).await.is_none());
+    assert_eq!(tagged(&seeker.events()), Some((hider.id, seeker.id)));
+}
+
+#[actix::test]
+async fn photo_tag_is_disputed_after_timeout() {
+    let harness = Harness::new("photo_tag", SEED);
+    let (game, id, seeker, hider) = harness.start(10, TagMode::Photo).await;
+
+    let mut png = Vec::new();
+    RgbImage::new(4, 4).write_to(&mut Cursor::new(&mut png), ImageFormat::Png).unwrap();
+    let image = harness.images.store(id, &seeker.token, &png).unwrap();
+
+    let response = send(&game, &seeker, ClientEvent::TagPlayer { player: hider.id, image: None }).await;
+    assert_eq!(error_code(response), Some(ErrorCode::PhotoRequired));
+
+    assert!(send(&game, &seeker, ClientEvent::TagPlayer { player: hider.id, image: Some(image.clone()) }).await.is_none());
+    assert!(hider.events().iter().any(|event| matches!(event, ServerEvent::TagPending { .. })));
+
+    harness.advance(&game, 59).await;
+    assert_eq!(tagged(&hider.events()), None);
+
+    harness.advance(&game, 1).await;
+    let events = hider.events();
+    assert_eq!(tagged(&events), None);
+    assert!(events.iter().any(|event| matches!(event, ServerEvent::TagDisputed { tagged, by, .. } if *tagged == hider.id && *by == hider.id)));
+    assert_eq!(error_code(send(&game, &hider, ClientEvent::ConfirmTag).await), Some(ErrorCode::NoPendingTag));
+
+    // the seeker is still the seeker and may try again
+    assert!(send(&game, &seeker, ClientEvent::TagPlayer { player: hider.id, image: Some(image) }).await.is_none());
+}
+
+#[actix::test]
+async fn host_is_promoted_and_empty_game_is_closed() {
+    let harness = Harness::new("host", SEED);
+    let host = harness.connect("host").await;
+    let first = harness.connect("first").await;
+    let second = harness.connect("second").await;
+
+    let (game, id) = harness.create(&host, 10, TagMode::Instant).await;
+    harness.join(&first, id).await;
+    harness.join(&second, id).await;
+    assert_eq!(harness.find_game(&second).await, Some(id));
+
+    // ids are public, only the session token identifies an uploader
+    assert_eq!(harness.server.send(FindGame { token: second.id.to_string() }).await.unwrap(), None);
+
+    let new_host = first.id.min(second.id);
+    assert!(matches!(send(&game, &host, ClientEvent::LeaveGame).await, Some(ServerEvent::LeftGame)));
+    assert_eq!(harness.find_game(&host).await, None);
+
+    // nobody is left to read it
+    let chat = ClientEvent::Chat { message: "bye".to_string(), image: None, channel: ChatChannel::All };
+    assert_eq!(error_code(harness.server.send(ClientMessage { sender: host.id, event: chat }).await.unwrap()), Some(ErrorCode::NotInGame));
+
+    let left = first.events().into_iter().find_map(|event| match event {
+        ServerEvent::PlayerLeft { id, new_host } => Some((id, new_host)),
+        _ => None,
+    });
+    assert_eq!(left, Some((host.id, new_host)));
+
+    let (stays, leaves) = if new_host == first.id { (&first, &second) } else { (&second, &first) };
+    assert_eq!(error_code(send(&game, leaves, ClientEvent::StartGame).await), Some(ErrorCode::NotHost));
+
+    send(&game, leaves, ClientEvent::LeaveGame).await;
+    send(&game, stays, ClientEvent::LeaveGame).await;
+
+    assert_eq!(harness.find_game(stays).await, None);
+    assert_eq!(harness.server.send(GetMetrics).await.unwrap().games, 0);
+}
+
+#[actix::test]
+async fn game_ends_when_too_few_players_remain() {
+    let harness = Harness::new("too_few", SEED);
+    let (game, _, seeker, hider) = harness.start(10, TagMode::Instant).await;
+
+    send(&game, &hider, ClientEvent::UpdatePosition { x: 0.0, y: 0.0 }).await;
+    harness.advance(&game, 2).await;
+    seeker.events();
+
+    send(&game, &hider, ClientEvent::LeaveGame).await;
+
+    let events = seeker.events();
+    assert!(matches!(events.first(), Some(ServerEvent::GameEnded { winner, .. }) if *winner == hider.id));
+    assert!(matches!(events.last(), Some(ServerEvent::PlayerLeft { id, .. }) if *id == hider.id));
+}
+
+#[actix::test]
+async fn play_area_is_up_to_the_host() {
+    let harness = Harness::new("play_area", SEED);
+    let host = harness.connect("host").await;
+
+    let create = |power_ups, radius| {
+        let new_game = NewGame { player: host.id, x: 0.0, y: 0.0, minutes: 10, tag_mode: TagMode::Instant, power_ups, radius };
+        harness.server.send(new_game)
+    };
+    let error = |created: Result<_, ServerEvent>| error_code(created.err());
+
+    assert_eq!(error(create(false, Some(5.0)).await.unwrap()), Some(ErrorCode::InvalidPlayArea));
+    assert_eq!(error(create(true, None).await.unwrap()), Some(ErrorCode::PlayAreaRequired));
+    assert!(create(true, Some(300.0)).await.unwrap().is_ok());
+}
+
+#[actix::test]
+async fn numbers_must_be_finite() {
+    let harness = Harness::new("finite", SEED);
+    let host = harness.connect("host").await;
+
+    let position = ClientMessage { sender: host.id, event: ClientEvent::UpdatePosition { x: f64::NAN, y: 0.0 } };
+    assert_eq!(error_code(harness.server.send(position).await.unwrap()), Some(ErrorCode::InvalidMessage));
+
+    let create = |x, radius| {
+        harness.server.send(NewGame { player: host.id, x, y: 0.0, minutes: 10, tag_mode: TagMode::Instant, power_ups: false, radius })
+    };
+
+    assert_eq!(error_code(create(f64::INFINITY, None).await.unwrap().err()), Some(ErrorCode::InvalidMessage));
+    assert_eq!(error_code(create(0.0, Some(f64::NAN)).await.unwrap().err()), Some(ErrorCode::InvalidMessage));
+
+    let (game, _) = harness.create(&host, 10, TagMode::Instant).await;
+    let response = send(&game, &host, ClientEvent::UpdatePosition { x: 0.0, y: f64::NEG_INFINITY }).await;
+    assert_eq!(error_code(response), Some(ErrorCode::InvalidMessage));
+}
+
+#[actix::test]
+async fn handshake_nonces_expire() {
+    let harness = Harness::new("nonces", SEED);
+    let (game, _, seeker, hider) = harness.start(10, TagMode::Handshake).await;
+
+    assert_eq!(nonce(&seeker.events()), None);
+    let stale = nonce(&hider.events()).unwrap();
+
+    // a fresh nonce is handed out before the old one runs out
+    harness.advance(&game, 30).await;
+    let fresh = nonce(&hider.events()).unwrap();
+    assert_ne!(stale, fresh);
+
+    harness.clock.advance(Duration::from_secs(15));
+    let response = send(&game, &seeker, ClientEvent::TagWithProof { nonce: stale.clone() }).await;
+    assert_eq!(error_code(response), Some(ErrorCode::NonceExpired));
+
+    game.send(Tick).await.unwrap();
+    let response = send(&game, &seeker, ClientEvent::TagWithProof { nonce: stale }).await;
+    assert_eq!(error_code(response), Some(ErrorCode::InvalidNonce));
+
+    assert!(send(&game, &seeker, ClientEvent::TagWithProof { nonce: fresh.clone() }).await.is_none());
+    assert_eq!(tagged(&seeker.events()), Some((seeker.id, hider.id)));
+
+    let response = send(&game, &hider, ClientEvent::TagWithProof { nonce: fresh }).await;
+    assert_eq!(error_code(response), Some(ErrorCode::NonceUsed));
+}
+
+#[actix::test]
+async fn games_that_die_let_go_of_their_players() {
+    let arbiter = Arbiter::new();
+    let handle = arbiter.handle();
+    let harness = Harness::with("dead_game", SEED, |server| server.with_arbiters(vec![handle]));
+    let (game, id, seeker, hider) = harness.start(10, TagMode::Instant).await;
+
+    // the game's thread goes away without the game ever closing
+    arbiter.stop();
+    arbiter.join().unwrap();
+    assert!(!game.connected());
+    assert_eq!(harness.find_game(&seeker).await, Some(id));
+
+    // a session whose message the game didn't take reports it gone
+    harness.server.do_send(GameGone { player: seeker.id });
+    assert_eq!(harness.find_game(&seeker).await, None);
+    assert_eq!(harness.find_game(&hider).await, None);
+
+    let lookup = harness.server.send(LookupGame { player: hider.id, game: id }).await.unwrap();
+    assert!(matches!(lookup, Err(ServerEvent::Error { code: ErrorCode::GameNotFound, .. })));
+
+    let created = harness.server.send(NewGame { player: seeker.id, x: 0.0, y: 0.0, minutes: 10, tag_mode: TagMode::Instant, power_ups: false, radius: None });
+    assert!(created.await.unwrap().is_ok());
+}
+
+#[actix::test]
+async fn resumed_player_catches_up_with_the_game() {
+    let harness = Harness::new("resume", SEED);
+    let (game, id, seeker, hider) = harness.start(10, TagMode::Instant).await;
+
+    let chat = ClientEvent::Chat { message: "brb".to_string(), image: None, channel: ChatChannel::All };
+    assert!(send(&game, &seeker, chat).await.is_none());
+
+    // nobody reads the old connection while a busy game keeps filling it,
+    // but the player keeps their place
+    harness.server.send(Disconnect { id: seeker.id, outbox: seeker.outbox.clone() }).await.unwrap();
+    harness.run_out(&game, 20, |second| {
+        for _ in 0..MAX_QUEUE_DEPTH {
+            seeker.outbox.send(ServerEvent::PlayerMuted { id: hider.id, muted: second % 2 == 0 });
+        }
+        vec![]
+    }).await;
+    assert!(!seeker.outbox.is_closed());
+
+    let outbox = Outbox::new(Sink.start().recipient()).with_clock(harness.clock.clone());
+    let connect = Connect { outbox: outbox.clone(), name: "host".to_string(), score_deltas: false, resume: Some(seeker.token.clone()) };
+    let (resumed, _, addr) = harness.server.send(connect).await.unwrap();
+    assert_eq!(resumed, seeker.id);
+    assert!(addr.is_some());
+
+    // resuming goes through the server's mailbox to the game's
+    game.send(Tick).await.unwrap();
+
+    let events: Vec<ServerEvent> = outbox.drain().iter().map(|event| event.event().clone()).collect();
+    let joined = events.iter().position(|event| matches!(event, ServerEvent::JoinedGame { id: joined, chat, .. }
+        if *joined == id && chat.last().is_some_and(|message| message.message == "brb")));
+    let started = events.iter().position(|event| matches!(event, ServerEvent::GameStarted { seeker: current } if *current == seeker.id));
+    let scores = events.iter().position(|event| matches!(event, ServerEvent::ScoreSnapshot { scores, .. } if scores.contains_key(&hider.id)));
+
+    assert!(joined.is_some() && joined < started && started < scores);
+    assert_eq!(harness.find_game(&seeker).await, Some(id));
+}
@@ -0,0 +1,143 @@
+use std::{
+    collections::HashMap,
+    sync::{atomic::{AtomicUsize, Ordering}, Arc},
+    time::Duration,
+};
+
+use actix::prelude::*;
+
+use hide_and_seek::{
+    clock::Clock,
+    message::*,
+    outbox::{Flush, Outbox, Sink, MAX_QUEUE_DEPTH},
+};
+
+fn chat(message: usize) -> ServerEvent {
+    ServerEvent::Chat { sender: 1, message: message.to_string(), image: None, channel: ChatChannel::All }
+}
+
+fn score(seconds_left: u64) -> ServerEvent {
+    ServerEvent::ScoreUpdate { scores: HashMap::new(), seconds_left }
+}
+
+fn delta(seq: u64) -> ServerEvent {
+    ServerEvent::ScoreDelta { seq, changed: HashMap::new(), seconds_left: 0 }
+}
+
+fn snapshot(seq: u64) -> ServerEvent {
+    ServerEvent::ScoreSnapshot { seq, scores: HashMap::new(), seconds_left: 0 }
+}
+
+fn drained(outbox: &Outbox) -> Vec<ServerEvent> {
+    outbox.drain().iter().map(|event| event.event().clone()).collect()
+}
+
+fn fill(outbox: &Outbox, count: usize) {
+    for message in 0..count {
+        outbox.send(chat(message));
+    }
+}
+
+struct Wakes(Arc<AtomicUsize>);
+
+impl Actor for Wakes {
+    type Context = Context<Self>;
+}
+
+impl Handler<Flush> for Wakes {
+    type Result = ();
+
+    fn handle(&mut self, _: Flush, _: &mut Context<Self>) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[actix::test]
+async fn newer_scores_replace_queued_ones() {
+    let outbox = Outbox::new(Sink.start().recipient());
+
+    outbox.send(score(10));
+    outbox.send(chat(1));
+    outbox.send(score(9));
+
+    let events = drained(&outbox);
+    assert!(matches!(events[..], [ServerEvent::Chat { .. }, ServerEvent::ScoreUpdate { seconds_left: 9, .. }]));
+
+    // a snapshot makes every delta before it useless, but not the other way round
+    outbox.send(delta(1));
+    outbox.send(delta(2));
+    outbox.send(snapshot(2));
+    outbox.send(delta(3));
+
+    let events = drained(&outbox);
+    assert!(matches!(events[..], [ServerEvent::ScoreSnapshot { seq: 2, .. }, ServerEvent::ScoreDelta { seq: 3, .. }]));
+}
+
+#[actix::test]
+async fn session_is_only_woken_for_an_empty_outbox() {
+    let wakes = Arc::new(AtomicUsize::new(0));
+    let outbox = Outbox::new(Wakes(wakes.clone()).start().recipient());
+
+    fill(&outbox, 3);
+    outbox.drain();
+    fill(&outbox, 2);
+
+    actix::clock::sleep(Duration::from_millis(10)).await;
+    assert_eq!(wakes.load(Ordering::SeqCst), 2);
+}
+
+#[actix::test]
+async fn backed_up_client_gets_a_while_to_catch_up() {
+    let clock = Clock::manual();
+    let outbox = Outbox::new(Sink.start().recipient()).with_clock(clock.clone());
+
+    fill(&outbox, MAX_QUEUE_DEPTH + 1);
+    clock.advance(Duration::from_secs(9));
+    outbox.send(chat(0));
+    assert!(!outbox.is_closed());
+
+    // catching up starts the wait over
+    assert_eq!(outbox.drain().len(), MAX_QUEUE_DEPTH + 2);
+    fill(&outbox, MAX_QUEUE_DEPTH + 1);
+    clock.advance(Duration::from_secs(9));
+    outbox.send(chat(0));
+    assert!(!outbox.is_closed());
+
+    clock.advance(Duration::from_secs(1));
+    outbox.send(chat(0));
+    assert!(outbox.is_closed());
+    assert_eq!(outbox.depth(), 0);
+
+    // nothing more is queued for a client that is being disconnected
+    outbox.send(chat(0));
+    assert_eq!(outbox.depth(), 0);
+}
+
+#[actix::test]
+async fn far_behind_client_is_closed_at_once() {
+    let outbox = Outbox::new(Sink.start().recipient()).with_clock(Clock::manual());
+
+    fill(&outbox, MAX_QUEUE_DEPTH * 4);
+    assert!(!outbox.is_closed());
+
+    outbox.send(chat(0));
+    assert!(outbox.is_closed());
+    assert!(outbox.drain().is_empty());
+}
+
+#[actix::test]
+async fn parked_outbox_keeps_the_latest_events_open() {
+    let clock = Clock::manual();
+    let outbox = Outbox::new(Sink.start().recipient()).with_clock(clock.clone());
+
+    outbox.park();
+    fill(&outbox, MAX_QUEUE_DEPTH + 1);
+    clock.advance(Duration::from_secs(30));
+    fill(&outbox, MAX_QUEUE_DEPTH * 4);
+    outbox.send(chat(1000));
+    assert!(!outbox.is_closed());
+
+    let events = drained(&outbox);
+    assert_eq!(events.len(), MAX_QUEUE_DEPTH);
+    assert!(matches!(events.last(), Some(ServerEvent::Chat { message, .. }) if message == "1000"));
+}
@@ -0,0 +1,31 @@
+use hide_and_seek::{message::*, protocol::{self, Encoding, Payload}};
+
+fn v1_text(event: &ServerEvent) -> Option<String> {
+    match protocol::encode(event, 1, Encoding::Json)? {
+        Payload::Text(text) => Some(text.to_string()),
+        Payload::Binary(_) => panic!("json is sent as text"),
+    }
+}
+
+#[test]
+fn v1_clients_can_tell_private_chat_from_public() {
+    let chat = |channel| ServerEvent::Chat { sender: 1, message: "over here".to_string(), image: None, channel };
+
+    assert_eq!(v1_text(&chat(ChatChannel::All)).unwrap(), r#"{"Chat":{"sender":1,"message":"over here"}}"#);
+    assert_eq!(v1_text(&chat(ChatChannel::Team)).unwrap(), r#"{"Chat":{"sender":1,"message":"[team] over here"}}"#);
+    assert_eq!(v1_text(&chat(ChatChannel::Direct(2))).unwrap(), r#"{"Chat":{"sender":1,"message":"[private] over here"}}"#);
+}
+
+#[test]
+fn requests_carry_an_id_next_to_the_event() {
+    let request = ClientRequest::from_json(r#"{"StartGame":null,"request_id":4}"#).unwrap();
+    assert_eq!(request.request_id, Some(4));
+    assert!(matches!(request.event, ClientEvent::StartGame));
+
+    let request = ClientRequest::from_json(r#"{"JoinGame":{"game":12},"request_id":5}"#).unwrap();
+    assert_eq!(request.request_id, Some(5));
+    assert!(matches!(request.event, ClientEvent::JoinGame { game: 12 }));
+
+    let request = ClientRequest::from_json(r#""StartGame""#).unwrap();
+    assert_eq!(request.request_id, None);
+}
@@ -0,0 +1,117 @@
+mod common;
+
+use std::{fs, time::{Duration, Instant}};
+
+use actix_web::{http::StatusCode, test::{call_service, init_service, TestRequest}, web, App};
+
+use hide_and_seek::{
+    message::*,
+    moderation::WordFilter,
+    replay::{self, RecordedCommand, Replay, ReplayStore},
+};
+
+use common::{send, temp_dir, Harness};
+
+#[actix::test]
+async fn recorded_game_replays_to_the_same_outcome() {
+    let replays = ReplayStore::new(temp_dir("replays")).unwrap();
+    let harness = Harness::with("replay_images", 7, |server| server.with_replays(replays.clone()));
+
+    let host = harness.connect("host").await;
+    let guest = harness.connect("guest").await;
+    let third = harness.connect_with("third", true).await;
+
+    let (game, game_id) = harness.create(&host, 1, TagMode::Instant).await;
+    harness.join(&guest, game_id).await;
+    harness.join(&third, game_id).await;
+
+    for (offset, player) in [&host, &guest, &third].into_iter().enumerate() {
+        send(&game, player, ClientEvent::UpdatePosition { x: 0.0001 * offset as f64, y: 0.0 }).await;
+    }
+
+    send(&game, &guest, ClientEvent::Chat { message: "ready?".to_string(), image: None, channel: ChatChannel::All }).await;
+    send(&game, &host, ClientEvent::StartGame).await;
+
+    harness.run_out(&game, 61, |second| match second {
+        20 => vec![(third.id, ClientEvent::LeaveGame)],
+        _ => vec![],
+    }).await;
+
+    let (winner, token) = host.events().into_iter().find_map(|event| match event {
+        ServerEvent::GameEnded { winner, download_token } => Some((Some(winner), download_token.unwrap())),
+        _ => None,
+    }).unwrap();
+
+    // the game id alone doesn't get anyone the replay
+    let app = init_service(App::new().app_data(web::Data::new(replays.clone())).service(replay::download)).await;
+    let download = |token: &str| TestRequest::get().uri(&format!("/replays/{}?token={}", game_id, token)).to_request();
+
+    assert_eq!(call_service(&app, download(&token)).await.status(), StatusCode::OK);
+    assert_eq!(call_service(&app, download("guessed")).await.status(), StatusCode::NOT_FOUND);
+
+    let path = replays.find(game_id, &token).unwrap();
+    let mut replay = Replay::read(&fs::read(path).unwrap()).unwrap();
+    let summary = replay.run(WordFilter::default());
+
+    assert!(summary.mismatches.is_empty(), "replay diverged at entry {}", summary.mismatches[0].index);
+    assert_eq!(summary.winner, winner);
+
+    // a changed command shows up as a mismatch
+    let moved = replay.entries.iter_mut().find_map(|entry| match &mut entry.command {
+        RecordedCommand::Client { event: ClientEvent::UpdatePosition { x, .. }, .. } => Some(x),
+        _ => None,
+    }).unwrap();
+    *moved += 1.0;
+
+    assert!(!replay.run(WordFilter::default()).mismatches.is_empty());
+
+    // expired replays can't be downloaded, even before they are removed
+    let expired = ReplayStore::new(temp_dir("replays")).unwrap().with_retention(Duration::ZERO);
+    assert!(expired.find(game_id, &token).is_none());
+    assert!(replays.find(game_id, &token).is_some());
+
+    expired.prune();
+    assert!(replays.find(game_id, &token).is_none());
+
+    // and old replays make room for new ones
+    replays.create(&replay.header, "old", Instant::now()).unwrap();
+    expired.create(&replay.header, "next", Instant::now()).unwrap();
+
+    assert!(replays.find(game_id, "old").is_none());
+    assert!(replays.find(game_id, "next").is_some());
+}
+
+#[actix::test]
+async fn positions_from_before_joining_are_replayed() {
+    let replays = ReplayStore::new(temp_dir("replays_early")).unwrap();
+    let harness = Harness::with("replay_early_images", 11, |server| server.with_replays(replays.clone()));
+
+    let host = harness.connect("host").await;
+    let guest = harness.connect("guest").await;
+
+    // nobody moves once they are in the game
+    for player in [&host, &guest] {
+        let position = ClientMessage { sender: player.id, event: ClientEvent::UpdatePosition { x: 0.0, y: 0.0 } };
+        harness.server.send(position).await.unwrap();
+    }
+
+    harness.clock.advance(Duration::from_secs(5));
+    let (game, game_id) = harness.create(&host, 1, TagMode::Instant).await;
+    harness.join(&guest, game_id).await;
+    send(&game, &host, ClientEvent::StartGame).await;
+
+    harness.run_out(&game, 61, |_| vec![]).await;
+
+    let (winner, token, scored) = host.events().into_iter().fold((None, None, 0.0), |(winner, token, scored), event| match event {
+        ServerEvent::GameEnded { winner, download_token } => (Some(winner), download_token, scored),
+        ServerEvent::ScoreUpdate { scores, .. } => (winner, token, scores.values().copied().fold(scored, f32::max)),
+        _ => (winner, token, scored),
+    });
+    assert!(scored > 0.0);
+
+    let replay = Replay::read(&fs::read(replays.find(game_id, &token.unwrap()).unwrap()).unwrap()).unwrap();
+    let summary = replay.run(WordFilter::default());
+
+    assert!(summary.mismatches.is_empty(), "{} of {} entries differ", summary.mismatches.len(), summary.entries);
+    assert_eq!(summary.winner, winner);
+}
@@ -0,0 +1,50 @@
+use std::{fs, path::Path};
+
+use hide_and_seek::scenario::{self, parse_gpx, Scenario, ScenarioError};
+
+// Each tests/scenarios/<name>.scenario.json is replayed and compared with the
+// report recorded next to it.
+#[actix::test]
+async fn scenarios_match_their_reports() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/scenarios");
+    let mut checked = 0;
+
+    for entry in fs::read_dir(&dir).unwrap() {
+        let path = entry.unwrap().path();
+        let name = match path.file_name().and_then(|name| name.to_str()?.strip_suffix(".scenario.json")) {
+            Some(name) => name.to_string(),
+            None => continue,
+        };
+
+        let report = scenario::run(&Scenario::load(&path).unwrap()).await.unwrap();
+        let actual = serde_json::to_string_pretty(&report).unwrap() + "\n";
+        let expected = fs::read_to_string(dir.join(format!("{}.report.json", name))).unwrap_or_default();
+
+        assert!(
+            actual == expected,
+            "tests/scenarios/{0}.report.json is stale, run `cargo run --bin scenario -- tests/scenarios/{0}.scenario.json tests/scenarios/{0}.report.json` in backend/ after checking the changes",
+            name,
+        );
+
+        checked += 1;
+    }
+
+    assert!(checked > 0);
+}
+
+#[test]
+fn gpx_points_without_time_are_skipped_only_when_self_closing() {
+    let timed = |lat| format!(r#"<trkpt lat="{}" lon="8.54"><time>2024-05-01T10:00:0{}Z</time></trkpt>"#, lat, lat);
+    let gpx = format!(r#"<gpx><trk><trkseg>{}<trkpt lat="1" lon="8.54"/>{}</trkseg></trk></gpx>"#, timed(0), timed(2));
+
+    let points = parse_gpx(&gpx).unwrap();
+    assert_eq!(points.iter().map(|(at, _)| *at).collect::<Vec<_>>(), [0, 2]);
+
+    let error = |gpx: &str| match parse_gpx(gpx) {
+        Err(ScenarioError::Gpx(message)) => message,
+        result => panic!("unexpected result {:?}", result),
+    };
+
+    assert_eq!(error(r#"<trkpt lat="0" lon="8.54"></trkpt>"#), "Track point without time");
+    assert_eq!(error(r#"<trkpt lat="0" lon="8.54"><time>2024-05-01T10:00:00Z</time>"#), "Track point without </trkpt>");
+}
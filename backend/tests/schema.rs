@@ -0,0 +1,18 @@
+use std::{fs, path::Path};
+
+use hide_and_seek::codegen;
+
+#[test]
+fn generated_files_are_up_to_date() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+
+    for (path, expected) in codegen::outputs() {
+        let actual = fs::read_to_string(root.join(path)).unwrap_or_default();
+
+        assert!(
+            actual == expected,
+            "{} is stale, run `cargo run --bin codegen` in backend/ to regenerate",
+            path,
+        );
+    }
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use actix_web::{http::StatusCode, test::{call_service, init_service, TestRequest}, web, App};
+
+use hide_and_seek::{message::*, scenario::parse_gpx, tracks::{self, TrackStore}};
+
+use common::{send, temp_dir, Harness};
+
+#[actix::test]
+async fn finished_games_export_their_tracks() {
+    let tracks = TrackStore::new(temp_dir("tracks")).unwrap();
+    let harness = Harness::with("tracks_images", 3, |server| server.with_tracks(tracks.clone()));
+
+    let host = harness.connect("host").await;
+    let guest = harness.connect("guest & co").await;
+    let players = [host.id, guest.id];
+
+    let new_game = NewGame { player: host.id, x: 0.0, y: 0.0, minutes: 1, tag_mode: TagMode::Instant, power_ups: false, radius: Some(150.0) };
+    let (game, game_id) = match harness.server.send(new_game).await.unwrap() {
+        Ok((game, ServerEvent::JoinedGame { id, .. })) => (game, id),
+        _ => panic!("game wasn't created"),
+    };
+    harness.join(&guest, game_id).await;
+
+    // only positions while the game runs are kept
+    send(&game, &host, ClientEvent::UpdatePosition { x: 0.001, y: 0.0 }).await;
+    send(&game, &host, ClientEvent::UpdatePosition { x: 0.0, y: 0.0 }).await;
+    send(&game, &host, ClientEvent::StartGame).await;
+
+    harness.run_out(&game, 61, |second| {
+        let mut events: Vec<_> = players.iter().enumerate().map(|(index, &player)| {
+            (player, ClientEvent::UpdatePosition { x: 0.00001 * second as f64, y: 0.0001 * index as f64 })
+        }).collect();
+
+        // whoever isn't the seeker answers with an error
+        if second == 10 {
+            events.push((players[0], ClientEvent::TagPlayer { player: players[1], image: None }));
+            events.push((players[1], ClientEvent::TagPlayer { player: players[0], image: None }));
+        }
+
+        events
+    }).await;
+
+    let token = guest.events().into_iter().find_map(|event| match event {
+        ServerEvent::GameEnded { download_token, .. } => download_token,
+        _ => None,
+    }).expect("no download token");
+
+    // the game id alone doesn't get anyone the tracks
+    let app = init_service(App::new()
+        .app_data(web::Data::new(tracks.clone()))
+        .service(tracks::download_geojson)
+        .service(tracks::download_gpx)).await;
+    let download = |path: String| TestRequest::get().uri(&path).to_request();
+
+    assert_eq!(call_service(&app, download(format!("/tracks/{}?token={}", game_id, token))).await.status(), StatusCode::OK);
+    assert_eq!(call_service(&app, download(format!("/tracks/{}/{}?token={}", game_id, guest.id, token))).await.status(), StatusCode::OK);
+    assert_eq!(call_service(&app, download(format!("/tracks/{}?token=guessed", game_id))).await.status(), StatusCode::NOT_FOUND);
+    assert_eq!(call_service(&app, download(format!("/tracks/{}/{}", game_id, guest.id))).await.status(), StatusCode::BAD_REQUEST);
+
+    let saved = tracks.find(game_id, &token).expect("tracks weren't saved");
+    assert_eq!(saved.players.len(), 2);
+    assert_eq!(saved.tags.len(), 1);
+    assert!(saved.tags[0].pos.is_some());
+
+    // the position from before the start, then one per second
+    let host = saved.players.iter().find(|track| track.id == players[0]).unwrap();
+    assert_eq!(host.points.len(), 61);
+    assert_eq!((host.points[0].x, host.points[0].y), (0.0, 0.0));
+
+    let geojson = saved.geojson();
+    let kinds: Vec<&str> = geojson["features"].as_array().unwrap().iter()
+        .map(|feature| feature["properties"]["kind"].as_str().unwrap())
+        .collect();
+    assert_eq!(kinds, ["center", "play_area", "track", "track", "tag"]);
+
+    let gpx = saved.gpx(players[1]).unwrap();
+    assert!(gpx.contains("<name>guest &amp; co</name>"));
+    assert_eq!(parse_gpx(&gpx).unwrap().len(), 60);
+    assert!(saved.gpx(12345).is_none());
+}
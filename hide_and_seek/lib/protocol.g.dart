@@ -0,0 +1,916 @@
+// GENERATED CODE - DO NOT EDIT.
+// Run `cargo run --bin codegen` in backend/ to regenerate.
+// ignore_for_file: constant_identifier_names
+
+sealed class ClientEvent {
+  const ClientEvent();
+
+  static ClientEvent fromJson(dynamic json) {
+    if (json is String) {
+      switch (json) {
+        case 'LeaveGame': return const ClientEventLeaveGame();
+        case 'StartGame': return const ClientEventStartGame();
+        case 'ConfirmTag': return const ClientEventConfirmTag();
+        case 'DisputeTag': return const ClientEventDisputeTag();
+        case 'ResyncScores': return const ClientEventResyncScores();
+      }
+    } else if (json is Map<String, dynamic> && json.length == 1) {
+      final MapEntry(:key, :value) = json.entries.first;
+      switch (key) {
+        case 'LeaveGame': return const ClientEventLeaveGame();
+        case 'StartGame': return const ClientEventStartGame();
+        case 'ConfirmTag': return const ClientEventConfirmTag();
+        case 'DisputeTag': return const ClientEventDisputeTag();
+        case 'ResyncScores': return const ClientEventResyncScores();
+        case 'Connect': return ClientEventConnect.fromJson(value as Map<String, dynamic>);
+        case 'Chat': return ClientEventChat.fromJson(value as Map<String, dynamic>);
+        case 'JoinGame': return ClientEventJoinGame.fromJson(value as Map<String, dynamic>);
+        case 'CreateGame': return ClientEventCreateGame.fromJson(value as Map<String, dynamic>);
+        case 'MutePlayer': return ClientEventMutePlayer.fromJson(value as Map<String, dynamic>);
+        case 'UnmutePlayer': return ClientEventUnmutePlayer.fromJson(value as Map<String, dynamic>);
+        case 'AddBot': return ClientEventAddBot.fromJson(value as Map<String, dynamic>);
+        case 'RemoveBot': return ClientEventRemoveBot.fromJson(value as Map<String, dynamic>);
+        case 'UpdatePosition': return ClientEventUpdatePosition.fromJson(value as Map<String, dynamic>);
+        case 'TagPlayer': return ClientEventTagPlayer.fromJson(value as Map<String, dynamic>);
+        case 'TagWithProof': return ClientEventTagWithProof.fromJson(value as Map<String, dynamic>);
+      }
+    }
+
+    throw ArgumentError('Unknown ClientEvent: $json');
+  }
+
+  dynamic toJson();
+}
+
+class ClientEventLeaveGame extends ClientEvent {
+  const ClientEventLeaveGame();
+
+  @override
+  dynamic toJson() => 'LeaveGame';
+}
+
+class ClientEventStartGame extends ClientEvent {
+  const ClientEventStartGame();
+
+  @override
+  dynamic toJson() => 'StartGame';
+}
+
+class ClientEventConfirmTag extends ClientEvent {
+  const ClientEventConfirmTag();
+
+  @override
+  dynamic toJson() => 'ConfirmTag';
+}
+
+class ClientEventDisputeTag extends ClientEvent {
+  const ClientEventDisputeTag();
+
+  @override
+  dynamic toJson() => 'DisputeTag';
+}
+
+class ClientEventResyncScores extends ClientEvent {
+  const ClientEventResyncScores();
+
+  @override
+  dynamic toJson() => 'ResyncScores';
+}
+
+class ClientEventConnect extends ClientEvent {
+  final String name;
+  final int version;
+  final Encoding encoding;
+  final bool scoreDeltas;
+  final String? resume;
+
+  const ClientEventConnect({required this.name, this.version = 1, this.encoding = Encoding.Json, this.scoreDeltas = false, this.resume});
+
+  factory ClientEventConnect.fromJson(Map<String, dynamic> json) => ClientEventConnect(
+    name: json['name'] as String,
+    version: json.containsKey('version') ? (json['version'] as num).toInt() : 1,
+    encoding: json.containsKey('encoding') ? Encoding.fromJson(json['encoding']) : Encoding.Json,
+    scoreDeltas: json.containsKey('score_deltas') ? json['score_deltas'] as bool : false,
+    resume: json['resume'] == null ? null : json['resume'] as String,
+  );
+
+  @override
+  dynamic toJson() => {'Connect': {'name': name, 'version': version, 'encoding': encoding.toJson(), 'score_deltas': scoreDeltas, 'resume': resume}};
+}
+
+class ClientEventChat extends ClientEvent {
+  final String message;
+  final String? image;
+  final ChatChannel channel;
+
+  const ClientEventChat({required this.message, this.image, this.channel = const ChatChannelAll()});
+
+  factory ClientEventChat.fromJson(Map<String, dynamic> json) => ClientEventChat(
+    message: json['message'] as String,
+    image: json['image'] == null ? null : json['image'] as String,
+    channel: json.containsKey('channel') ? ChatChannel.fromJson(json['channel']) : const ChatChannelAll(),
+  );
+
+  @override
+  dynamic toJson() => {'Chat': {'message': message, 'image': image, 'channel': channel.toJson()}};
+}
+
+class ClientEventJoinGame extends ClientEvent {
+  final int game;
+
+  const ClientEventJoinGame({required this.game});
+
+  factory ClientEventJoinGame.fromJson(Map<String, dynamic> json) => ClientEventJoinGame(
+    game: (json['game'] as num).toInt(),
+  );
+
+  @override
+  dynamic toJson() => {'JoinGame': {'game': game}};
+}
+
+class ClientEventCreateGame extends ClientEvent {
+  final double x;
+  final double y;
+  final int minutes;
+  final TagMode tagMode;
+  final bool powerUps;
+  final double? radius;
+
+  const ClientEventCreateGame({required this.x, required this.y, required this.minutes, this.tagMode = TagMode.Instant, this.powerUps = false, this.radius});
+
+  factory ClientEventCreateGame.fromJson(Map<String, dynamic> json) => ClientEventCreateGame(
+    x: (json['x'] as num).toDouble(),
+    y: (json['y'] as num).toDouble(),
+    minutes: (json['minutes'] as num).toInt(),
+    tagMode: json.containsKey('tag_mode') ? TagMode.fromJson(json['tag_mode']) : TagMode.Instant,
+    powerUps: json.containsKey('power_ups') ? json['power_ups'] as bool : false,
+    radius: json['radius'] == null ? null : (json['radius'] as num).toDouble(),
+  );
+
+  @override
+  dynamic toJson() => {'CreateGame': {'x': x, 'y': y, 'minutes': minutes, 'tag_mode': tagMode.toJson(), 'power_ups': powerUps, 'radius': radius}};
+}
+
+class ClientEventMutePlayer extends ClientEvent {
+  final int player;
+
+  const ClientEventMutePlayer({required this.player});
+
+  factory ClientEventMutePlayer.fromJson(Map<String, dynamic> json) => ClientEventMutePlayer(
+    player: (json['player'] as num).toInt(),
+  );
+
+  @override
+  dynamic toJson() => {'MutePlayer': {'player': player}};
+}
+
+class ClientEventUnmutePlayer extends ClientEvent {
+  final int player;
+
+  const ClientEventUnmutePlayer({required this.player});
+
+  factory ClientEventUnmutePlayer.fromJson(Map<String, dynamic> json) => ClientEventUnmutePlayer(
+    player: (json['player'] as num).toInt(),
+  );
+
+  @override
+  dynamic toJson() => {'UnmutePlayer': {'player': player}};
+}
+
+class ClientEventAddBot extends ClientEvent {
+  final Difficulty difficulty;
+  final List<(double, double)> path;
+
+  const ClientEventAddBot({this.difficulty = Difficulty.Medium, this.path = []});
+
+  factory ClientEventAddBot.fromJson(Map<String, dynamic> json) => ClientEventAddBot(
+    difficulty: json.containsKey('difficulty') ? Difficulty.fromJson(json['difficulty']) : Difficulty.Medium,
+    path: json.containsKey('path') ? (json['path'] as List).map((e0) => (((e0 as List)[0] as num).toDouble(), ((e0 as List)[1] as num).toDouble())).toList() : [],
+  );
+
+  @override
+  dynamic toJson() => {'AddBot': {'difficulty': difficulty.toJson(), 'path': path.map((e0) => [e0.$1, e0.$2]).toList()}};
+}
+
+class ClientEventRemoveBot extends ClientEvent {
+  final int player;
+
+  const ClientEventRemoveBot({required this.player});
+
+  factory ClientEventRemoveBot.fromJson(Map<String, dynamic> json) => ClientEventRemoveBot(
+    player: (json['player'] as num).toInt(),
+  );
+
+  @override
+  dynamic toJson() => {'RemoveBot': {'player': player}};
+}
+
+class ClientEventUpdatePosition extends ClientEvent {
+  final double x;
+  final double y;
+
+  const ClientEventUpdatePosition({required this.x, required this.y});
+
+  factory ClientEventUpdatePosition.fromJson(Map<String, dynamic> json) => ClientEventUpdatePosition(
+    x: (json['x'] as num).toDouble(),
+    y: (json['y'] as num).toDouble(),
+  );
+
+  @override
+  dynamic toJson() => {'UpdatePosition': {'x': x, 'y': y}};
+}
+
+class ClientEventTagPlayer extends ClientEvent {
+  final int player;
+  final String? image;
+
+  const ClientEventTagPlayer({required this.player, this.image});
+
+  factory ClientEventTagPlayer.fromJson(Map<String, dynamic> json) => ClientEventTagPlayer(
+    player: (json['player'] as num).toInt(),
+    image: json['image'] == null ? null : json['image'] as String,
+  );
+
+  @override
+  dynamic toJson() => {'TagPlayer': {'player': player, 'image': image}};
+}
+
+class ClientEventTagWithProof extends ClientEvent {
+  final String nonce;
+
+  const ClientEventTagWithProof({required this.nonce});
+
+  factory ClientEventTagWithProof.fromJson(Map<String, dynamic> json) => ClientEventTagWithProof(
+    nonce: json['nonce'] as String,
+  );
+
+  @override
+  dynamic toJson() => {'TagWithProof': {'nonce': nonce}};
+}
+
+enum Encoding {
+  Json,
+  MessagePack;
+
+  static Encoding fromJson(dynamic json) => values.byName(json as String);
+
+  dynamic toJson() => name;
+}
+
+sealed class ChatChannel {
+  const ChatChannel();
+
+  static ChatChannel fromJson(dynamic json) {
+    if (json is String) {
+      switch (json) {
+        case 'All': return const ChatChannelAll();
+        case 'Team': return const ChatChannelTeam();
+        case 'Hiders': return const ChatChannelHiders();
+        case 'Seeker': return const ChatChannelSeeker();
+      }
+    } else if (json is Map<String, dynamic> && json.length == 1) {
+      final MapEntry(:key, :value) = json.entries.first;
+      switch (key) {
+        case 'All': return const ChatChannelAll();
+        case 'Team': return const ChatChannelTeam();
+        case 'Hiders': return const ChatChannelHiders();
+        case 'Seeker': return const ChatChannelSeeker();
+        case 'Direct': return ChatChannelDirect((value as num).toInt());
+      }
+    }
+
+    throw ArgumentError('Unknown ChatChannel: $json');
+  }
+
+  dynamic toJson();
+}
+
+class ChatChannelAll extends ChatChannel {
+  const ChatChannelAll();
+
+  @override
+  dynamic toJson() => 'All';
+}
+
+class ChatChannelTeam extends ChatChannel {
+  const ChatChannelTeam();
+
+  @override
+  dynamic toJson() => 'Team';
+}
+
+class ChatChannelHiders extends ChatChannel {
+  const ChatChannelHiders();
+
+  @override
+  dynamic toJson() => 'Hiders';
+}
+
+class ChatChannelSeeker extends ChatChannel {
+  const ChatChannelSeeker();
+
+  @override
+  dynamic toJson() => 'Seeker';
+}
+
+class ChatChannelDirect extends ChatChannel {
+  final int value;
+
+  const ChatChannelDirect(this.value);
+
+  @override
+  dynamic toJson() => {'Direct': value};
+}
+
+enum TagMode {
+  Instant,
+  Photo,
+  Handshake;
+
+  static TagMode fromJson(dynamic json) => values.byName(json as String);
+
+  dynamic toJson() => name;
+}
+
+enum Difficulty {
+  Easy,
+  Medium,
+  Hard;
+
+  static Difficulty fromJson(dynamic json) => values.byName(json as String);
+
+  dynamic toJson() => name;
+}
+
+sealed class ServerEvent {
+  const ServerEvent();
+
+  static ServerEvent fromJson(dynamic json) {
+    if (json is String) {
+      switch (json) {
+        case 'LeftGame': return const ServerEventLeftGame();
+      }
+    } else if (json is Map<String, dynamic> && json.length == 1) {
+      final MapEntry(:key, :value) = json.entries.first;
+      switch (key) {
+        case 'LeftGame': return const ServerEventLeftGame();
+        case 'Connected': return ServerEventConnected.fromJson(value as Map<String, dynamic>);
+        case 'IncompatibleProtocol': return ServerEventIncompatibleProtocol.fromJson(value as Map<String, dynamic>);
+        case 'Chat': return ServerEventChat.fromJson(value as Map<String, dynamic>);
+        case 'Error': return ServerEventError.fromJson(value as Map<String, dynamic>);
+        case 'Ack': return ServerEventAck.fromJson(value as Map<String, dynamic>);
+        case 'JoinedGame': return ServerEventJoinedGame.fromJson(value as Map<String, dynamic>);
+        case 'PlayerJoined': return ServerEventPlayerJoined.fromJson(value as Map<String, dynamic>);
+        case 'PlayerLeft': return ServerEventPlayerLeft.fromJson(value as Map<String, dynamic>);
+        case 'PlayerMuted': return ServerEventPlayerMuted.fromJson(value as Map<String, dynamic>);
+        case 'GameStarted': return ServerEventGameStarted.fromJson(value as Map<String, dynamic>);
+        case 'PlayerTagged': return ServerEventPlayerTagged.fromJson(value as Map<String, dynamic>);
+        case 'TagPending': return ServerEventTagPending.fromJson(value as Map<String, dynamic>);
+        case 'TagDisputed': return ServerEventTagDisputed.fromJson(value as Map<String, dynamic>);
+        case 'TagNonce': return ServerEventTagNonce.fromJson(value as Map<String, dynamic>);
+        case 'ScoreUpdate': return ServerEventScoreUpdate.fromJson(value as Map<String, dynamic>);
+        case 'ScoreSnapshot': return ServerEventScoreSnapshot.fromJson(value as Map<String, dynamic>);
+        case 'ScoreDelta': return ServerEventScoreDelta.fromJson(value as Map<String, dynamic>);
+        case 'PowerUpSpawned': return ServerEventPowerUpSpawned.fromJson(value as Map<String, dynamic>);
+        case 'PowerUpCollected': return ServerEventPowerUpCollected.fromJson(value as Map<String, dynamic>);
+        case 'PowerUpExpired': return ServerEventPowerUpExpired.fromJson(value as Map<String, dynamic>);
+        case 'RadarPing': return ServerEventRadarPing.fromJson(value as Map<String, dynamic>);
+        case 'ShieldBroken': return ServerEventShieldBroken.fromJson(value as Map<String, dynamic>);
+        case 'GameEnded': return ServerEventGameEnded.fromJson(value as Map<String, dynamic>);
+      }
+    }
+
+    throw ArgumentError('Unknown ServerEvent: $json');
+  }
+
+  dynamic toJson();
+}
+
+class ServerEventLeftGame extends ServerEvent {
+  const ServerEventLeftGame();
+
+  @override
+  dynamic toJson() => 'LeftGame';
+}
+
+class ServerEventConnected extends ServerEvent {
+  final int id;
+  final int version;
+  final int minVersion;
+  final int maxVersion;
+  final String token;
+
+  const ServerEventConnected({required this.id, required this.version, required this.minVersion, required this.maxVersion, required this.token});
+
+  factory ServerEventConnected.fromJson(Map<String, dynamic> json) => ServerEventConnected(
+    id: (json['id'] as num).toInt(),
+    version: (json['version'] as num).toInt(),
+    minVersion: (json['min_version'] as num).toInt(),
+    maxVersion: (json['max_version'] as num).toInt(),
+    token: json['token'] as String,
+  );
+
+  @override
+  dynamic toJson() => {'Connected': {'id': id, 'version': version, 'min_version': minVersion, 'max_version': maxVersion, 'token': token}};
+}
+
+class ServerEventIncompatibleProtocol extends ServerEvent {
+  final int minVersion;
+  final int maxVersion;
+
+  const ServerEventIncompatibleProtocol({required this.minVersion, required this.maxVersion});
+
+  factory ServerEventIncompatibleProtocol.fromJson(Map<String, dynamic> json) => ServerEventIncompatibleProtocol(
+    minVersion: (json['min_version'] as num).toInt(),
+    maxVersion: (json['max_version'] as num).toInt(),
+  );
+
+  @override
+  dynamic toJson() => {'IncompatibleProtocol': {'min_version': minVersion, 'max_version': maxVersion}};
+}
+
+class ServerEventChat extends ServerEvent {
+  final int sender;
+  final String message;
+  final String? image;
+  final ChatChannel channel;
+
+  const ServerEventChat({required this.sender, required this.message, this.image, required this.channel});
+
+  factory ServerEventChat.fromJson(Map<String, dynamic> json) => ServerEventChat(
+    sender: (json['sender'] as num).toInt(),
+    message: json['message'] as String,
+    image: json['image'] == null ? null : json['image'] as String,
+    channel: ChatChannel.fromJson(json['channel']),
+  );
+
+  @override
+  dynamic toJson() => {'Chat': {'sender': sender, 'message': message, 'image': image, 'channel': channel.toJson()}};
+}
+
+class ServerEventError extends ServerEvent {
+  final ErrorCode code;
+  final String message;
+  final ErrorDetails? details;
+  final int? requestId;
+
+  const ServerEventError({required this.code, required this.message, this.details, this.requestId});
+
+  factory ServerEventError.fromJson(Map<String, dynamic> json) => ServerEventError(
+    code: ErrorCode.fromJson(json['code']),
+    message: json['message'] as String,
+    details: json['details'] == null ? null : ErrorDetails.fromJson(json['details']),
+    requestId: json['request_id'] == null ? null : (json['request_id'] as num).toInt(),
+  );
+
+  @override
+  dynamic toJson() => {'Error': {'code': code.toJson(), 'message': message, 'details': details == null ? null : details!.toJson(), 'request_id': requestId}};
+}
+
+class ServerEventAck extends ServerEvent {
+  final int requestId;
+
+  const ServerEventAck({required this.requestId});
+
+  factory ServerEventAck.fromJson(Map<String, dynamic> json) => ServerEventAck(
+    requestId: (json['request_id'] as num).toInt(),
+  );
+
+  @override
+  dynamic toJson() => {'Ack': {'request_id': requestId}};
+}
+
+class ServerEventJoinedGame extends ServerEvent {
+  final int id;
+  final double x;
+  final double y;
+  final List<(int, String)> players;
+  final int host;
+  final TagMode tagMode;
+  final List<ChatEntry> chat;
+
+  const ServerEventJoinedGame({required this.id, required this.x, required this.y, required this.players, required this.host, required this.tagMode, required this.chat});
+
+  factory ServerEventJoinedGame.fromJson(Map<String, dynamic> json) => ServerEventJoinedGame(
+    id: (json['id'] as num).toInt(),
+    x: (json['x'] as num).toDouble(),
+    y: (json['y'] as num).toDouble(),
+    players: (json['players'] as List).map((e0) => (((e0 as List)[0] as num).toInt(), (e0 as List)[1] as String)).toList(),
+    host: (json['host'] as num).toInt(),
+    tagMode: TagMode.fromJson(json['tag_mode']),
+    chat: (json['chat'] as List).map((e0) => ChatEntry.fromJson(e0)).toList(),
+  );
+
+  @override
+  dynamic toJson() => {'JoinedGame': {'id': id, 'x': x, 'y': y, 'players': players.map((e0) => [e0.$1, e0.$2]).toList(), 'host': host, 'tag_mode': tagMode.toJson(), 'chat': chat.map((e0) => e0.toJson()).toList()}};
+}
+
+class ServerEventPlayerJoined extends ServerEvent {
+  final int id;
+  final String name;
+
+  const ServerEventPlayerJoined({required this.id, required this.name});
+
+  factory ServerEventPlayerJoined.fromJson(Map<String, dynamic> json) => ServerEventPlayerJoined(
+    id: (json['id'] as num).toInt(),
+    name: json['name'] as String,
+  );
+
+  @override
+  dynamic toJson() => {'PlayerJoined': {'id': id, 'name': name}};
+}
+
+class ServerEventPlayerLeft extends ServerEvent {
+  final int id;
+  final int newHost;
+
+  const ServerEventPlayerLeft({required this.id, required this.newHost});
+
+  factory ServerEventPlayerLeft.fromJson(Map<String, dynamic> json) => ServerEventPlayerLeft(
+    id: (json['id'] as num).toInt(),
+    newHost: (json['new_host'] as num).toInt(),
+  );
+
+  @override
+  dynamic toJson() => {'PlayerLeft': {'id': id, 'new_host': newHost}};
+}
+
+class ServerEventPlayerMuted extends ServerEvent {
+  final int id;
+  final bool muted;
+
+  const ServerEventPlayerMuted({required this.id, required this.muted});
+
+  factory ServerEventPlayerMuted.fromJson(Map<String, dynamic> json) => ServerEventPlayerMuted(
+    id: (json['id'] as num).toInt(),
+    muted: json['muted'] as bool,
+  );
+
+  @override
+  dynamic toJson() => {'PlayerMuted': {'id': id, 'muted': muted}};
+}
+
+class ServerEventGameStarted extends ServerEvent {
+  final int seeker;
+
+  const ServerEventGameStarted({required this.seeker});
+
+  factory ServerEventGameStarted.fromJson(Map<String, dynamic> json) => ServerEventGameStarted(
+    seeker: (json['seeker'] as num).toInt(),
+  );
+
+  @override
+  dynamic toJson() => {'GameStarted': {'seeker': seeker}};
+}
+
+class ServerEventPlayerTagged extends ServerEvent {
+  final int tagger;
+  final int tagged;
+
+  const ServerEventPlayerTagged({required this.tagger, required this.tagged});
+
+  factory ServerEventPlayerTagged.fromJson(Map<String, dynamic> json) => ServerEventPlayerTagged(
+    tagger: (json['tagger'] as num).toInt(),
+    tagged: (json['tagged'] as num).toInt(),
+  );
+
+  @override
+  dynamic toJson() => {'PlayerTagged': {'tagger': tagger, 'tagged': tagged}};
+}
+
+class ServerEventTagPending extends ServerEvent {
+  final int tagger;
+  final int tagged;
+  final String image;
+
+  const ServerEventTagPending({required this.tagger, required this.tagged, required this.image});
+
+  factory ServerEventTagPending.fromJson(Map<String, dynamic> json) => ServerEventTagPending(
+    tagger: (json['tagger'] as num).toInt(),
+    tagged: (json['tagged'] as num).toInt(),
+    image: json['image'] as String,
+  );
+
+  @override
+  dynamic toJson() => {'TagPending': {'tagger': tagger, 'tagged': tagged, 'image': image}};
+}
+
+class ServerEventTagDisputed extends ServerEvent {
+  final int tagger;
+  final int tagged;
+  final int by;
+
+  const ServerEventTagDisputed({required this.tagger, required this.tagged, required this.by});
+
+  factory ServerEventTagDisputed.fromJson(Map<String, dynamic> json) => ServerEventTagDisputed(
+    tagger: (json['tagger'] as num).toInt(),
+    tagged: (json['tagged'] as num).toInt(),
+    by: (json['by'] as num).toInt(),
+  );
+
+  @override
+  dynamic toJson() => {'TagDisputed': {'tagger': tagger, 'tagged': tagged, 'by': by}};
+}
+
+class ServerEventTagNonce extends ServerEvent {
+  final String nonce;
+  final int expiresIn;
+
+  const ServerEventTagNonce({required this.nonce, required this.expiresIn});
+
+  factory ServerEventTagNonce.fromJson(Map<String, dynamic> json) => ServerEventTagNonce(
+    nonce: json['nonce'] as String,
+    expiresIn: (json['expires_in'] as num).toInt(),
+  );
+
+  @override
+  dynamic toJson() => {'TagNonce': {'nonce': nonce, 'expires_in': expiresIn}};
+}
+
+class ServerEventScoreUpdate extends ServerEvent {
+  final Map<String, double> scores;
+  final int secondsLeft;
+
+  const ServerEventScoreUpdate({required this.scores, required this.secondsLeft});
+
+  factory ServerEventScoreUpdate.fromJson(Map<String, dynamic> json) => ServerEventScoreUpdate(
+    scores: (json['scores'] as Map<String, dynamic>).map((k, e0) => MapEntry(k, (e0 as num).toDouble())),
+    secondsLeft: (json['seconds_left'] as num).toInt(),
+  );
+
+  @override
+  dynamic toJson() => {'ScoreUpdate': {'scores': scores, 'seconds_left': secondsLeft}};
+}
+
+class ServerEventScoreSnapshot extends ServerEvent {
+  final int seq;
+  final Map<String, double> scores;
+  final int secondsLeft;
+
+  const ServerEventScoreSnapshot({required this.seq, required this.scores, required this.secondsLeft});
+
+  factory ServerEventScoreSnapshot.fromJson(Map<String, dynamic> json) => ServerEventScoreSnapshot(
+    seq: (json['seq'] as num).toInt(),
+    scores: (json['scores'] as Map<String, dynamic>).map((k, e0) => MapEntry(k, (e0 as num).toDouble())),
+    secondsLeft: (json['seconds_left'] as num).toInt(),
+  );
+
+  @override
+  dynamic toJson() => {'ScoreSnapshot': {'seq': seq, 'scores': scores, 'seconds_left': secondsLeft}};
+}
+
+class ServerEventScoreDelta extends ServerEvent {
+  final int seq;
+  final Map<String, double> changed;
+  final int secondsLeft;
+
+  const ServerEventScoreDelta({required this.seq, required this.changed, required this.secondsLeft});
+
+  factory ServerEventScoreDelta.fromJson(Map<String, dynamic> json) => ServerEventScoreDelta(
+    seq: (json['seq'] as num).toInt(),
+    changed: (json['changed'] as Map<String, dynamic>).map((k, e0) => MapEntry(k, (e0 as num).toDouble())),
+    secondsLeft: (json['seconds_left'] as num).toInt(),
+  );
+
+  @override
+  dynamic toJson() => {'ScoreDelta': {'seq': seq, 'changed': changed, 'seconds_left': secondsLeft}};
+}
+
+class ServerEventPowerUpSpawned extends ServerEvent {
+  final int id;
+  final PowerUp kind;
+  final double x;
+  final double y;
+  final int expiresIn;
+
+  const ServerEventPowerUpSpawned({required this.id, required this.kind, required this.x, required this.y, required this.expiresIn});
+
+  factory ServerEventPowerUpSpawned.fromJson(Map<String, dynamic> json) => ServerEventPowerUpSpawned(
+    id: (json['id'] as num).toInt(),
+    kind: PowerUp.fromJson(json['kind']),
+    x: (json['x'] as num).toDouble(),
+    y: (json['y'] as num).toDouble(),
+    expiresIn: (json['expires_in'] as num).toInt(),
+  );
+
+  @override
+  dynamic toJson() => {'PowerUpSpawned': {'id': id, 'kind': kind.toJson(), 'x': x, 'y': y, 'expires_in': expiresIn}};
+}
+
+class ServerEventPowerUpCollected extends ServerEvent {
+  final int id;
+  final PowerUp kind;
+  final int player;
+  final int? expiresIn;
+
+  const ServerEventPowerUpCollected({required this.id, required this.kind, required this.player, this.expiresIn});
+
+  factory ServerEventPowerUpCollected.fromJson(Map<String, dynamic> json) => ServerEventPowerUpCollected(
+    id: (json['id'] as num).toInt(),
+    kind: PowerUp.fromJson(json['kind']),
+    player: (json['player'] as num).toInt(),
+    expiresIn: json['expires_in'] == null ? null : (json['expires_in'] as num).toInt(),
+  );
+
+  @override
+  dynamic toJson() => {'PowerUpCollected': {'id': id, 'kind': kind.toJson(), 'player': player, 'expires_in': expiresIn}};
+}
+
+class ServerEventPowerUpExpired extends ServerEvent {
+  final int id;
+
+  const ServerEventPowerUpExpired({required this.id});
+
+  factory ServerEventPowerUpExpired.fromJson(Map<String, dynamic> json) => ServerEventPowerUpExpired(
+    id: (json['id'] as num).toInt(),
+  );
+
+  @override
+  dynamic toJson() => {'PowerUpExpired': {'id': id}};
+}
+
+class ServerEventRadarPing extends ServerEvent {
+  final List<(int, double, double)> positions;
+
+  const ServerEventRadarPing({required this.positions});
+
+  factory ServerEventRadarPing.fromJson(Map<String, dynamic> json) => ServerEventRadarPing(
+    positions: (json['positions'] as List).map((e0) => (((e0 as List)[0] as num).toInt(), ((e0 as List)[1] as num).toDouble(), ((e0 as List)[2] as num).toDouble())).toList(),
+  );
+
+  @override
+  dynamic toJson() => {'RadarPing': {'positions': positions.map((e0) => [e0.$1, e0.$2, e0.$3]).toList()}};
+}
+
+class ServerEventShieldBroken extends ServerEvent {
+  final int player;
+  final int tagger;
+
+  const ServerEventShieldBroken({required this.player, required this.tagger});
+
+  factory ServerEventShieldBroken.fromJson(Map<String, dynamic> json) => ServerEventShieldBroken(
+    player: (json['player'] as num).toInt(),
+    tagger: (json['tagger'] as num).toInt(),
+  );
+
+  @override
+  dynamic toJson() => {'ShieldBroken': {'player': player, 'tagger': tagger}};
+}
+
+class ServerEventGameEnded extends ServerEvent {
+  final int winner;
+  final String? downloadToken;
+
+  const ServerEventGameEnded({required this.winner, this.downloadToken});
+
+  factory ServerEventGameEnded.fromJson(Map<String, dynamic> json) => ServerEventGameEnded(
+    winner: (json['winner'] as num).toInt(),
+    downloadToken: json['download_token'] == null ? null : json['download_token'] as String,
+  );
+
+  @override
+  dynamic toJson() => {'GameEnded': {'winner': winner, 'download_token': downloadToken}};
+}
+
+enum ErrorCode {
+  InvalidMessage,
+  NotConnected,
+  AlreadyConnected,
+  ServerUnavailable,
+  ServerTimeout,
+  HeartbeatTimeout,
+  SlowClient,
+  PlayerNotFound,
+  NotInGame,
+  AlreadyInGame,
+  GameNotFound,
+  GameNotStarted,
+  GameAlreadyStarted,
+  GameAlreadyEnded,
+  NotHost,
+  NotEnoughPlayers,
+  TooManyBots,
+  BotsUnavailable,
+  InvalidPlayArea,
+  PlayAreaRequired,
+  MessageEmpty,
+  MessageTooLong,
+  RateLimited,
+  Muted,
+  ImageNotFound,
+  ChannelUnavailable,
+  ChannelForbidden,
+  NotSeeker,
+  InvalidTarget,
+  WrongTagMode,
+  TagAlreadyPending,
+  NoPendingTag,
+  NotReferee,
+  PhotoRequired,
+  InvalidNonce,
+  NonceExpired,
+  NonceUsed;
+
+  static ErrorCode fromJson(dynamic json) => values.byName(json as String);
+
+  dynamic toJson() => name;
+}
+
+sealed class ErrorDetails {
+  const ErrorDetails();
+
+  static ErrorDetails fromJson(dynamic json) {
+    if (json is String) {
+      switch (json) {
+      }
+    } else if (json is Map<String, dynamic> && json.length == 1) {
+      final MapEntry(:key, :value) = json.entries.first;
+      switch (key) {
+        case 'RetryAfter': return ErrorDetailsRetryAfter.fromJson(value as Map<String, dynamic>);
+        case 'MaxLength': return ErrorDetailsMaxLength.fromJson(value as Map<String, dynamic>);
+      }
+    }
+
+    throw ArgumentError('Unknown ErrorDetails: $json');
+  }
+
+  dynamic toJson();
+}
+
+class ErrorDetailsRetryAfter extends ErrorDetails {
+  final double seconds;
+
+  const ErrorDetailsRetryAfter({required this.seconds});
+
+  factory ErrorDetailsRetryAfter.fromJson(Map<String, dynamic> json) => ErrorDetailsRetryAfter(
+    seconds: (json['seconds'] as num).toDouble(),
+  );
+
+  @override
+  dynamic toJson() => {'RetryAfter': {'seconds': seconds}};
+}
+
+class ErrorDetailsMaxLength extends ErrorDetails {
+  final int max;
+
+  const ErrorDetailsMaxLength({required this.max});
+
+  factory ErrorDetailsMaxLength.fromJson(Map<String, dynamic> json) => ErrorDetailsMaxLength(
+    max: (json['max'] as num).toInt(),
+  );
+
+  @override
+  dynamic toJson() => {'MaxLength': {'max': max}};
+}
+
+class ChatEntry {
+  final int sender;
+  final String message;
+  final String? image;
+
+  const ChatEntry({required this.sender, required this.message, this.image});
+
+  factory ChatEntry.fromJson(Map<String, dynamic> json) => ChatEntry(
+    sender: (json['sender'] as num).toInt(),
+    message: json['message'] as String,
+    image: json['image'] == null ? null : json['image'] as String,
+  );
+
+  dynamic toJson() => {'sender': sender, 'message': message, 'image': image};
+}
+
+enum PowerUp {
+  ScoreBoost,
+  Invisibility,
+  Radar,
+  Shield;
+
+  static PowerUp fromJson(dynamic json) => values.byName(json as String);
+
+  dynamic toJson() => name;
+}
+
+class ClientRequest {
+  final ClientEvent event;
+  final int? requestId;
+
+  const ClientRequest(this.event, {this.requestId});
+
+  factory ClientRequest.fromJson(dynamic json) {
+    if (json is! Map<String, dynamic>) return ClientRequest(ClientEvent.fromJson(json));
+
+    final event = Map.of(json)..removeWhere((key, _) => ['request_id'].contains(key));
+    return ClientRequest(
+      ClientEvent.fromJson(event),
+      requestId: json['request_id'] == null ? null : (json['request_id'] as num).toInt(),
+    );
+  }
+
+  dynamic toJson() {
+    final event = this.event.toJson();
+    return <String, dynamic>{
+      ...(event is String ? {event: null} : event as Map<String, dynamic>),
+      if (requestId != null) 'request_id': requestId,
+    };
+  }
+}